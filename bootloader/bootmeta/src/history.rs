@@ -0,0 +1,294 @@
+use crc::{Crc, CRC_32_ISO_HDLC};
+
+use crate::BootBank;
+
+/// ブート履歴に用いる Backup SRAM の 32bit ワード数（4KB）
+pub const BOOT_HISTORY_WORDS: usize = 1024;
+
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+const RECORD_WORDS: usize = 4;
+
+/// ブートローダが行った動作
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BootAction {
+    /// アプリケーションを起動した
+    Boot = 0,
+    /// アプリケーションの要求によりバンクを切り替えた
+    Switch = 1,
+    /// 起動確認がない、またはイメージの検証に失敗したためにバンクを切り替えた
+    Rollback = 2,
+}
+
+impl BootAction {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(BootAction::Boot),
+            1 => Some(BootAction::Switch),
+            2 => Some(BootAction::Rollback),
+            _ => None,
+        }
+    }
+}
+
+/// ブートローダの 1 回の実行の記録
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BootRecord {
+    /// 通し番号
+    ///
+    /// [`BootHistory::push`] が振るため、構築時の値は無視される。
+    pub sequence: u32,
+    /// リセットフラグの生値
+    pub reset_flag: u32,
+    /// [`BootAction::Boot`] の場合は起動したバンク、それ以外の場合は切り替え先のバンク
+    pub bank: BootBank,
+    pub action: BootAction,
+    /// 起動確認のない連続した起動回数
+    pub boot_attempts: u16,
+}
+
+impl BootRecord {
+    /// 通し番号を除いた内容から記録を構築する
+    pub fn new(reset_flag: u32, bank: BootBank, action: BootAction, boot_attempts: u32) -> Self {
+        Self {
+            sequence: 0,
+            reset_flag,
+            bank,
+            action,
+            boot_attempts: u16::try_from(boot_attempts).unwrap_or(u16::MAX),
+        }
+    }
+
+    fn checksum(words: &[u32]) -> u32 {
+        let mut digest = CRC32.digest();
+        for word in words {
+            digest.update(&word.to_le_bytes());
+        }
+        digest.finalize()
+    }
+
+    fn encode(&self) -> [u32; RECORD_WORDS] {
+        let packed = u32::from(self.bank as u8)
+            | u32::from(self.action as u8) << 8
+            | u32::from(self.boot_attempts) << 16;
+        let words = [self.sequence, self.reset_flag, packed];
+        [words[0], words[1], words[2], Self::checksum(&words)]
+    }
+
+    fn decode(words: &[u32]) -> Option<Self> {
+        if words[3] != Self::checksum(&words[..3]) {
+            return None;
+        }
+        let [bank, action, attempts_lo, attempts_hi] = words[2].to_le_bytes();
+        let bank = match bank {
+            1 => BootBank::Bank1,
+            2 => BootBank::Bank2,
+            _ => return None,
+        };
+        Some(Self {
+            sequence: words[0],
+            reset_flag: words[1],
+            bank,
+            action: BootAction::from_u8(action)?,
+            boot_attempts: u16::from_le_bytes([attempts_lo, attempts_hi]),
+        })
+    }
+}
+
+/// CRC で保護された [`BootRecord`] のリングバッファ
+///
+/// 各スロットは独立に CRC を持ち、通し番号が最大のスロットを最新の記録とする。
+/// 書き込み中にリセットされても、そのスロット以外の記録は失われない。
+pub struct BootHistory<S> {
+    words: S,
+}
+
+impl<S: AsRef<[u32]>> BootHistory<S> {
+    /// `words` を記録領域とする `BootHistory` を構築する
+    ///
+    /// # Panics
+    /// `words` が 1 つの記録も保持できない場合は panic する。
+    pub fn new(words: S) -> Self {
+        assert!(words.as_ref().len() >= RECORD_WORDS);
+        Self { words }
+    }
+
+    /// 記録領域の生値
+    pub fn words(&self) -> &[u32] {
+        self.words.as_ref()
+    }
+
+    /// 保持できる記録の数
+    pub fn capacity(&self) -> usize {
+        self.words.as_ref().len() / RECORD_WORDS
+    }
+
+    fn slot(&self, slot: usize) -> Option<BootRecord> {
+        let start = slot * RECORD_WORDS;
+        BootRecord::decode(&self.words.as_ref()[start..start + RECORD_WORDS])
+    }
+
+    /// 最新の記録のスロットと記録
+    fn newest(&self) -> Option<(usize, BootRecord)> {
+        (0..self.capacity())
+            .filter_map(|slot| self.slot(slot).map(|record| (slot, record)))
+            .max_by_key(|(_, record)| record.sequence)
+    }
+
+    /// 新しいものから順に記録を返す
+    ///
+    /// 通し番号が連続していない記録は、それ以前の記録も含めて無効とみなす。
+    pub fn iter(&self) -> impl Iterator<Item = BootRecord> + '_ {
+        let capacity = self.capacity();
+        let mut next = self.newest();
+        (0..capacity).map_while(move |_| {
+            let (slot, record) = next?;
+            let prev_slot = (slot + capacity - 1) % capacity;
+            next = self
+                .slot(prev_slot)
+                .filter(|prev| prev.sequence == record.sequence.wrapping_sub(1))
+                .map(|prev| (prev_slot, prev));
+            Some(record)
+        })
+    }
+
+    /// 最新の記録から `index` 個遡った記録を返す
+    pub fn get(&self, index: usize) -> Option<BootRecord> {
+        self.iter().nth(index)
+    }
+
+    /// 読み出せる記録の数
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// 記録が 1 つもないかどうか
+    pub fn is_empty(&self) -> bool {
+        self.newest().is_none()
+    }
+}
+
+impl<S: AsRef<[u32]> + AsMut<[u32]>> BootHistory<S> {
+    /// 記録を追加し、振られた通し番号を返す
+    ///
+    /// 記録が一杯の場合は最も古い記録を上書きする。
+    pub fn push(&mut self, mut record: BootRecord) -> u32 {
+        let (slot, sequence) = match self.newest() {
+            Some((slot, newest)) => (
+                (slot + 1) % self.capacity(),
+                newest.sequence.wrapping_add(1),
+            ),
+            None => (0, 0),
+        };
+        record.sequence = sequence;
+        let start = slot * RECORD_WORDS;
+        self.words.as_mut()[start..start + RECORD_WORDS].copy_from_slice(&record.encode());
+        sequence
+    }
+
+    /// すべての記録を消去する
+    pub fn clear(&mut self) {
+        self.words.as_mut().fill(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(reset_flag: u32) -> BootRecord {
+        BootRecord::new(reset_flag, BootBank::Bank1, BootAction::Boot, 1)
+    }
+
+    #[test]
+    fn empty_history() {
+        let history = BootHistory::new([0u32; BOOT_HISTORY_WORDS]);
+        assert_eq!(history.capacity(), 256);
+        assert!(history.is_empty());
+        assert_eq!(history.len(), 0);
+        assert_eq!(history.get(0), None);
+    }
+
+    #[test]
+    fn garbage_is_not_a_record() {
+        let words: [u32; BOOT_HISTORY_WORDS] =
+            core::array::from_fn(|i| u32::try_from(i).unwrap().wrapping_mul(0x9E37_79B9));
+        let history = BootHistory::new(words);
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn newest_first() {
+        let mut history = BootHistory::new([0u32; BOOT_HISTORY_WORDS]);
+        for flag in 0..5 {
+            assert_eq!(history.push(record(flag)), flag);
+        }
+        assert_eq!(history.len(), 5);
+        let flags: Vec<_> = history.iter().map(|r| r.reset_flag).collect();
+        assert_eq!(flags, [4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn record_round_trips() {
+        let mut history = BootHistory::new([0u32; 8]);
+        let record = BootRecord::new(0x1234_5678, BootBank::Bank2, BootAction::Rollback, 70_000);
+        history.push(record);
+        assert_eq!(
+            history.get(0),
+            Some(BootRecord {
+                sequence: 0,
+                boot_attempts: u16::MAX,
+                ..record
+            })
+        );
+    }
+
+    #[test]
+    fn wraps_around() {
+        let mut history = BootHistory::new([0u32; 4 * RECORD_WORDS]);
+        for flag in 0..10 {
+            history.push(record(flag));
+        }
+        assert_eq!(history.len(), 4);
+        let flags: Vec<_> = history.iter().map(|r| r.reset_flag).collect();
+        assert_eq!(flags, [9, 8, 7, 6]);
+        let sequences: Vec<_> = history.iter().map(|r| r.sequence).collect();
+        assert_eq!(sequences, [9, 8, 7, 6]);
+    }
+
+    #[test]
+    fn corrupted_slot_truncates_history() {
+        let mut history = BootHistory::new([0u32; 8 * RECORD_WORDS]);
+        for flag in 0..6 {
+            history.push(record(flag));
+        }
+        // 3 番目に古い記録を壊す
+        history.words[2 * RECORD_WORDS + 1] ^= 1;
+        let flags: Vec<_> = history.iter().map(|r| r.reset_flag).collect();
+        assert_eq!(flags, [5, 4, 3]);
+    }
+
+    #[test]
+    fn corrupted_newest_slot_falls_back() {
+        let mut history = BootHistory::new([0u32; 8 * RECORD_WORDS]);
+        for flag in 0..3 {
+            history.push(record(flag));
+        }
+        // 最新の記録の書き込み中にリセットされた
+        history.words[2 * RECORD_WORDS + 3] ^= 1;
+        let flags: Vec<_> = history.iter().map(|r| r.reset_flag).collect();
+        assert_eq!(flags, [1, 0]);
+        // 壊れたスロットに次の記録が書き込まれる
+        assert_eq!(history.push(record(3)), 2);
+        let flags: Vec<_> = history.iter().map(|r| r.reset_flag).collect();
+        assert_eq!(flags, [3, 1, 0]);
+    }
+
+    #[test]
+    fn clear_removes_all_records() {
+        let mut history = BootHistory::new([0u32; 8 * RECORD_WORDS]);
+        history.push(record(0));
+        history.clear();
+        assert!(history.is_empty());
+    }
+}
@@ -0,0 +1,289 @@
+use crc::{Crc, CRC_32_ISO_HDLC};
+use sha2::{Digest as _, Sha256};
+
+/// アプリケーションの配置される領域（各バンクの sector 0〜6）のサイズ
+pub const APP_REGION_SIZE: usize = 7 * 128 * 1024;
+
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+const MAGIC: u32 = u32::from_le_bytes(*b"MZIM");
+const FORMAT_VERSION: u16 = 1;
+
+const ALGORITHM_CRC32: u16 = 1;
+const ALGORITHM_SHA256: u16 = 2;
+
+/// イメージのダイジェスト
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Digest {
+    Crc32(u32),
+    Sha256([u8; 32]),
+}
+
+impl Digest {
+    /// `image` の CRC32 を計算する
+    pub fn crc32(image: &[u8]) -> Self {
+        Digest::Crc32(CRC32.checksum(image))
+    }
+
+    /// `image` の SHA-256 を計算する
+    pub fn sha256(image: &[u8]) -> Self {
+        Digest::Sha256(Sha256::digest(image).into())
+    }
+
+    /// `image` について `self` と同じアルゴリズムでダイジェストを計算する
+    fn compute_same_kind(&self, image: &[u8]) -> Self {
+        match self {
+            Digest::Crc32(_) => Self::crc32(image),
+            Digest::Sha256(_) => Self::sha256(image),
+        }
+    }
+}
+
+/// イメージの検証に失敗した理由
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImageError {
+    /// トレーラのマジックナンバーが一致しない（書き込まれていない・消去済みを含む）
+    NoTrailer,
+    /// トレーラの CRC が一致しない
+    TrailerCorrupted,
+    /// 未対応のトレーラのフォーマットバージョン
+    UnsupportedFormat(u16),
+    /// 未対応のダイジェストのアルゴリズム
+    UnsupportedAlgorithm(u16),
+    /// イメージの長さがトレーラを除いた領域に収まらない
+    InvalidLength(u32),
+    /// イメージのダイジェストが一致しない
+    DigestMismatch,
+}
+
+/// アプリケーション領域の末尾 [`ImageTrailer::SIZE`] バイトに置かれるイメージのトレーラ
+///
+/// すべてリトルエンディアンで、以下のレイアウトを持つ。
+///
+/// | offset | size | 内容 |
+/// |--------|------|------|
+/// | 0      | 4    | マジックナンバー `"MZIM"` |
+/// | 4      | 2    | トレーラのフォーマットバージョン（1） |
+/// | 6      | 2    | ダイジェストのアルゴリズム（1: CRC32, 2: SHA-256） |
+/// | 8      | 4    | イメージのバージョン |
+/// | 12     | 4    | イメージの長さ（領域の先頭から） |
+/// | 16     | 32   | ダイジェスト（CRC32 の場合は先頭 4 バイトのみ使用し、残りは 0） |
+/// | 48     | 12   | 予約（0） |
+/// | 60     | 4    | offset 0〜59 の CRC32 |
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ImageTrailer {
+    pub version: u32,
+    pub length: u32,
+    pub digest: Digest,
+}
+
+impl ImageTrailer {
+    pub const SIZE: usize = 64;
+
+    /// アプリケーション領域内のトレーラの位置
+    pub const OFFSET: usize = APP_REGION_SIZE - Self::SIZE;
+
+    /// `image` に対するトレーラを構築する
+    ///
+    /// `digest` には [`Digest::crc32`] または [`Digest::sha256`] で計算した `image` のダイジェストを渡す。
+    ///
+    /// # Panics
+    /// `image` がトレーラを除いたアプリケーション領域に収まらない場合は panic する。
+    pub fn new(image: &[u8], version: u32, digest: Digest) -> Self {
+        assert!(image.len() <= Self::OFFSET);
+        #[allow(clippy::cast_possible_truncation)]
+        Self {
+            version,
+            length: image.len() as u32,
+            digest,
+        }
+    }
+
+    /// トレーラをバイト列に変換する
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+        bytes[4..6].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+        let algorithm = match self.digest {
+            Digest::Crc32(crc) => {
+                bytes[16..20].copy_from_slice(&crc.to_le_bytes());
+                ALGORITHM_CRC32
+            }
+            Digest::Sha256(hash) => {
+                bytes[16..48].copy_from_slice(&hash);
+                ALGORITHM_SHA256
+            }
+        };
+        bytes[6..8].copy_from_slice(&algorithm.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.version.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.length.to_le_bytes());
+        let crc = CRC32.checksum(&bytes[..60]);
+        bytes[60..64].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    /// バイト列からトレーラを読み出す
+    ///
+    /// # Errors
+    /// トレーラとして不正な場合は [`ImageError`] を返す。
+    pub fn parse(bytes: &[u8; Self::SIZE]) -> Result<Self, ImageError> {
+        let u16_at = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        let u32_at = |offset: usize| {
+            u32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+
+        if u32_at(0) != MAGIC {
+            return Err(ImageError::NoTrailer);
+        }
+        if u32_at(60) != CRC32.checksum(&bytes[..60]) {
+            return Err(ImageError::TrailerCorrupted);
+        }
+        let format = u16_at(4);
+        if format != FORMAT_VERSION {
+            return Err(ImageError::UnsupportedFormat(format));
+        }
+        let digest = match u16_at(6) {
+            ALGORITHM_CRC32 => Digest::Crc32(u32_at(16)),
+            ALGORITHM_SHA256 => {
+                let mut hash = [0; 32];
+                hash.copy_from_slice(&bytes[16..48]);
+                Digest::Sha256(hash)
+            }
+            algorithm => return Err(ImageError::UnsupportedAlgorithm(algorithm)),
+        };
+        let length = u32_at(12);
+        if length as usize > Self::OFFSET {
+            return Err(ImageError::InvalidLength(length));
+        }
+        Ok(Self {
+            version: u32_at(8),
+            length,
+            digest,
+        })
+    }
+}
+
+/// アプリケーション領域全体 `region` のトレーラを読み出し、イメージのダイジェストを検証する
+///
+/// # Errors
+/// トレーラが不正な場合やダイジェストが一致しない場合は [`ImageError`] を返す。
+///
+/// # Panics
+/// `region` の長さが [`APP_REGION_SIZE`] でない場合は panic する。
+pub fn verify_image(region: &[u8]) -> Result<ImageTrailer, ImageError> {
+    assert_eq!(region.len(), APP_REGION_SIZE);
+    let mut bytes = [0; ImageTrailer::SIZE];
+    bytes.copy_from_slice(&region[ImageTrailer::OFFSET..]);
+    let trailer = ImageTrailer::parse(&bytes)?;
+    let image = &region[..trailer.length as usize];
+    if trailer.digest.compute_same_kind(image) != trailer.digest {
+        return Err(ImageError::DigestMismatch);
+    }
+    Ok(trailer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region_with(image: &[u8], trailer: &ImageTrailer) -> Vec<u8> {
+        let mut region = vec![0xFF; APP_REGION_SIZE];
+        region[..image.len()].copy_from_slice(image);
+        region[ImageTrailer::OFFSET..].copy_from_slice(&trailer.to_bytes());
+        region
+    }
+
+    fn sample_image() -> Vec<u8> {
+        (0..4096u32).map(|i| (i * 7).to_le_bytes()[0]).collect()
+    }
+
+    #[test]
+    fn trailer_round_trips() {
+        let image = sample_image();
+        for digest in [Digest::crc32(&image), Digest::sha256(&image)] {
+            let trailer = ImageTrailer::new(&image, 42, digest);
+            assert_eq!(ImageTrailer::parse(&trailer.to_bytes()), Ok(trailer));
+        }
+    }
+
+    #[test]
+    fn valid_image_is_accepted() {
+        let image = sample_image();
+        for digest in [Digest::crc32(&image), Digest::sha256(&image)] {
+            let trailer = ImageTrailer::new(&image, 3, digest);
+            let region = region_with(&image, &trailer);
+            assert_eq!(verify_image(&region), Ok(trailer));
+        }
+    }
+
+    #[test]
+    fn erased_region_has_no_trailer() {
+        let region = vec![0xFF; APP_REGION_SIZE];
+        assert_eq!(verify_image(&region), Err(ImageError::NoTrailer));
+    }
+
+    #[test]
+    fn corrupted_image_is_rejected() {
+        let image = sample_image();
+        for digest in [Digest::crc32(&image), Digest::sha256(&image)] {
+            let trailer = ImageTrailer::new(&image, 3, digest);
+            let mut region = region_with(&image, &trailer);
+            region[100] ^= 0x01;
+            assert_eq!(verify_image(&region), Err(ImageError::DigestMismatch));
+        }
+    }
+
+    #[test]
+    fn bytes_after_image_are_not_covered() {
+        let image = sample_image();
+        let trailer = ImageTrailer::new(&image, 3, Digest::crc32(&image));
+        let mut region = region_with(&image, &trailer);
+        region[image.len()] = 0x00;
+        assert_eq!(verify_image(&region), Ok(trailer));
+    }
+
+    #[test]
+    fn corrupted_trailer_is_rejected() {
+        let image = sample_image();
+        let trailer = ImageTrailer::new(&image, 3, Digest::crc32(&image));
+        let mut bytes = trailer.to_bytes();
+        bytes[8] ^= 0x01;
+        assert_eq!(
+            ImageTrailer::parse(&bytes),
+            Err(ImageError::TrailerCorrupted)
+        );
+    }
+
+    #[test]
+    fn unsupported_fields_are_rejected() {
+        let image = sample_image();
+        let trailer = ImageTrailer::new(&image, 3, Digest::crc32(&image));
+
+        let with = |offset: usize, value: &[u8]| {
+            let mut bytes = trailer.to_bytes();
+            bytes[offset..offset + value.len()].copy_from_slice(value);
+            let crc = CRC32.checksum(&bytes[..60]);
+            bytes[60..64].copy_from_slice(&crc.to_le_bytes());
+            ImageTrailer::parse(&bytes)
+        };
+
+        assert_eq!(
+            with(4, &2u16.to_le_bytes()),
+            Err(ImageError::UnsupportedFormat(2))
+        );
+        assert_eq!(
+            with(6, &9u16.to_le_bytes()),
+            Err(ImageError::UnsupportedAlgorithm(9))
+        );
+        let too_long = u32::try_from(ImageTrailer::OFFSET + 1).unwrap();
+        assert_eq!(
+            with(12, &too_long.to_le_bytes()),
+            Err(ImageError::InvalidLength(too_long))
+        );
+    }
+}
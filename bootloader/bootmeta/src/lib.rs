@@ -1,14 +1,27 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 #![allow(clippy::must_use_candidate)]
 
+mod history;
+mod image;
+mod select;
+
 use core::ops::Not;
 
+#[cfg(feature = "hal")]
 use pac::rtc::bkpr::BKPR_SPEC;
+#[cfg(feature = "hal")]
 use stm32h7::Reg;
+#[cfg(feature = "hal")]
 use stm32h7xx_hal::pac;
-use stm32h7xx_hal::rcc::ResetReason;
 
-#[derive(Clone, Copy, PartialEq)]
+pub use history::{BootAction, BootHistory, BootRecord, BOOT_HISTORY_WORDS};
+pub use image::{verify_image, Digest, ImageError, ImageTrailer, APP_REGION_SIZE};
+pub use select::{
+    sanitize_max_boot_attempts, select_boot, BootDecision, BootRegisters, BOOT_CONFIRMED_MARKER,
+    DEFAULT_MAX_BOOT_ATTEMPTS, MAX_BOOT_ATTEMPTS_LIMIT,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum BootBank {
     Bank1 = 1,
     Bank2 = 2,
@@ -46,7 +59,8 @@ impl Not for BootBank {
     }
 }
 
-fn serialize_next_boot_bank(next_boot_bank: Option<BootBank>) -> u32 {
+/// `next_boot_bank` を Backup Register に書き込む値に変換する
+pub fn serialize_next_boot_bank(next_boot_bank: Option<BootBank>) -> u32 {
     match next_boot_bank {
         None => 0,
         Some(BootBank::Bank1) => 1,
@@ -54,7 +68,11 @@ fn serialize_next_boot_bank(next_boot_bank: Option<BootBank>) -> u32 {
     }
 }
 
-fn deserialize_next_boot_bank(bkpr_value: u32) -> Result<Option<BootBank>, u32> {
+/// Backup Register から読み出した値を `next_boot_bank` に変換する
+///
+/// # Errors
+/// 無効な値の場合は `Err` で返す。
+pub fn deserialize_next_boot_bank(bkpr_value: u32) -> Result<Option<BootBank>, u32> {
     match bkpr_value {
         0 => Ok(None),
         1 => Ok(Some(BootBank::Bank1)),
@@ -63,6 +81,82 @@ fn deserialize_next_boot_bank(bkpr_value: u32) -> Result<Option<BootBank>, u32>
     }
 }
 
+/// リセット要因
+///
+/// STM の HAL の `ResetReason` から `Unknown` を除いたもの
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ResetReason {
+    PowerOnReset,
+    PinReset,
+    BrownoutReset,
+    SystemReset,
+    CpuReset,
+    WindowWatchdogReset,
+    IndependentWatchdogReset,
+    GenericWatchdogReset,
+    D1ExitsDStandbyMode,
+    D2ExitsDStandbyMode,
+    D1EntersDStandbyErroneouslyOrCpuEntersCStopErroneously,
+}
+
+impl ResetReason {
+    /// このリセット要因で `RCC_RSR` に立つフラグの値を返す
+    pub fn reset_flag(self) -> u32 {
+        #[allow(clippy::wildcard_imports)]
+        use reset_flag_bits::*;
+
+        match self {
+            ResetReason::PowerOnReset => PORRSTF | PINRSTF | BORRSTF | D2RSTF | D1RSTF | CPURSTF,
+            ResetReason::PinReset => PINRSTF | CPURSTF,
+            ResetReason::BrownoutReset => PINRSTF | BORRSTF | CPURSTF,
+            ResetReason::SystemReset => SFTRSTF | PINRSTF | CPURSTF,
+            ResetReason::CpuReset => CPURSTF,
+            ResetReason::WindowWatchdogReset => WWDG1RSTF | PINRSTF | CPURSTF,
+            ResetReason::IndependentWatchdogReset => IWDG1RSTF | PINRSTF | CPURSTF,
+            ResetReason::GenericWatchdogReset => WWDG1RSTF | IWDG1RSTF | PINRSTF | CPURSTF,
+            ResetReason::D1ExitsDStandbyMode => D1RSTF,
+            ResetReason::D2ExitsDStandbyMode => D2RSTF,
+            ResetReason::D1EntersDStandbyErroneouslyOrCpuEntersCStopErroneously => {
+                LPWRRSTF | PINRSTF | CPURSTF
+            }
+        }
+    }
+}
+
+#[cfg(feature = "hal")]
+impl From<ResetReason> for stm32h7xx_hal::rcc::ResetReason {
+    fn from(reason: ResetReason) -> Self {
+        match reason {
+            ResetReason::PowerOnReset => Self::PowerOnReset,
+            ResetReason::PinReset => Self::PinReset,
+            ResetReason::BrownoutReset => Self::BrownoutReset,
+            ResetReason::SystemReset => Self::SystemReset,
+            ResetReason::CpuReset => Self::CpuReset,
+            ResetReason::WindowWatchdogReset => Self::WindowWatchdogReset,
+            ResetReason::IndependentWatchdogReset => Self::IndependentWatchdogReset,
+            ResetReason::GenericWatchdogReset => Self::GenericWatchdogReset,
+            ResetReason::D1ExitsDStandbyMode => Self::D1ExitsDStandbyMode,
+            ResetReason::D2ExitsDStandbyMode => Self::D2ExitsDStandbyMode,
+            ResetReason::D1EntersDStandbyErroneouslyOrCpuEntersCStopErroneously => {
+                Self::D1EntersDStandbyErroneouslyOrCpuEntersCStopErroneously
+            }
+        }
+    }
+}
+
+mod reset_flag_bits {
+    pub const LPWRRSTF: u32 = 1 << 30;
+    pub const WWDG1RSTF: u32 = 1 << 28;
+    pub const IWDG1RSTF: u32 = 1 << 26;
+    pub const SFTRSTF: u32 = 1 << 24;
+    pub const PORRSTF: u32 = 1 << 23;
+    pub const PINRSTF: u32 = 1 << 22;
+    pub const BORRSTF: u32 = 1 << 21;
+    pub const D2RSTF: u32 = 1 << 20;
+    pub const D1RSTF: u32 = 1 << 19;
+    pub const CPURSTF: u32 = 1 << 17;
+}
+
 struct ResetFlag {
     value: u32,
 }
@@ -73,50 +167,111 @@ impl ResetFlag {
     }
 
     fn lpwrrstf(&self) -> bool {
-        self.value & (1 << 30) != 0
+        self.value & reset_flag_bits::LPWRRSTF != 0
     }
 
     fn wwdg1rstf(&self) -> bool {
-        self.value & (1 << 28) != 0
+        self.value & reset_flag_bits::WWDG1RSTF != 0
     }
 
     fn iwdg1rstf(&self) -> bool {
-        self.value & (1 << 26) != 0
+        self.value & reset_flag_bits::IWDG1RSTF != 0
     }
 
     fn sftrstf(&self) -> bool {
-        self.value & (1 << 24) != 0
+        self.value & reset_flag_bits::SFTRSTF != 0
     }
 
     fn porrstf(&self) -> bool {
-        self.value & (1 << 23) != 0
+        self.value & reset_flag_bits::PORRSTF != 0
     }
 
     fn pinrstf(&self) -> bool {
-        self.value & (1 << 22) != 0
+        self.value & reset_flag_bits::PINRSTF != 0
     }
 
     fn borrstf(&self) -> bool {
-        self.value & (1 << 21) != 0
+        self.value & reset_flag_bits::BORRSTF != 0
     }
 
     fn d2rstf(&self) -> bool {
-        self.value & (1 << 20) != 0
+        self.value & reset_flag_bits::D2RSTF != 0
     }
 
     fn d1rstf(&self) -> bool {
-        self.value & (1 << 19) != 0
+        self.value & reset_flag_bits::D1RSTF != 0
     }
 
     fn cpurstf(&self) -> bool {
-        self.value & (1 << 17) != 0
+        self.value & reset_flag_bits::CPURSTF != 0
     }
 }
 
+/// リセットフラグの値からリセット原因を判定する
+///
+/// # Errors
+/// 既知のパターンに当てはまらない場合は `reset_flag` 生値を `Err` で返す。
+pub fn decode_reset_flag(reset_flag_val: u32) -> Result<ResetReason, u32> {
+    // copy from https://docs.rs/stm32h7xx-hal/latest/src/stm32h7xx_hal/rcc/reset_reason.rs.html
+    let reset_flag = ResetFlag::new(reset_flag_val);
+    match (
+        reset_flag.lpwrrstf(),
+        reset_flag.wwdg1rstf(),
+        reset_flag.iwdg1rstf(),
+        reset_flag.sftrstf(),
+        reset_flag.porrstf(),
+        reset_flag.pinrstf(),
+        reset_flag.borrstf(),
+        reset_flag.d2rstf(),
+        reset_flag.d1rstf(),
+        reset_flag.cpurstf(),
+    ) {
+        (false, false, false, false, true, true, true, true, true, true) => {
+            Ok(ResetReason::PowerOnReset)
+        }
+        (false, false, false, false, false, true, false, false, false, true) => {
+            Ok(ResetReason::PinReset)
+        }
+        (false, false, false, false, false, true, true, false, false, true) => {
+            Ok(ResetReason::BrownoutReset)
+        }
+        (false, false, false, true, false, true, false, false, false, true) => {
+            Ok(ResetReason::SystemReset)
+        }
+        (false, false, false, false, false, false, false, false, false, true) => {
+            Ok(ResetReason::CpuReset)
+        }
+        (false, true, false, false, false, false, false, false, false, false)
+        | (false, true, false, false, false, true, false, false, false, true) => {
+            // コピペ元の HAL を見る限り、リファレンスの表で太字になっている1が両方0でもこのケースと判定していいらしい（リファレンスマニュアルに明記はされていない）
+            Ok(ResetReason::WindowWatchdogReset)
+        }
+        (false, false, true, false, false, true, false, false, false, true) => {
+            Ok(ResetReason::IndependentWatchdogReset)
+        }
+        (false, true, true, false, false, true, false, false, false, true) => {
+            // おそらくフラグをリセットせずに WWDG1 と IWDG1 が連続して発火したケースに対応（リファレンスマニュアルに明記はされていない）
+            Ok(ResetReason::GenericWatchdogReset)
+        }
+        (false, false, false, false, false, false, false, false, true, false) => {
+            Ok(ResetReason::D1ExitsDStandbyMode)
+        }
+        (false, false, false, false, false, false, false, true, false, false) => {
+            Ok(ResetReason::D2ExitsDStandbyMode)
+        }
+        (true, false, false, false, false, true, false, false, false, true) => {
+            Ok(ResetReason::D1EntersDStandbyErroneouslyOrCpuEntersCStopErroneously)
+        }
+        _ => Err(reset_flag_val),
+    }
+}
+
+#[cfg(feature = "hal")]
 pub struct BootMeta {
     rtc: pac::RTC,
 }
 
+#[cfg(feature = "hal")]
 impl BootMeta {
     /// `BootMeta` を構築する
     pub fn new(rtc: pac::RTC) -> Self {
@@ -131,6 +286,18 @@ impl BootMeta {
         &self.rtc.bkpr[1]
     }
 
+    fn boot_attempts_reg(&self) -> &Reg<BKPR_SPEC> {
+        &self.rtc.bkpr[2]
+    }
+
+    fn max_boot_attempts_reg(&self) -> &Reg<BKPR_SPEC> {
+        &self.rtc.bkpr[3]
+    }
+
+    fn boot_confirmed_reg(&self) -> &Reg<BKPR_SPEC> {
+        &self.rtc.bkpr[4]
+    }
+
     /// 次回起動時のブートバンクを取得する
     ///
     /// `None` は次回起動時にも現在のブートバンクが維持されることを表す。
@@ -151,6 +318,52 @@ impl BootMeta {
             .write(|w| w.bkp().bits(bkpr_value));
     }
 
+    /// ブートローダの判断に用いる Backup Register の生値を読み出す
+    pub fn boot_registers(&self) -> BootRegisters {
+        BootRegisters {
+            next_boot_bank: self.next_boot_bank_reg().read().bits(),
+            boot_attempts: self.boot_attempts_reg().read().bits(),
+            max_boot_attempts: self.max_boot_attempts_reg().read().bits(),
+            boot_confirmed: self.boot_confirmed_reg().read().bits(),
+        }
+    }
+
+    /// 起動確認のない連続した起動回数を取得する
+    pub fn boot_attempts(&self) -> u32 {
+        self.boot_attempts_reg().read().bits()
+    }
+
+    /// 起動確認のない連続した起動回数を設定する
+    pub fn set_boot_attempts(&self, boot_attempts: u32) {
+        self.boot_attempts_reg()
+            .write(|w| w.bkp().bits(boot_attempts));
+    }
+
+    /// ロールバックするまでに許す、起動確認のない連続した起動回数を取得する
+    ///
+    /// 不正な値が書かれている場合は [`DEFAULT_MAX_BOOT_ATTEMPTS`] を返す。
+    pub fn max_boot_attempts(&self) -> u32 {
+        sanitize_max_boot_attempts(self.max_boot_attempts_reg().read().bits())
+    }
+
+    /// ロールバックするまでに許す、起動確認のない連続した起動回数を設定する
+    pub fn set_max_boot_attempts(&self, max_boot_attempts: u32) {
+        self.max_boot_attempts_reg()
+            .write(|w| w.bkp().bits(max_boot_attempts));
+    }
+
+    /// 現在の起動が確認済みかどうかを取得する
+    pub fn boot_confirmed(&self) -> bool {
+        self.boot_confirmed_reg().read().bits() == BOOT_CONFIRMED_MARKER
+    }
+
+    /// 起動確認のマーカーを書き込む、または消去する
+    pub fn set_boot_confirmed(&self, confirmed: bool) {
+        let bkpr_value = if confirmed { BOOT_CONFIRMED_MARKER } else { 0 };
+        self.boot_confirmed_reg()
+            .write(|w| w.bkp().bits(bkpr_value));
+    }
+
     /// リセットフラグを読み出す
     pub fn reset_flag(&self) -> u32 {
         self.reset_flag_reg().read().bits()
@@ -167,64 +380,35 @@ impl BootMeta {
     ///
     /// # Errors
     /// 無効な値が読み出された場合は `reset_flag` 生値を `Err` で返す。
-    pub fn check_reset_source(&self, reset_flag_val: u32) -> Result<ResetReason, u32> {
-        // copy from https://docs.rs/stm32h7xx-hal/latest/src/stm32h7xx_hal/rcc/reset_reason.rs.html
-        let reset_flag = ResetFlag::new(reset_flag_val);
-        match (
-            reset_flag.lpwrrstf(),
-            reset_flag.wwdg1rstf(),
-            reset_flag.iwdg1rstf(),
-            reset_flag.sftrstf(),
-            reset_flag.porrstf(),
-            reset_flag.pinrstf(),
-            reset_flag.borrstf(),
-            reset_flag.d2rstf(),
-            reset_flag.d1rstf(),
-            reset_flag.cpurstf(),
-        ) {
-            (false, false, false, false, true, true, true, true, true, true) => {
-                Ok(ResetReason::PowerOnReset)
-            }
-            (false, false, false, false, false, true, false, false, false, true) => {
-                Ok(ResetReason::PinReset)
-            }
-            (false, false, false, false, false, true, true, false, false, true) => {
-                Ok(ResetReason::BrownoutReset)
-            }
-            (false, false, false, true, false, true, false, false, false, true) => {
-                Ok(ResetReason::SystemReset)
-            }
-            (false, false, false, false, false, false, false, false, false, true) => {
-                Ok(ResetReason::CpuReset)
-            }
-            (false, true, false, false, false, false, false, false, false, false)
-            | (false, true, false, false, false, true, false, false, false, true) => {
-                // コピペ元の HAL を見る限り、リファレンスの表で太字になっている1が両方0でもこのケースと判定していいらしい（リファレンスマニュアルに明記はされていない）
-                Ok(ResetReason::WindowWatchdogReset)
-            }
-            (false, false, true, false, false, true, false, false, false, true) => {
-                Ok(ResetReason::IndependentWatchdogReset)
-            }
-            (false, true, true, false, false, true, false, false, false, true) => {
-                // おそらくフラグをリセットせずに WWDG1 と IWDG1 が連続して発火したケースに対応（リファレンスマニュアルに明記はされていない）
-                Ok(ResetReason::GenericWatchdogReset)
-            }
-            (false, false, false, false, false, false, false, false, true, false) => {
-                Ok(ResetReason::D1ExitsDStandbyMode)
-            }
-            (false, false, false, false, false, false, false, true, false, false) => {
-                Ok(ResetReason::D2ExitsDStandbyMode)
-            }
-            (true, false, false, false, false, true, false, false, false, true) => {
-                Ok(ResetReason::D1EntersDStandbyErroneouslyOrCpuEntersCStopErroneously)
-            }
-            _ => Err(reset_flag_val),
-        }
+    pub fn check_reset_source(
+        &self,
+        reset_flag_val: u32,
+    ) -> Result<stm32h7xx_hal::rcc::ResetReason, u32> {
+        decode_reset_flag(reset_flag_val).map(Into::into)
     }
 }
 
+/// Backup SRAM の先頭アドレス
+#[cfg(feature = "hal")]
+const BSRAM_BASE: usize = 0x3880_0000;
+
+/// Backup SRAM 上のブート履歴を返す
+///
+/// # Safety
+/// Backup SRAM のクロックが有効になっている必要がある。
+/// また、返り値を通じた書き込みと、他の参照からの読み書きが同時に行われてはならない。
+#[cfg(feature = "hal")]
+pub unsafe fn bsram_boot_history() -> BootHistory<&'static mut [u32]> {
+    BootHistory::new(core::slice::from_raw_parts_mut(
+        BSRAM_BASE as *mut u32,
+        BOOT_HISTORY_WORDS,
+    ))
+}
+
+#[cfg(feature = "hal")]
 pub struct FlashOptionBytes(core::marker::PhantomData<()>);
 
+#[cfg(feature = "hal")]
 impl FlashOptionBytes {
     /// # Safety
     /// TODO
@@ -0,0 +1,391 @@
+use crate::{deserialize_next_boot_bank, BootBank, ResetFlag};
+
+/// アプリケーションが起動に成功したことを表すマーカーの値
+pub const BOOT_CONFIRMED_MARKER: u32 = 0xB007_600D;
+
+/// ロールバックするまでに許す、起動確認のない連続した起動回数の既定値
+///
+/// 1 の場合、起動確認のないまま 1 回リセットされるとロールバックする。
+pub const DEFAULT_MAX_BOOT_ATTEMPTS: u32 = 1;
+
+/// ロールバックするまでに許す、起動確認のない連続した起動回数の上限
+pub const MAX_BOOT_ATTEMPTS_LIMIT: u32 = 255;
+
+/// 起動回数の上限の設定値を検証し、不正な値の場合は既定値に潰す
+pub fn sanitize_max_boot_attempts(max_boot_attempts: u32) -> u32 {
+    if (1..=MAX_BOOT_ATTEMPTS_LIMIT).contains(&max_boot_attempts) {
+        max_boot_attempts
+    } else {
+        DEFAULT_MAX_BOOT_ATTEMPTS
+    }
+}
+
+/// ブートローダの判断に用いる Backup Register の生値
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct BootRegisters {
+    /// 次回起動時のブートバンク
+    pub next_boot_bank: u32,
+    /// 起動確認のない連続した起動回数
+    pub boot_attempts: u32,
+    /// ロールバックするまでに許す、起動確認のない連続した起動回数
+    pub max_boot_attempts: u32,
+    /// 前回の起動でアプリケーションが書き込んだ起動確認のマーカー
+    pub boot_confirmed: u32,
+}
+
+/// ブートローダがリセット直後に行うべき動作
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BootDecision {
+    /// `SWAP_BANK` を書き換えてバンクを切り替え、システムリセットする
+    ///
+    /// 切り替え先のバンクでは起動回数を数え直すため、リセット前に `boot_attempts` を 0 にする。
+    /// 前回の起動が確認されていない場合は、アプリケーションの要求ではなくロールバックとして `rollback` が真になる。
+    Switch { bank: BootBank, rollback: bool },
+    /// 現在のバンクのアプリケーションを起動する
+    ///
+    /// 起動前に `next_boot_bank` と `boot_attempts` を Backup Register に書き込み、起動確認のマーカーを消去する。
+    Boot {
+        bank: BootBank,
+        next_boot_bank: Option<BootBank>,
+        boot_attempts: u32,
+    },
+}
+
+/// リセットフラグ・Backup Register・`SWAP_BANK` からブートローダの動作を決める
+///
+/// - パワーオンリセット・ブラウンアウトリセットの場合は Backup Register が不定なため、ブートバンクの変更がなく、起動回数も 0 として扱う
+/// - `next_boot_bank` に不正な値が書かれている場合も、ブートバンクの変更がないものとして扱う
+/// - `next_boot_bank` が現在のブートバンクと異なる場合はバンクを切り替える
+/// - それ以外の場合は現在のバンクで起動する。前回の起動が確認されていれば起動回数を数え直し、そうでなければ加算する。
+///   起動回数が上限に達した場合は、この起動にも失敗したときに裏のバンクに切り替えるため、`next_boot_bank` に現在の裏のバンクを設定する
+///   （ブートが成功した場合はアプリケーションの責任で起動確認のマーカーを書き込み、`next_boot_bank` を `None` に戻す）
+pub fn select_boot(reset_flag: u32, registers: &BootRegisters, swap_bank: bool) -> BootDecision {
+    let current_boot_bank = BootBank::from_swap_bank(swap_bank);
+    let power_on = ResetFlag::new(reset_flag).borrstf();
+
+    let desired_boot_bank = if power_on {
+        None
+    } else {
+        deserialize_next_boot_bank(registers.next_boot_bank).unwrap_or(None)
+    };
+    if let Some(desired_boot_bank) = desired_boot_bank {
+        if desired_boot_bank != current_boot_bank {
+            return BootDecision::Switch {
+                bank: desired_boot_bank,
+                rollback: registers.boot_confirmed != BOOT_CONFIRMED_MARKER,
+            };
+        }
+    }
+
+    let previous_attempts = if power_on || registers.boot_confirmed == BOOT_CONFIRMED_MARKER {
+        0
+    } else {
+        registers.boot_attempts
+    };
+    let boot_attempts = previous_attempts.saturating_add(1);
+    let max_boot_attempts = sanitize_max_boot_attempts(registers.max_boot_attempts);
+    let next_boot_bank = if boot_attempts >= max_boot_attempts {
+        Some(!current_boot_bank)
+    } else {
+        None
+    };
+    BootDecision::Boot {
+        bank: current_boot_bank,
+        next_boot_bank,
+        boot_attempts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{serialize_next_boot_bank, ResetReason};
+
+    fn registers(next_boot_bank: u32) -> BootRegisters {
+        BootRegisters {
+            next_boot_bank,
+            ..BootRegisters::default()
+        }
+    }
+
+    fn boot(bank: BootBank) -> BootDecision {
+        BootDecision::Boot {
+            bank,
+            next_boot_bank: Some(!bank),
+            boot_attempts: 1,
+        }
+    }
+
+    /// `decision` に従って Backup Register と `SWAP_BANK` を更新する
+    fn apply(decision: BootDecision, registers: &mut BootRegisters, swap_bank: &mut bool) {
+        match decision {
+            BootDecision::Switch { bank, .. } => {
+                *swap_bank = bank.to_swap_bank();
+                registers.boot_attempts = 0;
+            }
+            BootDecision::Boot {
+                next_boot_bank,
+                boot_attempts,
+                ..
+            } => {
+                registers.next_boot_bank = serialize_next_boot_bank(next_boot_bank);
+                registers.boot_attempts = boot_attempts;
+                registers.boot_confirmed = 0;
+            }
+        }
+    }
+
+    /// リセット後、起動するまでブートローダを実行する
+    fn boot_until_app(
+        mut reset_flag: u32,
+        registers: &mut BootRegisters,
+        swap_bank: &mut bool,
+    ) -> BootDecision {
+        loop {
+            let decision = select_boot(reset_flag, registers, *swap_bank);
+            apply(decision, registers, swap_bank);
+            if let BootDecision::Boot { .. } = decision {
+                return decision;
+            }
+            reset_flag = ResetReason::SystemReset.reset_flag();
+        }
+    }
+
+    #[test]
+    fn power_on_ignores_backup_register() {
+        let flag = ResetReason::PowerOnReset.reset_flag();
+        assert_eq!(
+            select_boot(flag, &registers(2), false),
+            boot(BootBank::Bank1)
+        );
+        assert_eq!(
+            select_boot(flag, &registers(1), true),
+            boot(BootBank::Bank2)
+        );
+        let stale = BootRegisters {
+            next_boot_bank: 0,
+            boot_attempts: 100,
+            max_boot_attempts: 1000,
+            boot_confirmed: 0,
+        };
+        assert_eq!(select_boot(flag, &stale, false), boot(BootBank::Bank1));
+    }
+
+    #[test]
+    fn brownout_ignores_backup_register() {
+        let flag = ResetReason::BrownoutReset.reset_flag();
+        assert_eq!(
+            select_boot(flag, &registers(2), false),
+            boot(BootBank::Bank1)
+        );
+    }
+
+    #[test]
+    fn cleared_next_boot_bank_keeps_current_bank() {
+        let flag = ResetReason::SystemReset.reset_flag();
+        assert_eq!(
+            select_boot(flag, &registers(0), false),
+            boot(BootBank::Bank1)
+        );
+        assert_eq!(
+            select_boot(flag, &registers(0), true),
+            boot(BootBank::Bank2)
+        );
+    }
+
+    #[test]
+    fn same_bank_does_not_switch() {
+        let flag = ResetReason::PinReset.reset_flag();
+        assert_eq!(
+            select_boot(flag, &registers(1), false),
+            boot(BootBank::Bank1)
+        );
+        assert_eq!(
+            select_boot(flag, &registers(2), true),
+            boot(BootBank::Bank2)
+        );
+    }
+
+    #[test]
+    fn different_bank_switches() {
+        let flag = ResetReason::SystemReset.reset_flag();
+        assert_eq!(
+            select_boot(flag, &registers(2), false),
+            BootDecision::Switch {
+                bank: BootBank::Bank2,
+                rollback: true,
+            }
+        );
+        let confirmed = BootRegisters {
+            boot_confirmed: BOOT_CONFIRMED_MARKER,
+            ..registers(1)
+        };
+        assert_eq!(
+            select_boot(flag, &confirmed, true),
+            BootDecision::Switch {
+                bank: BootBank::Bank1,
+                rollback: false,
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_next_boot_bank_keeps_current_bank() {
+        let flag = ResetReason::SystemReset.reset_flag();
+        assert_eq!(
+            select_boot(flag, &registers(3), false),
+            boot(BootBank::Bank1)
+        );
+        assert_eq!(
+            select_boot(flag, &registers(u32::MAX), true),
+            boot(BootBank::Bank2)
+        );
+    }
+
+    #[test]
+    fn watchdog_after_failed_boot_rolls_back() {
+        let mut registers = BootRegisters::default();
+        let mut swap_bank = false;
+        boot_until_app(
+            ResetReason::PowerOnReset.reset_flag(),
+            &mut registers,
+            &mut swap_bank,
+        );
+        // アプリケーションが起動確認をする前に WDT リセットした
+        let decision = boot_until_app(
+            ResetReason::IndependentWatchdogReset.reset_flag(),
+            &mut registers,
+            &mut swap_bank,
+        );
+        assert_eq!(decision, boot(BootBank::Bank2));
+    }
+
+    #[test]
+    fn rolls_back_after_max_attempts() {
+        let mut registers = BootRegisters {
+            max_boot_attempts: 3,
+            ..BootRegisters::default()
+        };
+        let mut swap_bank = false;
+        let wdt = ResetReason::IndependentWatchdogReset.reset_flag();
+
+        let decision = boot_until_app(
+            ResetReason::PowerOnReset.reset_flag(),
+            &mut registers,
+            &mut swap_bank,
+        );
+        assert_eq!(
+            decision,
+            BootDecision::Boot {
+                bank: BootBank::Bank1,
+                next_boot_bank: None,
+                boot_attempts: 1,
+            }
+        );
+        let decision = boot_until_app(wdt, &mut registers, &mut swap_bank);
+        assert_eq!(
+            decision,
+            BootDecision::Boot {
+                bank: BootBank::Bank1,
+                next_boot_bank: None,
+                boot_attempts: 2,
+            }
+        );
+        // 最後の試行ではロールバックの準備をする
+        let decision = boot_until_app(wdt, &mut registers, &mut swap_bank);
+        assert_eq!(
+            decision,
+            BootDecision::Boot {
+                bank: BootBank::Bank1,
+                next_boot_bank: Some(BootBank::Bank2),
+                boot_attempts: 3,
+            }
+        );
+        // 切り替え先では起動回数を数え直す
+        let decision = boot_until_app(wdt, &mut registers, &mut swap_bank);
+        assert_eq!(
+            decision,
+            BootDecision::Boot {
+                bank: BootBank::Bank2,
+                next_boot_bank: None,
+                boot_attempts: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn confirmed_boot_resets_attempts() {
+        let mut registers = BootRegisters {
+            max_boot_attempts: 2,
+            ..BootRegisters::default()
+        };
+        let mut swap_bank = false;
+        let wdt = ResetReason::IndependentWatchdogReset.reset_flag();
+
+        boot_until_app(
+            ResetReason::PowerOnReset.reset_flag(),
+            &mut registers,
+            &mut swap_bank,
+        );
+        for _ in 0..10 {
+            // アプリケーションが起動確認をした後のリセットではロールバックしない
+            registers.boot_confirmed = BOOT_CONFIRMED_MARKER;
+            registers.next_boot_bank = serialize_next_boot_bank(None);
+            let decision = boot_until_app(wdt, &mut registers, &mut swap_bank);
+            assert_eq!(
+                decision,
+                BootDecision::Boot {
+                    bank: BootBank::Bank1,
+                    next_boot_bank: None,
+                    boot_attempts: 1,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_max_attempts_falls_back_to_default() {
+        assert_eq!(sanitize_max_boot_attempts(0), DEFAULT_MAX_BOOT_ATTEMPTS);
+        assert_eq!(sanitize_max_boot_attempts(1), 1);
+        assert_eq!(
+            sanitize_max_boot_attempts(MAX_BOOT_ATTEMPTS_LIMIT),
+            MAX_BOOT_ATTEMPTS_LIMIT
+        );
+        assert_eq!(
+            sanitize_max_boot_attempts(MAX_BOOT_ATTEMPTS_LIMIT + 1),
+            DEFAULT_MAX_BOOT_ATTEMPTS
+        );
+    }
+
+    #[test]
+    fn attempts_counter_saturates() {
+        let flag = ResetReason::SystemReset.reset_flag();
+        let registers = BootRegisters {
+            boot_attempts: u32::MAX,
+            ..BootRegisters::default()
+        };
+        let BootDecision::Boot { boot_attempts, .. } = select_boot(flag, &registers, false) else {
+            panic!("expected boot");
+        };
+        assert_eq!(boot_attempts, u32::MAX);
+    }
+
+    #[test]
+    fn reset_flag_round_trips() {
+        for reason in [
+            ResetReason::PowerOnReset,
+            ResetReason::PinReset,
+            ResetReason::BrownoutReset,
+            ResetReason::SystemReset,
+            ResetReason::CpuReset,
+            ResetReason::WindowWatchdogReset,
+            ResetReason::IndependentWatchdogReset,
+            ResetReason::GenericWatchdogReset,
+            ResetReason::D1ExitsDStandbyMode,
+            ResetReason::D2ExitsDStandbyMode,
+            ResetReason::D1EntersDStandbyErroneouslyOrCpuEntersCStopErroneously,
+        ] {
+            assert_eq!(crate::decode_reset_flag(reason.reset_flag()), Ok(reason));
+        }
+    }
+}
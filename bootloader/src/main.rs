@@ -3,7 +3,10 @@
 
 use core::ops::Range;
 
-use bootmeta::{BootBank, FlashOptionBytes};
+use bootmeta::{
+    bsram_boot_history, select_boot, verify_image, BootAction, BootBank, BootDecision, BootMeta,
+    BootRecord, FlashOptionBytes, APP_REGION_SIZE,
+};
 use core::mem::size_of;
 use defmt_rtt as _;
 use fugit::MillisDurationU32;
@@ -16,6 +19,10 @@ const WDT_TIMEOUT: MillisDurationU32 = MillisDurationU32::secs(20);
 // リセット直後の CPU は HSI の 64MHz で駆動されている
 const CYCLES_PER_SECOND: u32 = 64_000_000;
 
+// 現在のブートバンクは常に 0x0800_0000 に、裏のバンクは 0x0810_0000 にマップされる
+const CURRENT_BANK_BASE: usize = 0x0800_0000;
+const OTHER_BANK_BASE: usize = 0x0810_0000;
+
 const AXI_SRAM: Range<usize> = 0x2400_0000..0x2408_0000;
 const SRAM1: Range<usize> = 0x3000_0000..0x3002_0000;
 const SRAM2: Range<usize> = 0x3002_0000..0x3004_0000;
@@ -39,49 +46,89 @@ fn main() -> ! {
     // Backup Domain の有効化に時間がかかっている可能性があるので、ここでウォッチドッグをフィードする
     iwdg.feed();
 
-    let bootmeta = bootmeta::BootMeta::new(dp.RTC);
+    let bootmeta = BootMeta::new(dp.RTC);
     let flop = unsafe { FlashOptionBytes::new() };
 
-    // Flash のオプションバイトを読み出し、現在のブートバンクを取得する
+    // Flash のオプションバイトを読み出す
     let flop_swap_bank = flop.read_swap_bank();
-    let current_boot_bank = BootBank::from_swap_bank(flop_swap_bank);
 
     // リセットフラグを読み出し、Backup Register に保存する
-    bootmeta.set_reset_flag(dp.RCC.rsr.read().bits());
-
-    // ブートすべきバンクを取得する
-    let desired_boot_bank = if dp.RCC.rsr.read().borrstf().is_reset_occourred() {
-        // パワーオンリセット・ブラウンアウトリセットが発生した場合 Backup Register は不定なため、ブートバンクの変更がないものとして扱う
-        None
-    } else {
-        // WDTによるリセット・システムリセットの場合は Backup Register からブートバンクを取得する
-        // 不正な値が読み出された場合は None に潰し、ブートバンクの変更がないものとして扱う
-        bootmeta.next_boot_bank().unwrap_or(None)
-    };
+    let reset_flag = dp.RCC.rsr.read().bits();
+    bootmeta.set_reset_flag(reset_flag);
+
+    // Backup SRAM を有効化し、ブート履歴を読み書きできるようにする
+    enable_bsram(&dp.RCC);
+    let mut history = unsafe { bsram_boot_history() };
+    if dp.RCC.rsr.read().borrstf().is_reset_occourred() {
+        // パワーオンリセット・ブラウンアウトリセットが発生した場合 Backup SRAM は不定なため、ブート履歴を消去する
+        history.clear();
+    }
+
+    // ブートすべきバンクを決定する
+    let registers = bootmeta.boot_registers();
+    let decision = select_boot(reset_flag, &registers, flop_swap_bank);
     // リセットフラグをクリア
     dp.RCC.rsr.modify(|_, w| w.rmvf().set_bit());
 
-    if let Some(desired_boot_bank) = desired_boot_bank {
-        if desired_boot_bank != current_boot_bank {
+    match decision {
+        BootDecision::Switch {
+            bank: desired_boot_bank,
+            rollback,
+        } => {
             // ブートすべきバンクが指定されており、それが現在のブートバンクと異なる場合
-
-            // 待機の前にウォッチドッグをフィードする
+            let action = if rollback {
+                BootAction::Rollback
+            } else {
+                BootAction::Switch
+            };
+            history.push(BootRecord::new(
+                reset_flag,
+                desired_boot_bank,
+                action,
+                registers.boot_attempts,
+            ));
+            switch_boot_bank(&mut iwdg, &bootmeta, &flop, desired_boot_bank);
+        }
+        BootDecision::Boot {
+            bank,
+            next_boot_bank,
+            boot_attempts,
+        } => {
+            // ダイジェストの計算に時間がかかる可能性があるので、事前にウォッチドッグをフィードする
             iwdg.feed();
-            // リブートループで内蔵 Flash が高速に消耗するのを防ぐため、1秒待機する
-            cortex_m::asm::delay(CYCLES_PER_SECOND);
-
-            // ブートバンクを切り替える
-            flop.write_swap_bank(desired_boot_bank.to_swap_bank());
-            cortex_m::peripheral::SCB::sys_reset();
-            // ここには到達しない
+            if let Err(err) = verify_image(app_region(CURRENT_BANK_BASE)) {
+                defmt::warn!("image verification failed: {}", defmt::Debug2Format(&err));
+                iwdg.feed();
+                if verify_image(app_region(OTHER_BANK_BASE)).is_ok() {
+                    // 裏のバンクのイメージが正しければ、裏のバンクに切り替える
+                    // 切り替え後にブートローダが元のバンクに戻さないよう、次回ブートバンクを裏のバンクにしておく
+                    bootmeta.set_next_boot_bank(Some(!bank));
+                    history.push(BootRecord::new(
+                        reset_flag,
+                        !bank,
+                        BootAction::Rollback,
+                        boot_attempts,
+                    ));
+                    switch_boot_bank(&mut iwdg, &bootmeta, &flop, !bank);
+                }
+                // どちらのバンクのイメージも正しくない場合は、起動できないよりはましなので現在のバンクで起動を試みる
+                defmt::warn!("no valid image found, booting current bank anyway");
+            }
+
+            // 起動回数が上限に達した場合は、ブートに失敗したときに裏のバンクに切り替えるため、次回ブートバンクが現在の裏のバンクになる
+            // ブートが成功した場合はアプリケーションの責任で起動確認のマーカーを書き込み、next_boot_bank を None に戻すことになっている
+            bootmeta.set_next_boot_bank(next_boot_bank);
+            bootmeta.set_boot_attempts(boot_attempts);
+            bootmeta.set_boot_confirmed(false);
+            history.push(BootRecord::new(
+                reset_flag,
+                bank,
+                BootAction::Boot,
+                boot_attempts,
+            ));
         }
     }
 
-    // ブートに失敗した場合に裏のバンクに切り替えるため、次回ブートバンクを現在の裏のバンクに設定する
-    // ブートが成功した場合はアプリケーションの責任で next_boot_bank を None に戻すことになっている
-    let rolled_back_boot_bank = !current_boot_bank;
-    bootmeta.set_next_boot_bank(rolled_back_boot_bank.into());
-
     // 内蔵 RAM のゼロクリアに時間がかかる可能性があるので、事前にウォッチドッグをフィードする
     iwdg.feed();
 
@@ -101,15 +148,43 @@ fn main() -> ! {
         // Arm の Vector Table をアプリケーションのものに書き換える
         cp.SCB.vtor.write(0x0800_0000);
         // アプリケーションのコードにジャンプする
-        cortex_m::asm::bootload(0x0800_0000 as *const u32);
+        cortex_m::asm::bootload(CURRENT_BANK_BASE as *const u32);
     }
 }
 
+/// ブートバンクを切り替えてシステムリセットする
+fn switch_boot_bank(
+    iwdg: &mut IndependentWatchdog,
+    bootmeta: &BootMeta,
+    flop: &FlashOptionBytes,
+    bank: BootBank,
+) -> ! {
+    // 切り替え先のバンクでは起動回数を数え直す
+    bootmeta.set_boot_attempts(0);
+
+    // 待機の前にウォッチドッグをフィードする
+    iwdg.feed();
+    // リブートループで内蔵 Flash が高速に消耗するのを防ぐため、1秒待機する
+    cortex_m::asm::delay(CYCLES_PER_SECOND);
+
+    flop.write_swap_bank(bank.to_swap_bank());
+    cortex_m::peripheral::SCB::sys_reset();
+}
+
+/// `base` から始まるバンクのアプリケーション領域
+fn app_region(base: usize) -> &'static [u8] {
+    unsafe { core::slice::from_raw_parts(base as *const u8, APP_REGION_SIZE) }
+}
+
 #[defmt::panic_handler]
 fn panic() -> ! {
     cortex_m::asm::udf()
 }
 
+fn enable_bsram(rcc: &pac::RCC) {
+    rcc.ahb4enr.modify(|_, w| w.bkpramen().set_bit());
+}
+
 fn enable_sram123(rcc: &pac::RCC) {
     rcc.ahb2enr.modify(|_, w| {
         w.sram1en()
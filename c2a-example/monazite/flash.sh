@@ -0,0 +1,18 @@
+#!/bin/bash
+
+# cargo run の runner として、ELF に bootmeta のイメージのトレーラを付けて Bank2 に書き込む
+# ブートローダはトレーラで検証できないイメージから起動しないため、ELF をそのまま書き込んではならない
+# トレーラに書き込むイメージのバージョンは IMAGE_VERSION で指定する（省略時は 0）
+
+set -Cue -o pipefail
+
+ELF=$(readlink -f "$1")
+IMAGE="${ELF}.image.bin"
+
+cd $(readlink -f $(dirname $0))
+
+# image-packer はホスト向けのルートのワークスペースにある
+(cd ../.. && cargo run --quiet --package image-packer -- --image-version "${IMAGE_VERSION:-0}" "$ELF" "$IMAGE")
+
+probe-rs download --chip-description-path chip-description.yaml --chip monazite_bank2 \
+  --binary-format bin --base-address 0x08000000 "$IMAGE"
@@ -5,7 +5,8 @@ MEMORY
   /* STM32H742xI/743xI/753xI       */
   /* STM32H745xI/747xI/755xI/757xI */
   /* STM32H7A3xI/7B3xI             */
-  FLASH  : ORIGIN = 0x08000000, LENGTH = 1M - 128K
+  /* Sector 7 is for the bootloader, and the last 64 bytes of sector 6 are for the image trailer (see bootmeta) */
+  FLASH  : ORIGIN = 0x08000000, LENGTH = 1M - 128K - 64
 
   /* STM32H742xG/743xG       */
   /* STM32H745xG/STM32H747xG */
@@ -1,17 +1,17 @@
-use std::net::Ipv4Addr;
+use std::{env, net::Ipv4Addr, sync::Arc, time::Duration};
 
 use c2a_dev_runtime as c2a_runtime;
-use c2a_monazite_example as _;
+use c2a_monazite_example::c2a_core::system::time_manager::TMGR_get_master_total_cycle;
 
 use c2a_monazite_adc_dev::Adc;
-use c2a_monazite_btmgr_dev::Btmgr;
-use c2a_monazite_ccsds_dev::Ccsds;
+use c2a_monazite_btmgr_dev::{Btmgr, ResetReason};
+use c2a_monazite_ccsds_dev::{Ccsds, Config as CcsdsConfig};
 use c2a_monazite_gpio_dev::Gpio;
 use c2a_monazite_iflash_dev::Iflash;
 use c2a_monazite_ramecc_dev::Ramecc;
 use c2a_monazite_thermometer_dev::Thermometer;
 use c2a_monazite_uart_dev::Uart;
-use c2a_monazite_wdt_dev::Wdt;
+use c2a_monazite_wdt_dev::{Source as WdtSource, Wdt};
 
 use c2a_monazite_adc_bind::C2A_MONAZITE_ADC;
 use c2a_monazite_btmgr_bind::C2A_MONAZITE_BTMGR;
@@ -23,41 +23,98 @@ use c2a_monazite_thermometer_bind::C2A_MONAZITE_THERMOMETER;
 use c2a_monazite_uart_bind::C2A_MONAZITE_UART;
 use c2a_monazite_wdt_bind::C2A_MONAZITE_WDT;
 
+use traffic_capture::{Capture, Replay, ReplayClock};
+
 macro_rules! dyn_static {
     ($v:expr) => {
         Box::leak(Box::new(Box::leak(Box::new($v)) as &'static _))
     };
 }
 
+// src_user/settings/system/obc_time_params.h の OBCT_STEP_IN_MSEC * OBCT_STEPS_PER_CYCLE
+const CYCLE_DURATION: Duration = Duration::from_millis(100);
+
+/// C2A のマスタークロックの時刻
+///
+/// 実時間ではなく C2A の周期処理に合わせて進むため、この時刻で動かすエミュレーションの結果がホストの負荷によらない。
+fn master_clock() -> Duration {
+    CYCLE_DURATION * unsafe { TMGR_get_master_total_cycle() }
+}
+
 #[allow(clippy::similar_names)]
 fn main() {
-    let wdt = Wdt::new();
+    // SILS_CAPTURE: UART・CCSDS の通信を記録するキャプチャファイル
+    // SILS_REPLAY: C2A の受信を再生するキャプチャファイル
+    // SILS_ADC_CONTROL: ADC の入力チャネルの値を設定する TCP の待ち受けアドレス（例: 127.0.0.1:22546）
+    let clock: ReplayClock = Arc::new(master_clock);
+    let capture = env::var_os("SILS_CAPTURE")
+        .map(|path| Capture::create(path, clock.clone()).expect("failed to create capture file"));
+    let replay = env::var_os("SILS_REPLAY")
+        .map(|path| Replay::open(path, clock.clone()).expect("failed to open replay file"));
+
+    let btmgr: &'static Btmgr = Box::leak(Box::new(
+        Btmgr::new("btmgr.bin").expect("failed to open btmgr state"),
+    ));
+    C2A_MONAZITE_BTMGR.set(Box::leak(Box::new(btmgr as &'static _)));
+
+    let wdt = Wdt::new(|source| {
+        btmgr.reset(match source {
+            WdtSource::Independent => ResetReason::IndependentWatchdogReset,
+            WdtSource::Window => ResetReason::WindowWatchdogReset,
+        })
+    });
     C2A_MONAZITE_WDT.set(dyn_static!(wdt));
 
     let gpio = Gpio::default();
+    gpio.handle().set_clock(Arc::new(master_clock));
     C2A_MONAZITE_GPIO.set(dyn_static!(gpio));
 
     let adc = Adc::new();
+    adc.handle().set_clock(Arc::new(master_clock));
+    if let Some(addr) = env::var_os("SILS_ADC_CONTROL") {
+        let addr = addr
+            .to_str()
+            .and_then(|addr| addr.parse().ok())
+            .expect("invalid SILS_ADC_CONTROL address");
+        adc.handle()
+            .serve_control(addr)
+            .expect("failed to serve ADC control");
+    }
     C2A_MONAZITE_ADC.set(dyn_static!(adc));
 
     let thermometer = Thermometer::new();
+    thermometer.handle().set_clock(Arc::new(master_clock));
     C2A_MONAZITE_THERMOMETER.set(dyn_static!(thermometer));
 
-    let btmgr = Btmgr::new();
-    C2A_MONAZITE_BTMGR.set(dyn_static!(btmgr));
-
-    let ccsds = Ccsds::new((Ipv4Addr::UNSPECIFIED, 22545).into());
+    let ccsds = Ccsds::new(
+        (Ipv4Addr::UNSPECIFIED, 22545).into(),
+        CcsdsConfig {
+            capture: capture.clone(),
+            replay: replay.clone(),
+            ..CcsdsConfig::default()
+        },
+    );
     C2A_MONAZITE_CCSDS.set(dyn_static!(ccsds));
 
     let uart = Uart::new((Ipv4Addr::UNSPECIFIED, 9696).into());
+    uart.set_clock(Arc::new(master_clock));
+    if let Some(capture) = capture {
+        uart.set_capture(capture);
+    }
+    if let Some(replay) = replay {
+        uart.set_replay(replay);
+    }
     C2A_MONAZITE_UART.set(dyn_static!(uart));
 
-    let iflash = Iflash::new();
+    let iflash = Iflash::new("iflash.bin").expect("failed to open iflash image");
     C2A_MONAZITE_IFLASH.set(dyn_static!(iflash));
 
     let ramecc = Ramecc::new();
+    ramecc.handle().set_clock(Arc::new(master_clock));
     C2A_MONAZITE_RAMECC.set(dyn_static!(ramecc));
 
     c2a_runtime::c2a_init();
+    // 実機の monazite-rt と同様に、C2A の初期化が終わった時点で起動に成功したものとする
+    c2a_monazite_btmgr_bind::BTMGR_confirm_boot();
     c2a_runtime::c2a_main();
 }
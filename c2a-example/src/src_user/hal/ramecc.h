@@ -3,6 +3,90 @@
 
 #include <stdint.h>
 
+/**
+ * @enum  RAMECC_ERR_CODE
+ * @brief RAMECC の関数の返り値
+ */
+typedef enum
+{
+  RAMECC_NO_FAULT_ERR      = -4,  //!< ECC エラーが検出されていない
+  RAMECC_INVALID_PARAM_ERR = -3,  //!< 不正なパラメータ
+  RAMECC_OK                =  0,  //!< 正常終了
+} RAMECC_ERR_CODE;
+
+/**
+ * @enum  RAMECC_REGION
+ * @brief ECC で保護されたメモリ領域
+ */
+typedef enum
+{
+  RAMECC_AXI_SRAM = 0,  //!< AXI SRAM
+  RAMECC_SRAM1_0  = 1,  //!< SRAM1 (0x3000_0000 - 0x3000_FFFF)
+  RAMECC_SRAM1_1  = 2,  //!< SRAM1 (0x3001_0000 - 0x3001_FFFF)
+  RAMECC_SRAM2_0  = 3,  //!< SRAM2 (0x3002_0000 - 0x3002_FFFF)
+  RAMECC_SRAM2_1  = 4,  //!< SRAM2 (0x3003_0000 - 0x3003_FFFF)
+  RAMECC_SRAM3    = 5,  //!< SRAM3
+  RAMECC_SRAM4    = 6,  //!< SRAM4
+  RAMECC_D0TCM    = 7,  //!< DTCM の偶数ワード
+  RAMECC_D1TCM    = 8,  //!< DTCM の奇数ワード
+  RAMECC_ITCM     = 9,  //!< ITCM
+  RAMECC_BSRAM    = 10, //!< Backup SRAM
+} RAMECC_REGION;
+
+/**
+ * @enum  RAMECC_SCRUB_TARGET
+ * @brief Memory scrubbing の対象となるメモリ領域
+ * @note  複数の領域は論理和で指定する．この順に読み出す
+ */
+typedef enum
+{
+  RAMECC_SCRUB_AXI_SRAM = 0x001,  //!< AXI SRAM
+  RAMECC_SCRUB_SRAM1_0  = 0x002,  //!< SRAM1 (0x3000_0000 - 0x3000_FFFF)
+  RAMECC_SCRUB_SRAM1_1  = 0x004,  //!< SRAM1 (0x3001_0000 - 0x3001_FFFF)
+  RAMECC_SCRUB_SRAM2_0  = 0x008,  //!< SRAM2 (0x3002_0000 - 0x3002_FFFF)
+  RAMECC_SCRUB_SRAM2_1  = 0x010,  //!< SRAM2 (0x3003_0000 - 0x3003_FFFF)
+  RAMECC_SCRUB_SRAM3    = 0x020,  //!< SRAM3
+  RAMECC_SCRUB_SRAM4    = 0x040,  //!< SRAM4
+  RAMECC_SCRUB_DTCM     = 0x080,  //!< DTCM
+  RAMECC_SCRUB_ITCM     = 0x100,  //!< ITCM
+  RAMECC_SCRUB_BSRAM    = 0x200,  //!< Backup SRAM
+} RAMECC_SCRUB_TARGET;
+
+#define RAMECC_MAX_SCRUBBING_WORDS (16384) //!< 1 回の Memory scrubbing で読み出せる最大のワード数（64 KiB）
+
+/**
+ * @struct RAMECC_ErrorCount
+ * @brief  メモリ領域ごとの ECC エラーの数
+ */
+typedef struct
+{
+  uint32_t single_error;                //!< single error の数
+  uint32_t double_error;                //!< double error の数
+  uint32_t double_error_on_byte_write;  //!< double error on byte write の数
+} RAMECC_ErrorCount;
+
+/**
+ * @struct RAMECC_Fault
+ * @brief  最後に検出された ECC エラー
+ */
+typedef struct
+{
+  uint32_t region;     //!< エラーが検出されたメモリ領域（RAMECC_REGION）
+  uint32_t address;    //!< エラーが検出されたアドレス
+  uint32_t data_low;   //!< エラーが検出されたデータの下位 32 bit
+  uint32_t data_high;  //!< エラーが検出されたデータの上位 32 bit（AXI SRAM, ITCM 以外では 0）
+} RAMECC_Fault;
+
+/**
+ * @struct RAMECC_ScrubbingProgress
+ * @brief  現在の周回の Memory scrubbing の進み具合
+ */
+typedef struct
+{
+  uint32_t scrubbed_bytes;  //!< 現在の周回で読み出したバイト数
+  uint32_t total_bytes;     //!< 1 周で読み出すバイト数
+} RAMECC_ScrubbingProgress;
+
 /**
  * @brief  Memory scrubbing の回数を返す．
  * @note
@@ -75,4 +159,61 @@ uint32_t RAMECC_get_scrubbing_interval(void);
  */
 void RAMECC_set_scrubbing_interval(uint32_t scrubbing_interval_tick);
 
+/**
+ * @brief  メモリ領域ごとの ECC エラーの数を返す．
+ * @note   DTCM の数は RAMECC_get_dtcm_* の内訳となり、それ以外の数は RAMECC_get_* の内訳となる
+ * @param  region: メモリ領域
+ * @param[out] count: エラーの数の書き込み先
+ * @return region が範囲外の場合は RAMECC_INVALID_PARAM_ERR
+ */
+RAMECC_ERR_CODE RAMECC_get_region_error(RAMECC_REGION region, RAMECC_ErrorCount* count);
+
+/**
+ * @brief  最後に検出された ECC エラーを返す．
+ * @note
+ * @param[out] fault: エラーの書き込み先
+ * @return 起動してから ECC エラーが検出されていない場合は RAMECC_NO_FAULT_ERR
+ */
+RAMECC_ERR_CODE RAMECC_get_last_fault(RAMECC_Fault* fault);
+
+/**
+ * @brief  Memory scrubbing の対象を返す．
+ * @note
+ * @param  None.
+ * @return RAMECC_SCRUB_TARGET の論理和
+ */
+uint32_t RAMECC_get_scrubbing_targets(void);
+
+/**
+ * @brief  Memory scrubbing の対象を設定する．
+ * @note   現在の周回を中断し，新しい対象の先頭から読み出し直す
+ * @param  targets: RAMECC_SCRUB_TARGET の論理和
+ * @return targets が 0 の場合や，未知のビットを含む場合は RAMECC_INVALID_PARAM_ERR
+ */
+RAMECC_ERR_CODE RAMECC_set_scrubbing_targets(uint32_t targets);
+
+/**
+ * @brief  1 回の Memory scrubbing で読み出すワード数を返す．
+ * @note
+ * @param  None.
+ * @return 1 回の Memory scrubbing で読み出すワード数
+ */
+uint32_t RAMECC_get_scrubbing_words(void);
+
+/**
+ * @brief  1 回の Memory scrubbing で読み出すワード数を設定する．
+ * @note   1 周にかかる時間は，対象の合計バイト数 / (words * 4) * Memory scrubbing の間隔となる
+ * @param  words: 1 回の Memory scrubbing で読み出すワード数（1 以上 RAMECC_MAX_SCRUBBING_WORDS 以下）
+ * @return words が範囲外の場合は RAMECC_INVALID_PARAM_ERR
+ */
+RAMECC_ERR_CODE RAMECC_set_scrubbing_words(uint32_t words);
+
+/**
+ * @brief  現在の周回の Memory scrubbing の進み具合を返す．
+ * @note
+ * @param[out] progress: 進み具合の書き込み先
+ * @retval None
+ */
+void RAMECC_get_scrubbing_progress(RAMECC_ScrubbingProgress* progress);
+
 #endif
@@ -0,0 +1,230 @@
+use crate::fecf::{fecf, FECF_SIZE};
+
+/// AOS Transfer Frame Primary Header のバイト数
+pub const AOS_PRIMARY_HEADER_SIZE: usize = 6;
+
+/// AOS Transfer Frame の最大長
+pub const AOS_MAX_FRAME_LEN: usize = 2048;
+
+// Transfer Frame Version Number（AOS は 2 進数で 01）
+const TFVN: u8 = 0b01;
+
+/// AOS Transfer Frame の組み立て・解析に失敗した理由
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AosError {
+    /// Primary Header に満たない
+    TooShort,
+    /// Transfer Frame Version Number が AOS のものでない
+    InvalidVersion(u8),
+    /// 組み立てたフレームの長さが最大長または出力先の長さを超える
+    TooLong(usize),
+}
+
+/// AOS Transfer Frame Primary Header
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AosHeader {
+    pub scid: u8,
+    /// Virtual Channel ID（6 bit）
+    pub vcid: u8,
+    /// Virtual Channel Frame Count（24 bit）
+    pub frame_count: u32,
+    /// Signaling Field の生値
+    pub signaling: u8,
+}
+
+impl AosHeader {
+    /// `bytes` の先頭から Primary Header を読み出す
+    ///
+    /// # Errors
+    /// `bytes` が Primary Header に満たない場合や、AOS Transfer Frame でない場合は [`AosError`] を返す。
+    pub fn parse(bytes: &[u8]) -> Result<Self, AosError> {
+        let Some(header) = bytes.get(..AOS_PRIMARY_HEADER_SIZE) else {
+            return Err(AosError::TooShort);
+        };
+        let version = header[0] >> 6;
+        if version != TFVN {
+            return Err(AosError::InvalidVersion(version));
+        }
+        Ok(Self {
+            scid: header[0] << 2 | header[1] >> 6,
+            vcid: header[1] & 0x3F,
+            frame_count: u32::from_be_bytes([0, header[2], header[3], header[4]]),
+            signaling: header[5],
+        })
+    }
+
+    /// Primary Header をバイト列に変換する
+    ///
+    /// `vcid` と `frame_count` の範囲外のビットは無視される。
+    pub fn to_bytes(&self) -> [u8; AOS_PRIMARY_HEADER_SIZE] {
+        let [_, count_hi, count_mid, count_lo] = self.frame_count.to_be_bytes();
+        [
+            TFVN << 6 | self.scid >> 2,
+            self.scid << 6 | self.vcid & 0x3F,
+            count_hi,
+            count_mid,
+            count_lo,
+            self.signaling,
+        ]
+    }
+}
+
+/// ダウンリンクする AOS Transfer Frame を組み立てる
+///
+/// 組み立てたフレームの SCID は常に [`AosFrameBuilder::scid`] になる。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AosFrameBuilder {
+    scid: u8,
+    with_fecf: bool,
+}
+
+impl AosFrameBuilder {
+    /// `with_fecf` が `true` の場合、組み立てたフレームの末尾に FECF を付加する
+    pub const fn new(scid: u8, with_fecf: bool) -> Self {
+        Self { scid, with_fecf }
+    }
+
+    pub fn scid(&self) -> u8 {
+        self.scid
+    }
+
+    pub fn set_scid(&mut self, scid: u8) {
+        self.scid = scid;
+    }
+
+    /// Primary Header より後ろ（FECF を除く）が `data_len` バイトのフレームの長さ
+    pub fn frame_len(&self, data_len: usize) -> usize {
+        let fecf_len = if self.with_fecf { FECF_SIZE } else { 0 };
+        AOS_PRIMARY_HEADER_SIZE + data_len + fecf_len
+    }
+
+    /// `header` と Primary Header より後ろ（FECF を除く）の `data` からフレームを組み立てて `out` に書き込み、
+    /// フレームの長さを返す
+    ///
+    /// `header` の SCID は無視される。
+    ///
+    /// # Errors
+    /// フレームが [`AOS_MAX_FRAME_LEN`] または `out` に収まらない場合は [`AosError::TooLong`] を返す。
+    pub fn build(
+        &self,
+        header: &AosHeader,
+        data: &[u8],
+        out: &mut [u8],
+    ) -> Result<usize, AosError> {
+        let len = self.frame_len(data.len());
+        if len > AOS_MAX_FRAME_LEN || len > out.len() {
+            return Err(AosError::TooLong(len));
+        }
+        let header = AosHeader {
+            scid: self.scid,
+            ..*header
+        };
+        let data_end = AOS_PRIMARY_HEADER_SIZE + data.len();
+        out[..AOS_PRIMARY_HEADER_SIZE].copy_from_slice(&header.to_bytes());
+        out[AOS_PRIMARY_HEADER_SIZE..data_end].copy_from_slice(data);
+        if self.with_fecf {
+            let fecf = fecf(&out[..data_end]);
+            out[data_end..len].copy_from_slice(&fecf.to_be_bytes());
+        }
+        Ok(len)
+    }
+
+    /// C2A の生成した（FECF を含まない）フレーム `frame` を、SCID を置き換えて組み立て直す
+    ///
+    /// # Errors
+    /// `frame` が AOS Transfer Frame でない場合や、組み立てたフレームが収まらない場合は [`AosError`] を返す。
+    pub fn rebuild(&self, frame: &[u8], out: &mut [u8]) -> Result<usize, AosError> {
+        let header = AosHeader::parse(frame)?;
+        self.build(&header, &frame[AOS_PRIMARY_HEADER_SIZE..], out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: AosHeader = AosHeader {
+        scid: 0xA5,
+        vcid: 0x2A,
+        frame_count: 0x12_3456,
+        signaling: 0x80,
+    };
+
+    #[test]
+    fn header_round_trips() {
+        let bytes = HEADER.to_bytes();
+        assert_eq!(bytes, [0x69, 0x6A, 0x12, 0x34, 0x56, 0x80]);
+        assert_eq!(AosHeader::parse(&bytes), Ok(HEADER));
+    }
+
+    #[test]
+    fn rejects_non_aos_frames() {
+        assert_eq!(AosHeader::parse(&[0x40; 5]), Err(AosError::TooShort));
+        assert_eq!(
+            AosHeader::parse(&[0x00; AOS_PRIMARY_HEADER_SIZE]),
+            Err(AosError::InvalidVersion(0))
+        );
+    }
+
+    #[test]
+    fn build_overrides_scid() {
+        let builder = AosFrameBuilder::new(0x3C, false);
+        let mut out = [0; 16];
+        let len = builder.build(&HEADER, &[1, 2, 3], &mut out).unwrap();
+        assert_eq!(len, 9);
+        let header = AosHeader::parse(&out).unwrap();
+        assert_eq!(
+            header,
+            AosHeader {
+                scid: 0x3C,
+                ..HEADER
+            }
+        );
+        assert_eq!(&out[AOS_PRIMARY_HEADER_SIZE..len], [1, 2, 3]);
+    }
+
+    #[test]
+    fn build_appends_fecf() {
+        let builder = AosFrameBuilder::new(0x3C, true);
+        let mut out = [0; 16];
+        let len = builder.build(&HEADER, &[1, 2, 3], &mut out).unwrap();
+        assert_eq!(len, 11);
+        assert_eq!(fecf(&out[..len]), 0);
+    }
+
+    #[test]
+    fn rebuild_keeps_everything_but_scid() {
+        let mut frame = [0; 444];
+        frame[..AOS_PRIMARY_HEADER_SIZE].copy_from_slice(&HEADER.to_bytes());
+        frame[AOS_PRIMARY_HEADER_SIZE..].fill(0xEE);
+        let mut builder = AosFrameBuilder::new(0, false);
+        builder.set_scid(0x42);
+        let mut out = [0; AOS_MAX_FRAME_LEN];
+        let len = builder.rebuild(&frame, &mut out).unwrap();
+        assert_eq!(len, frame.len());
+        assert_eq!(
+            AosHeader::parse(&out),
+            Ok(AosHeader {
+                scid: 0x42,
+                ..HEADER
+            })
+        );
+        assert_eq!(
+            out[AOS_PRIMARY_HEADER_SIZE..len],
+            frame[AOS_PRIMARY_HEADER_SIZE..]
+        );
+    }
+
+    #[test]
+    fn too_long_frame_is_rejected() {
+        let builder = AosFrameBuilder::new(0, true);
+        let mut out = [0; 8];
+        assert_eq!(
+            builder.build(&HEADER, &[0; 1], &mut out),
+            Err(AosError::TooLong(9))
+        );
+        let data = [0; AOS_MAX_FRAME_LEN];
+        let mut out = [0; 2 * AOS_MAX_FRAME_LEN];
+        assert!(builder.build(&HEADER, &data, &mut out).is_err());
+    }
+}
@@ -0,0 +1,146 @@
+/// CLTU の Start Sequence
+pub const CLTU_START_SEQUENCE: [u8; 2] = [0xEB, 0x90];
+
+/// CLTU の Tail Sequence
+pub const CLTU_TAIL_SEQUENCE: [u8; 8] = [0xC5, 0xC5, 0xC5, 0xC5, 0xC5, 0xC5, 0xC5, 0x79];
+
+/// BCH 符号ブロックのバイト数
+pub const CODEBLOCK_SIZE: usize = 8;
+
+/// BCH 符号ブロックのうち情報部のバイト数
+pub const CODEBLOCK_INFO_SIZE: usize = 7;
+
+// 最後の符号ブロックの情報部の余りを埋める値
+const FILL: u8 = 0x55;
+
+// BCH(63, 56) の生成多項式 x^7 + x^6 + x^2 + 1
+const GENERATOR: u64 = 0b1100_0101;
+
+/// 63 bit の多項式 `value` を生成多項式で割った余り
+fn remainder(mut value: u64) -> u8 {
+    for bit in (7..63).rev() {
+        if value >> bit & 1 != 0 {
+            value ^= GENERATOR << (bit - 7);
+        }
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    let remainder = value as u8;
+    remainder
+}
+
+fn info_to_u64(info: [u8; CODEBLOCK_INFO_SIZE]) -> u64 {
+    let mut bytes = [0; 8];
+    bytes[1..].copy_from_slice(&info);
+    u64::from_be_bytes(bytes)
+}
+
+/// 情報部 `info` を BCH 符号ブロックに符号化する
+///
+/// パリティは反転して格納し、最後の 1 bit（Filler Bit）は 0 とする。
+pub fn encode_codeblock(info: &[u8; CODEBLOCK_INFO_SIZE]) -> [u8; CODEBLOCK_SIZE] {
+    let parity = !remainder(info_to_u64(*info) << 7) & 0x7F;
+    let mut codeblock = [0; CODEBLOCK_SIZE];
+    codeblock[..CODEBLOCK_INFO_SIZE].copy_from_slice(info);
+    codeblock[CODEBLOCK_INFO_SIZE] = parity << 1;
+    codeblock
+}
+
+/// BCH 符号ブロックを復号し、情報部と訂正したビット数を返す
+///
+/// 1 bit の誤りは訂正する。訂正できない場合（Tail Sequence を含む）は `None` を返す。
+pub fn decode_codeblock(
+    codeblock: &[u8; CODEBLOCK_SIZE],
+) -> Option<([u8; CODEBLOCK_INFO_SIZE], u32)> {
+    let mut info = [0; CODEBLOCK_INFO_SIZE];
+    info.copy_from_slice(&codeblock[..CODEBLOCK_INFO_SIZE]);
+    let parity = !(codeblock[CODEBLOCK_INFO_SIZE] >> 1) & 0x7F;
+    let received = info_to_u64(info) << 7 | u64::from(parity);
+    let syndrome = remainder(received);
+    if syndrome == 0 {
+        return Some((info, 0));
+    }
+    // 1 bit の誤りであれば、誤りの位置の単項式の余りがシンドロームに一致する
+    let position = (0..63).find(|&bit| remainder(1 << bit) == syndrome)?;
+    let corrected = received ^ 1 << position;
+    let bytes = (corrected >> 7).to_be_bytes();
+    info.copy_from_slice(&bytes[1..]);
+    Some((info, 1))
+}
+
+/// `frame_len` バイトのフレームを符号化した CLTU のバイト数
+pub const fn cltu_len(frame_len: usize) -> usize {
+    CLTU_START_SEQUENCE.len()
+        + frame_len.div_ceil(CODEBLOCK_INFO_SIZE) * CODEBLOCK_SIZE
+        + CLTU_TAIL_SEQUENCE.len()
+}
+
+/// `frame` を CLTU に符号化して `out` に書き込み、CLTU のバイト数を返す
+///
+/// # Panics
+/// `out` が [`cltu_len`] バイトに満たない場合は panic する。
+pub fn encode_cltu(frame: &[u8], out: &mut [u8]) -> usize {
+    let len = cltu_len(frame.len());
+    assert!(out.len() >= len);
+    let (start, rest) = out.split_at_mut(CLTU_START_SEQUENCE.len());
+    start.copy_from_slice(&CLTU_START_SEQUENCE);
+    let mut codeblocks = rest.chunks_exact_mut(CODEBLOCK_SIZE);
+    for (chunk, codeblock) in frame.chunks(CODEBLOCK_INFO_SIZE).zip(&mut codeblocks) {
+        let mut info = [FILL; CODEBLOCK_INFO_SIZE];
+        info[..chunk.len()].copy_from_slice(chunk);
+        codeblock.copy_from_slice(&encode_codeblock(&info));
+    }
+    codeblocks
+        .next()
+        .expect("BUG: no room for tail sequence")
+        .copy_from_slice(&CLTU_TAIL_SEQUENCE);
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INFO: [u8; CODEBLOCK_INFO_SIZE] = [0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC, 0xDE];
+
+    #[test]
+    fn codeblock_round_trips() {
+        let codeblock = encode_codeblock(&INFO);
+        assert_eq!(codeblock[7] & 1, 0);
+        assert_eq!(decode_codeblock(&codeblock), Some((INFO, 0)));
+    }
+
+    #[test]
+    fn single_bit_error_is_corrected() {
+        let codeblock = encode_codeblock(&INFO);
+        // Filler Bit を除く 63 bit のどこが反転しても訂正できる
+        for bit in 0..63 {
+            let mut corrupted = codeblock;
+            corrupted[bit / 8] ^= 0x80 >> (bit % 8);
+            assert_eq!(decode_codeblock(&corrupted), Some((INFO, 1)), "bit {bit}");
+        }
+    }
+
+    #[test]
+    fn double_bit_error_is_detected() {
+        let mut codeblock = encode_codeblock(&INFO);
+        codeblock[0] ^= 0x81;
+        assert_eq!(decode_codeblock(&codeblock), None);
+    }
+
+    #[test]
+    fn tail_sequence_is_uncorrectable() {
+        assert_eq!(decode_codeblock(&CLTU_TAIL_SEQUENCE), None);
+    }
+
+    #[test]
+    fn cltu_layout() {
+        let frame = [0xAA; 10];
+        let mut out = [0; 64];
+        let len = encode_cltu(&frame, &mut out);
+        assert_eq!(len, 2 + 2 * 8 + 8);
+        assert_eq!(out[..2], CLTU_START_SEQUENCE);
+        assert_eq!(out[len - 8..len], CLTU_TAIL_SEQUENCE);
+        let (info, _) = decode_codeblock(out[10..18].try_into().unwrap()).unwrap();
+        assert_eq!(info, [0xAA, 0xAA, 0xAA, FILL, FILL, FILL, FILL]);
+    }
+}
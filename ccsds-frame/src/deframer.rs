@@ -0,0 +1,268 @@
+use crate::cltu::{decode_codeblock, CLTU_START_SEQUENCE, CODEBLOCK_INFO_SIZE, CODEBLOCK_SIZE};
+use crate::stats::RxStats;
+use crate::tc::{parse_tc_frame, TC_MAX_FRAME_LEN, TC_PRIMARY_HEADER_SIZE};
+
+/// Attached Sync Marker
+pub const ASM: [u8; 4] = [0x1A, 0xCF, 0xFC, 0x1D];
+
+// 最大長のフレームを CLTU で受信した場合の情報部のバイト数
+const BUFFER_SIZE: usize = TC_MAX_FRAME_LEN.div_ceil(CODEBLOCK_INFO_SIZE) * CODEBLOCK_INFO_SIZE;
+
+/// バイト列中のフレームの区切り方
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SyncMode {
+    /// [`ASM`] の直後に TC Transfer Frame が続く
+    Asm,
+    /// CLTU（Start Sequence・BCH 符号ブロック・Tail Sequence）
+    Cltu,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum State {
+    /// 同期パターンを探している
+    Search,
+    /// ASM の後のフレームを読んでいる
+    Frame,
+    /// CLTU の符号ブロックを読んでいる
+    Codeblock,
+}
+
+/// バイト列から TC Transfer Frame を切り出す
+///
+/// 切り出したフレームは FECF を検証し、受信の統計情報を [`Deframer::stats`] に記録する。
+pub struct Deframer {
+    mode: SyncMode,
+    state: State,
+    // 直近に読んだ 4 バイト
+    window: u32,
+    buf: [u8; BUFFER_SIZE],
+    len: usize,
+    codeblock: [u8; CODEBLOCK_SIZE],
+    codeblock_len: usize,
+    corrected_errors: u32,
+    stats: RxStats,
+}
+
+impl Deframer {
+    pub const fn new(mode: SyncMode) -> Self {
+        Self {
+            mode,
+            state: State::Search,
+            window: 0,
+            buf: [0; BUFFER_SIZE],
+            len: 0,
+            codeblock: [0; CODEBLOCK_SIZE],
+            codeblock_len: 0,
+            corrected_errors: 0,
+            stats: RxStats {
+                corrupted_frames: 0,
+                overflowed_frames: 0,
+                found_starts: 0,
+                skipped_frames: 0,
+                last_frame_corrected_errors: 0,
+            },
+        }
+    }
+
+    pub fn stats(&self) -> RxStats {
+        self.stats
+    }
+
+    /// 呼び出し側でフレームを破棄した場合に統計情報を更新するために用いる
+    pub fn stats_mut(&mut self) -> &mut RxStats {
+        &mut self.stats
+    }
+
+    /// 読みかけのフレームを破棄し、同期パターンを探し直す
+    pub fn reset(&mut self) {
+        self.state = State::Search;
+        self.window = 0;
+    }
+
+    /// 1 バイト読み進め、正しいフレームが揃った場合はそれを返す
+    pub fn push(&mut self, byte: u8) -> Option<&[u8]> {
+        match self.state {
+            State::Search => {
+                self.window = self.window << 8 | u32::from(byte);
+                let found = match self.mode {
+                    SyncMode::Asm => self.window == u32::from_be_bytes(ASM),
+                    SyncMode::Cltu => {
+                        self.window & 0xFFFF == u32::from(u16::from_be_bytes(CLTU_START_SEQUENCE))
+                    }
+                };
+                if found {
+                    self.stats.count_start();
+                    self.window = 0;
+                    self.len = 0;
+                    self.codeblock_len = 0;
+                    self.corrected_errors = 0;
+                    self.state = match self.mode {
+                        SyncMode::Asm => State::Frame,
+                        SyncMode::Cltu => State::Codeblock,
+                    };
+                }
+                None
+            }
+            State::Frame => {
+                self.buf[self.len] = byte;
+                self.len += 1;
+                if self.len < TC_PRIMARY_HEADER_SIZE {
+                    return None;
+                }
+                // Frame Length は「フレームの長さ - 1」
+                let frame_len =
+                    usize::from(u16::from_be_bytes([self.buf[2], self.buf[3]]) & 0x03FF) + 1;
+                if self.len < frame_len {
+                    return None;
+                }
+                self.complete()
+            }
+            State::Codeblock => {
+                self.codeblock[self.codeblock_len] = byte;
+                self.codeblock_len += 1;
+                if self.codeblock_len < CODEBLOCK_SIZE {
+                    return None;
+                }
+                self.codeblock_len = 0;
+                // 訂正できない符号ブロック（通常は Tail Sequence）で CLTU が終わる
+                let Some((info, corrected)) = decode_codeblock(&self.codeblock) else {
+                    return self.complete();
+                };
+                if self.len + CODEBLOCK_INFO_SIZE > BUFFER_SIZE {
+                    self.stats.count_overflowed();
+                    self.reset();
+                    return None;
+                }
+                self.buf[self.len..self.len + CODEBLOCK_INFO_SIZE].copy_from_slice(&info);
+                self.len += CODEBLOCK_INFO_SIZE;
+                self.corrected_errors += corrected;
+                None
+            }
+        }
+    }
+
+    fn complete(&mut self) -> Option<&[u8]> {
+        self.reset();
+        let Ok(header) = parse_tc_frame(&self.buf[..self.len], true) else {
+            self.stats.count_corrupted();
+            return None;
+        };
+        self.stats.count_received(self.corrected_errors);
+        Some(&self.buf[..header.frame_len])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cltu::{cltu_len, encode_cltu};
+    use crate::fecf::fecf;
+
+    fn tc_frame(data: &[u8]) -> Vec<u8> {
+        let length_field = u16::try_from(TC_PRIMARY_HEADER_SIZE + data.len() + 2 - 1).unwrap();
+        let [len_hi, len_lo] = length_field.to_be_bytes();
+        let mut frame = vec![0x20, 0x01, len_hi, len_lo, 0x00];
+        frame.extend_from_slice(data);
+        let fecf = fecf(&frame);
+        frame.extend_from_slice(&fecf.to_be_bytes());
+        frame
+    }
+
+    fn cltu(frame: &[u8]) -> Vec<u8> {
+        let mut out = vec![0; cltu_len(frame.len())];
+        encode_cltu(frame, &mut out);
+        out
+    }
+
+    fn deframe(deframer: &mut Deframer, stream: &[u8]) -> Vec<Vec<u8>> {
+        stream
+            .iter()
+            .filter_map(|&byte| deframer.push(byte).map(<[u8]>::to_vec))
+            .collect()
+    }
+
+    #[test]
+    fn asm_stream() {
+        let frames = [tc_frame(&[1, 2, 3]), tc_frame(&[0x1A; 40])];
+        let mut stream = vec![0x00, 0x1A, 0xCF];
+        for frame in &frames {
+            stream.extend_from_slice(&ASM);
+            stream.extend_from_slice(frame);
+            stream.extend_from_slice(&[0xFF; 3]);
+        }
+        let mut deframer = Deframer::new(SyncMode::Asm);
+        assert_eq!(deframe(&mut deframer, &stream), frames);
+        let stats = deframer.stats();
+        assert_eq!(stats.found_starts, 2);
+        assert_eq!(stats.corrupted_frames, 0);
+    }
+
+    #[test]
+    fn asm_corrupted_frame() {
+        let good = tc_frame(&[4, 5, 6]);
+        let mut bad = tc_frame(&[1, 2, 3]);
+        bad[6] ^= 0x10;
+        let mut stream = Vec::new();
+        for frame in [&bad, &good] {
+            stream.extend_from_slice(&ASM);
+            stream.extend_from_slice(frame);
+        }
+        let mut deframer = Deframer::new(SyncMode::Asm);
+        assert_eq!(deframe(&mut deframer, &stream), [good]);
+        let stats = deframer.stats();
+        assert_eq!(stats.found_starts, 2);
+        assert_eq!(stats.corrupted_frames, 1);
+    }
+
+    #[test]
+    fn cltu_stream() {
+        let frames = [tc_frame(&[1, 2, 3]), tc_frame(&[0xEB; 30])];
+        let mut stream = vec![0x55; 5];
+        for frame in &frames {
+            stream.extend_from_slice(&cltu(frame));
+            stream.extend_from_slice(&[0x55; 4]);
+        }
+        let mut deframer = Deframer::new(SyncMode::Cltu);
+        assert_eq!(deframe(&mut deframer, &stream), frames);
+        let stats = deframer.stats();
+        assert_eq!(stats.found_starts, 2);
+        assert_eq!(stats.last_frame_corrected_errors, 0);
+    }
+
+    #[test]
+    fn cltu_bit_errors_are_corrected() {
+        let frame = tc_frame(&[0x42; 20]);
+        let mut stream = cltu(&frame);
+        // 異なる符号ブロックの 1 bit ずつを反転する
+        stream[3] ^= 0x01;
+        stream[12] ^= 0x40;
+        let mut deframer = Deframer::new(SyncMode::Cltu);
+        assert_eq!(deframe(&mut deframer, &stream), [frame]);
+        assert_eq!(deframer.stats().last_frame_corrected_errors, 2);
+    }
+
+    #[test]
+    fn cltu_uncorrectable_codeblock() {
+        let frame = tc_frame(&[0x42; 20]);
+        let mut stream = cltu(&frame);
+        stream[12] ^= 0x41;
+        let mut deframer = Deframer::new(SyncMode::Cltu);
+        assert!(deframe(&mut deframer, &stream).is_empty());
+        assert_eq!(deframer.stats().corrupted_frames, 1);
+    }
+
+    #[test]
+    fn cltu_without_tail_overflows() {
+        let mut stream = vec![0xEB, 0x90];
+        for _ in 0..=BUFFER_SIZE / CODEBLOCK_INFO_SIZE {
+            stream.extend_from_slice(&crate::cltu::encode_codeblock(&[0; 7]));
+        }
+        let frame = tc_frame(&[7; 7]);
+        stream.extend_from_slice(&cltu(&frame));
+        let mut deframer = Deframer::new(SyncMode::Cltu);
+        assert_eq!(deframe(&mut deframer, &stream), [frame]);
+        let stats = deframer.stats();
+        assert_eq!(stats.overflowed_frames, 1);
+        assert_eq!(stats.found_starts, 2);
+    }
+}
@@ -0,0 +1,22 @@
+use crc::{Crc, CRC_16_IBM_3740};
+
+/// Frame Error Control Field のバイト数
+pub const FECF_SIZE: usize = 2;
+
+// CCSDS の FECF は CRC-16-CCITT（多項式 0x1021、初期値 0xFFFF、反転なし）
+const CRC16: Crc<u16> = Crc::<u16>::new(&CRC_16_IBM_3740);
+
+/// `bytes` に対する Frame Error Control Field の値を計算する
+pub fn fecf(bytes: &[u8]) -> u16 {
+    CRC16.checksum(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_value() {
+        assert_eq!(fecf(b"123456789"), 0x29B1);
+    }
+}
@@ -0,0 +1,19 @@
+#![cfg_attr(not(test), no_std)]
+#![allow(clippy::must_use_candidate)]
+
+mod aos;
+mod cltu;
+mod deframer;
+mod fecf;
+mod stats;
+mod tc;
+
+pub use aos::{AosError, AosFrameBuilder, AosHeader, AOS_MAX_FRAME_LEN, AOS_PRIMARY_HEADER_SIZE};
+pub use cltu::{
+    cltu_len, decode_codeblock, encode_cltu, encode_codeblock, CLTU_START_SEQUENCE,
+    CLTU_TAIL_SEQUENCE, CODEBLOCK_INFO_SIZE, CODEBLOCK_SIZE,
+};
+pub use deframer::{Deframer, SyncMode, ASM};
+pub use fecf::{fecf, FECF_SIZE};
+pub use stats::RxStats;
+pub use tc::{parse_tc_frame, TcError, TcHeader, TC_MAX_FRAME_LEN, TC_PRIMARY_HEADER_SIZE};
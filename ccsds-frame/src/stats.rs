@@ -0,0 +1,43 @@
+/// 受信の統計情報
+///
+/// 各フィールドは C2A の `CCSDS_RxStats` と同じ意味を持つ。カウンタは飽和せずに 0 に戻る。
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct RxStats {
+    /// 訂正不能なフレーム数
+    pub corrupted_frames: u32,
+    /// 終了シーケンスが見つからなかったフレーム数
+    pub overflowed_frames: u32,
+    /// フレームの開始シーケンスが見つかった回数
+    pub found_starts: u32,
+    /// 受信バッファが溢れたためにスキップされたフレーム数
+    pub skipped_frames: u32,
+    /// 最後に受信されたフレームにおける訂正エラー数
+    pub last_frame_corrected_errors: u32,
+}
+
+impl RxStats {
+    /// フレームの開始を見つけたことを記録する
+    pub fn count_start(&mut self) {
+        self.found_starts = self.found_starts.wrapping_add(1);
+    }
+
+    /// 訂正不能なフレームを破棄したことを記録する
+    pub fn count_corrupted(&mut self) {
+        self.corrupted_frames = self.corrupted_frames.wrapping_add(1);
+    }
+
+    /// 終了シーケンスが見つからずにフレームを破棄したことを記録する
+    pub fn count_overflowed(&mut self) {
+        self.overflowed_frames = self.overflowed_frames.wrapping_add(1);
+    }
+
+    /// 受信バッファに収まらずにフレームを破棄したことを記録する
+    pub fn count_skipped(&mut self) {
+        self.skipped_frames = self.skipped_frames.wrapping_add(1);
+    }
+
+    /// フレームを受信したことを、そのフレームで訂正したエラー数とともに記録する
+    pub fn count_received(&mut self, corrected_errors: u32) {
+        self.last_frame_corrected_errors = corrected_errors;
+    }
+}
@@ -0,0 +1,146 @@
+use crate::fecf::{fecf, FECF_SIZE};
+
+/// TC Transfer Frame Primary Header のバイト数
+pub const TC_PRIMARY_HEADER_SIZE: usize = 5;
+
+/// TC Transfer Frame の最大長
+pub const TC_MAX_FRAME_LEN: usize = 1024;
+
+// Transfer Frame Version Number（TC は 2 進数で 00）
+const TFVN: u8 = 0b00;
+
+/// TC Transfer Frame の解析に失敗した理由
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TcError {
+    /// Primary Header に満たない
+    TooShort,
+    /// Transfer Frame Version Number が TC のものでない
+    InvalidVersion(u8),
+    /// Frame Length の示す長さに満たない、または Primary Header と FECF が収まらない
+    InvalidLength { frame_len: usize, actual: usize },
+    /// FECF が一致しない
+    FecfMismatch,
+}
+
+/// TC Transfer Frame Primary Header
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TcHeader {
+    pub bypass: bool,
+    pub control_command: bool,
+    /// Spacecraft ID（10 bit）
+    pub scid: u16,
+    /// Virtual Channel ID（6 bit）
+    pub vcid: u8,
+    /// Primary Header と FECF を含むフレームの長さ
+    pub frame_len: usize,
+    pub sequence: u8,
+}
+
+/// `bytes` の先頭にある TC Transfer Frame を解析する
+///
+/// フレームの長さは [`TcHeader::frame_len`] で、`bytes` のそれより後ろは無視される。
+/// `with_fecf` が `true` の場合は、フレームの末尾の FECF を検証する。
+///
+/// # Errors
+/// TC Transfer Frame として不正な場合は [`TcError`] を返す。
+pub fn parse_tc_frame(bytes: &[u8], with_fecf: bool) -> Result<TcHeader, TcError> {
+    let Some(header) = bytes.get(..TC_PRIMARY_HEADER_SIZE) else {
+        return Err(TcError::TooShort);
+    };
+    let version = header[0] >> 6;
+    if version != TFVN {
+        return Err(TcError::InvalidVersion(version));
+    }
+    // Frame Length は「フレームの長さ - 1」
+    let frame_len = usize::from(u16::from_be_bytes([header[2], header[3]]) & 0x03FF) + 1;
+    let fecf_len = if with_fecf { FECF_SIZE } else { 0 };
+    if frame_len > bytes.len() || frame_len < TC_PRIMARY_HEADER_SIZE + fecf_len {
+        return Err(TcError::InvalidLength {
+            frame_len,
+            actual: bytes.len(),
+        });
+    }
+    if with_fecf && fecf(&bytes[..frame_len]) != 0 {
+        return Err(TcError::FecfMismatch);
+    }
+    Ok(TcHeader {
+        bypass: header[0] & 0x20 != 0,
+        control_command: header[0] & 0x10 != 0,
+        scid: u16::from_be_bytes([header[0], header[1]]) & 0x03FF,
+        vcid: header[2] >> 2,
+        frame_len,
+        sequence: header[4],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(data: &[u8]) -> Vec<u8> {
+        let frame_len = TC_PRIMARY_HEADER_SIZE + data.len() + FECF_SIZE;
+        let length_field = u16::try_from(frame_len - 1).unwrap();
+        let [len_hi, len_lo] = length_field.to_be_bytes();
+        // BD フレーム, SCID 0x2A5, VCID 0x01, シーケンス番号 7
+        let mut frame = vec![0x22, 0xA5, 0x04 | len_hi, len_lo, 0x07];
+        frame.extend_from_slice(data);
+        let fecf = fecf(&frame);
+        frame.extend_from_slice(&fecf.to_be_bytes());
+        frame
+    }
+
+    #[test]
+    fn parses_header() {
+        let frame = frame(&[0xAA; 10]);
+        assert_eq!(
+            parse_tc_frame(&frame, true),
+            Ok(TcHeader {
+                bypass: true,
+                control_command: false,
+                scid: 0x2A5,
+                vcid: 0x01,
+                frame_len: 17,
+                sequence: 7,
+            })
+        );
+    }
+
+    #[test]
+    fn trailing_bytes_are_ignored() {
+        let mut bytes = frame(&[0xAA; 10]);
+        bytes.extend_from_slice(&[0x55; 6]);
+        assert_eq!(parse_tc_frame(&bytes, true).unwrap().frame_len, 17);
+    }
+
+    #[test]
+    fn corrupted_frame_is_rejected() {
+        let mut frame = frame(&[0xAA; 10]);
+        frame[8] ^= 0x04;
+        assert_eq!(parse_tc_frame(&frame, true), Err(TcError::FecfMismatch));
+        // FECF を検証しない場合は受け付ける
+        assert!(parse_tc_frame(&frame, false).is_ok());
+    }
+
+    #[test]
+    fn truncated_frame_is_rejected() {
+        let frame = frame(&[0xAA; 10]);
+        assert_eq!(parse_tc_frame(&frame[..3], true), Err(TcError::TooShort));
+        assert_eq!(
+            parse_tc_frame(&frame[..16], true),
+            Err(TcError::InvalidLength {
+                frame_len: 17,
+                actual: 16
+            })
+        );
+    }
+
+    #[test]
+    fn non_tc_frame_is_rejected() {
+        let mut frame = frame(&[0xAA; 10]);
+        frame[0] |= 0x40;
+        assert_eq!(
+            parse_tc_frame(&frame, true),
+            Err(TcError::InvalidVersion(1))
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread,
+};
+
+use c2a_monazite_adc_bind::InputChannelId;
+
+use crate::Handle;
+
+pub fn serve_in_background(handle: Handle, addr: SocketAddr) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    thread::spawn(move || {
+        for incoming in listener.incoming() {
+            let stream = match incoming {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("failed to accept ADC control client: {e}");
+                    continue;
+                }
+            };
+            let handle = handle.clone();
+            thread::spawn(move || {
+                if let Err(e) = serve_client(&handle, stream) {
+                    eprintln!("ADC control client has disconnected: {e}");
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+fn serve_client(handle: &Handle, stream: TcpStream) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        match execute(handle, &line?) {
+            Ok(()) => writeln!(writer, "ok")?,
+            Err(reason) => writeln!(writer, "error: {reason}")?,
+        }
+    }
+    Ok(())
+}
+
+fn execute(handle: &Handle, line: &str) -> Result<(), &'static str> {
+    let mut words = line.split_whitespace();
+    let (Some(ch), Some(value), None) = (words.next(), words.next(), words.next()) else {
+        return Err("expected `<channel> <value>`");
+    };
+    let ch = ch
+        .parse::<u8>()
+        .ok()
+        .and_then(|ch| InputChannelId::try_from(ch).ok())
+        .ok_or("invalid channel")?;
+    let value = value
+        .parse::<u16>()
+        .ok()
+        .filter(|value| *value < 1 << 12)
+        .ok_or("invalid value")?;
+    handle.set_value(ch, value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use c2a_monazite_adc_bind::Adc as _;
+
+    use super::*;
+    use crate::Adc;
+
+    fn ch(ch: u8) -> InputChannelId {
+        InputChannelId::try_from(ch).unwrap()
+    }
+
+    #[test]
+    fn sets_channel_value() {
+        let adc = Adc::new();
+        assert_eq!(execute(&adc.handle(), " 2  4095 "), Ok(()));
+        assert_eq!(adc.get_value(ch(2)), 4095);
+        assert_eq!(adc.get_value(ch(1)), 1);
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        let adc = Adc::new();
+        let handle = adc.handle();
+        for (line, reason) in [
+            ("", "expected `<channel> <value>`"),
+            ("1", "expected `<channel> <value>`"),
+            ("1 2 3", "expected `<channel> <value>`"),
+            ("x 1", "invalid channel"),
+            ("-1 1", "invalid channel"),
+            ("3 0", "invalid channel"),
+            ("0 x", "invalid value"),
+            ("0 -1", "invalid value"),
+            ("0 4096", "invalid value"),
+        ] {
+            assert_eq!(execute(&handle, line), Err(reason), "{line:?}");
+        }
+        assert_eq!(adc.get_value(ch(0)), 0);
+    }
+}
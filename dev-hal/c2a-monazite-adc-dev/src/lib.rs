@@ -1,12 +1,111 @@
-use c2a_monazite_adc_bind::{Error, InputChannelId, TestChannelId};
+mod control;
+mod source;
 
-pub struct Adc(core::marker::PhantomData<()>);
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use c2a_monazite_adc_bind::{Error, InputChannelId, TestChannelId, INPUT_CHANNEL_NUM};
+
+pub use sim_trace::Trace;
+use source::Noise;
+pub use source::Source;
+
+/// 入力チャネルの値を生成する時刻
+pub type Clock = Arc<dyn Fn() -> Duration + Send + Sync>;
+
+struct Channel {
+    source: Source,
+    // `source` を設定した時刻
+    start: Duration,
+    noise: Noise,
+}
+
+struct State {
+    channels: Vec<Channel>,
+    clock: Clock,
+}
+
+pub struct Adc {
+    state: Arc<Mutex<State>>,
+}
 
 impl Adc {
+    /// 入力チャネルはそれぞれチャネル番号を一定の値として返し、時刻は構築した時点からの実時間で数える
     #[allow(clippy::new_without_default)]
     #[must_use]
     pub fn new() -> Self {
-        Self(core::marker::PhantomData)
+        let start = Instant::now();
+        let channels = (0..)
+            .take(INPUT_CHANNEL_NUM)
+            .map(|ch: u16| Channel {
+                source: Source::Constant(ch),
+                start: Duration::ZERO,
+                noise: Noise::new(u64::from(ch)),
+            })
+            .collect();
+        Self {
+            state: Arc::new(Mutex::new(State {
+                channels,
+                clock: Arc::new(move || start.elapsed()),
+            })),
+        }
+    }
+
+    /// C2A から独立して入力チャネルの値を設定するためのハンドルを返す
+    #[must_use]
+    pub fn handle(&self) -> Handle {
+        Handle {
+            state: self.state.clone(),
+        }
+    }
+}
+
+/// [`Adc`] の入力チャネルの値を設定する
+///
+/// 複製したものは同じ [`Adc`] を操作する。
+#[derive(Clone)]
+pub struct Handle {
+    state: Arc<Mutex<State>>,
+}
+
+impl Handle {
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// 時刻を数える方法を `clock` に変更する
+    ///
+    /// 設定済みの [`Source`] の開始時刻は変わらないため、[`Handle::set_source`] の前に変更する。
+    pub fn set_clock(&self, clock: Clock) {
+        self.lock().clock = clock;
+    }
+
+    /// 入力チャネル `ch` の値を、現在の時刻から始まる `source` で生成する
+    pub fn set_source(&self, ch: InputChannelId, source: Source) {
+        let mut state = self.lock();
+        let start = (state.clock)();
+        let channel = &mut state.channels[usize::from(u8::from(ch))];
+        channel.source = source;
+        channel.start = start;
+    }
+
+    /// 入力チャネル `ch` の値を `value` に固定する
+    pub fn set_value(&self, ch: InputChannelId, value: u16) {
+        self.set_source(ch, Source::Constant(value));
+    }
+
+    /// `addr` で TCP 接続を待ち受け、受信した行に従って入力チャネルの値を設定する
+    ///
+    /// 各行は「チャネル番号 値」の形式で、設定できた場合は `ok`、できなかった場合は `error: 理由` を返す。
+    ///
+    /// # Errors
+    /// 待ち受けに失敗した場合は [`io::Error`] を返す。
+    pub fn serve_control(&self, addr: SocketAddr) -> io::Result<()> {
+        control::serve_in_background(self.clone(), addr)
     }
 }
 
@@ -16,12 +115,11 @@ impl c2a_monazite_adc_bind::Adc for Adc {
     }
 
     fn get_value(&self, ch: InputChannelId) -> u16 {
-        match u8::from(ch) {
-            0 => 0,
-            1 => 1,
-            2 => 2,
-            _ => unreachable!("out of range"),
-        }
+        let mut state = self.state.lock().unwrap();
+        let now = (state.clock)();
+        let channel = &mut state.channels[usize::from(u8::from(ch))];
+        let elapsed = now.saturating_sub(channel.start);
+        channel.source.sample(elapsed, &mut channel.noise)
     }
 
     fn get_test_value(&self, ch: TestChannelId) -> u16 {
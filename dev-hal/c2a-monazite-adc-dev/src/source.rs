@@ -0,0 +1,124 @@
+use std::{f64::consts::TAU, time::Duration};
+
+use sim_trace::Trace;
+
+// 12 bit の ADC の最大値
+const MAX_12BIT: f64 = 4095.0;
+
+/// 入力チャネルの値を生成する方法
+///
+/// 値は ADC の生の値（0 から 4095）で表し、範囲外の値は飽和させる。
+#[derive(Clone)]
+pub enum Source {
+    /// 一定の値
+    Constant(u16),
+    /// 時系列を線形補間した値
+    Trace(Trace),
+    /// `offset` を中心とする振幅 `amplitude`・周期 `period` の正弦波に、最大 `noise` の一様な雑音を加えた値
+    Sine {
+        offset: f64,
+        amplitude: f64,
+        period: Duration,
+        noise: f64,
+    },
+    /// `start` から 1 秒あたり `slope` ずつ変化する値に、最大 `noise` の一様な雑音を加えた値
+    Ramp { start: f64, slope: f64, noise: f64 },
+}
+
+impl Source {
+    /// 設定してから `elapsed` 経過した時点の値
+    pub(crate) fn sample(&self, elapsed: Duration, noise: &mut Noise) -> u16 {
+        let t = elapsed.as_secs_f64();
+        let value = match self {
+            Self::Constant(value) => return *value,
+            Self::Trace(trace) => trace.value_at(elapsed),
+            Self::Sine {
+                offset,
+                amplitude,
+                period,
+                noise: max,
+            } => {
+                let phase = t / period.as_secs_f64().max(f64::MIN_POSITIVE);
+                offset + amplitude * (TAU * phase).sin() + noise.next(*max)
+            }
+            Self::Ramp {
+                start,
+                slope,
+                noise: max,
+            } => start + slope * t + noise.next(*max),
+        };
+        // 範囲内に丸めているため、切り捨てや符号の喪失は起こらない
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let value = value.clamp(0.0, MAX_12BIT).round() as u16;
+        value
+    }
+}
+
+/// 再現性のある一様な雑音
+///
+/// 同じ種から始めれば、SILS を実行するたびに同じ系列を生成する。
+pub(crate) struct Noise(u64);
+
+impl Noise {
+    pub(crate) fn new(seed: u64) -> Self {
+        // xorshift は状態が 0 だと 0 しか生成しない
+        Self(seed | 1)
+    }
+
+    /// `-max` から `max` までの値
+    fn next(&mut self, max: f64) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        // 上位 53 bit を [0, 1) の値とする
+        #[allow(clippy::cast_precision_loss)]
+        let unit = (self.0 >> 11) as f64 / (1u64 << 53) as f64;
+        max * (2.0 * unit - 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    fn sample(source: &Source, elapsed: Duration) -> u16 {
+        source.sample(elapsed, &mut Noise::new(0))
+    }
+
+    #[test]
+    fn clamps_to_12bit_range() {
+        let low = Source::Ramp {
+            start: 100.0,
+            slope: -50.0,
+            noise: 0.0,
+        };
+        assert_eq!(sample(&low, secs(1.0)), 50);
+        assert_eq!(sample(&low, secs(10.0)), 0);
+        let high = Source::Sine {
+            offset: 2048.0,
+            amplitude: 10_000.0,
+            period: secs(4.0),
+            noise: 0.0,
+        };
+        assert_eq!(sample(&high, secs(1.0)), 4095);
+        assert_eq!(sample(&high, secs(3.0)), 0);
+        let trace = Source::Trace(Trace::new([(secs(0.0), -1.0), (secs(1.0), 5000.0)]));
+        assert_eq!(sample(&trace, secs(0.0)), 0);
+        assert_eq!(sample(&trace, secs(1.0)), 4095);
+    }
+
+    #[test]
+    fn noise_is_deterministic_per_seed() {
+        let sequence = |seed| {
+            let mut noise = Noise::new(seed);
+            (0..100).map(|_| noise.next(10.0)).collect::<Vec<_>>()
+        };
+        assert_eq!(sequence(1), sequence(1));
+        assert_ne!(sequence(1), sequence(2));
+        assert!(sequence(3).iter().all(|value| value.abs() <= 10.0));
+    }
+}
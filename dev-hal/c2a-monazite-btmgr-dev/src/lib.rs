@@ -1,43 +1,435 @@
-use std::{process, sync::Mutex};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+    sync::Mutex,
+};
 
-use c2a_monazite_btmgr_bind::{BootBank, Btmgr as BtmgrBind};
+use bootmeta::{
+    decode_reset_flag, deserialize_next_boot_bank, sanitize_max_boot_attempts, select_boot,
+    serialize_next_boot_bank, BootAction, BootBank as MetaBootBank, BootDecision, BootHistory,
+    BootRecord as MetaBootRecord, BootRegisters, BOOT_CONFIRMED_MARKER, BOOT_HISTORY_WORDS,
+};
+use c2a_monazite_btmgr_bind::{BootBank, BootRecord, Btmgr as BtmgrBind};
 
-pub struct Btmgr {
-    next_boot_bank: Mutex<Option<BootBank>>,
+pub use bootmeta::ResetReason;
+
+const BKPR_COUNT: usize = 32;
+const NEXT_BOOT_BANK_BKPR: usize = 0;
+const RESET_FLAG_BKPR: usize = 1;
+const BOOT_ATTEMPTS_BKPR: usize = 2;
+const MAX_BOOT_ATTEMPTS_BKPR: usize = 3;
+const BOOT_CONFIRMED_BKPR: usize = 4;
+
+fn to_meta_boot_bank(boot_bank: BootBank) -> MetaBootBank {
+    match boot_bank {
+        BootBank::Bank1 => MetaBootBank::Bank1,
+        BootBank::Bank2 => MetaBootBank::Bank2,
+    }
 }
 
-impl Btmgr {
-    #[allow(clippy::new_without_default)]
-    #[must_use]
-    pub fn new() -> Self {
+fn from_meta_boot_bank(meta_boot_bank: MetaBootBank) -> BootBank {
+    match meta_boot_bank {
+        MetaBootBank::Bank1 => BootBank::Bank1,
+        MetaBootBank::Bank2 => BootBank::Bank2,
+    }
+}
+
+fn from_reset_reason(reset_reason: ResetReason) -> i32 {
+    match reset_reason {
+        ResetReason::PowerOnReset => 0,
+        ResetReason::PinReset => 1,
+        ResetReason::BrownoutReset => 2,
+        ResetReason::SystemReset => 3,
+        ResetReason::CpuReset => 4,
+        ResetReason::WindowWatchdogReset => 5,
+        ResetReason::IndependentWatchdogReset => 6,
+        ResetReason::GenericWatchdogReset => 7,
+        ResetReason::D1ExitsDStandbyMode => 8,
+        ResetReason::D2ExitsDStandbyMode => 9,
+        ResetReason::D1EntersDStandbyErroneouslyOrCpuEntersCStopErroneously => 10,
+    }
+}
+
+fn from_meta_boot_record(record: MetaBootRecord) -> BootRecord {
+    BootRecord {
+        sequence: record.sequence,
+        reset_flag: record.reset_flag,
+        reset_reason: decode_reset_flag(record.reset_flag).map_or(-1, from_reset_reason),
+        boot_bank: from_meta_boot_bank(record.bank) as u32,
+        action: match record.action {
+            BootAction::Boot => 0,
+            BootAction::Switch => 1,
+            BootAction::Rollback => 2,
+        },
+        boot_attempts: u32::from(record.boot_attempts),
+    }
+}
+
+/// プロセスをまたいで保持される状態
+///
+/// 実機の `SWAP_BANK` オプションバイト・Backup Register・Backup SRAM に相当する。
+struct State {
+    swap_bank: bool,
+    // 前回のプロセスが要求したリセットのリセットフラグ（0 なら要求なし）
+    pending_reset_flag: u32,
+    bkpr: [u32; BKPR_COUNT],
+    history: BootHistory<Vec<u32>>,
+}
+
+impl State {
+    const SIZE: usize = 4 * (2 + BKPR_COUNT + BOOT_HISTORY_WORDS);
+
+    /// パワーオンリセット直後の状態
+    fn power_on() -> Self {
         Self {
-            next_boot_bank: Mutex::new(None),
+            swap_bank: false,
+            pending_reset_flag: 0,
+            bkpr: [0; BKPR_COUNT],
+            history: BootHistory::new(vec![0; BOOT_HISTORY_WORDS]),
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != Self::SIZE {
+            return None;
         }
+        let mut words = bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()));
+        let swap_bank = words.next()? != 0;
+        let pending_reset_flag = words.next()?;
+        let mut bkpr = [0; BKPR_COUNT];
+        for (reg, word) in bkpr.iter_mut().zip(words.by_ref()) {
+            *reg = word;
+        }
+        let history = BootHistory::new(words.collect());
+        Some(Self {
+            swap_bank,
+            pending_reset_flag,
+            bkpr,
+            history,
+        })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        [u32::from(self.swap_bank), self.pending_reset_flag]
+            .iter()
+            .chain(self.bkpr.iter())
+            .chain(self.history.words().iter())
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    }
+
+    /// ブートローダの動作を再現する
+    ///
+    /// バンクを切り替える場合はシステムリセットを挟んで再度ブートローダが実行される。
+    fn boot(&mut self, mut reset_flag: u32) {
+        loop {
+            self.bkpr[RESET_FLAG_BKPR] = reset_flag;
+            let registers = BootRegisters {
+                next_boot_bank: self.bkpr[NEXT_BOOT_BANK_BKPR],
+                boot_attempts: self.bkpr[BOOT_ATTEMPTS_BKPR],
+                max_boot_attempts: self.bkpr[MAX_BOOT_ATTEMPTS_BKPR],
+                boot_confirmed: self.bkpr[BOOT_CONFIRMED_BKPR],
+            };
+            match select_boot(reset_flag, &registers, self.swap_bank) {
+                BootDecision::Switch { bank, rollback } => {
+                    let action = if rollback {
+                        BootAction::Rollback
+                    } else {
+                        BootAction::Switch
+                    };
+                    self.history.push(MetaBootRecord::new(
+                        reset_flag,
+                        bank,
+                        action,
+                        registers.boot_attempts,
+                    ));
+                    self.bkpr[BOOT_ATTEMPTS_BKPR] = 0;
+                    self.swap_bank = bank.to_swap_bank();
+                    reset_flag = ResetReason::SystemReset.reset_flag();
+                }
+                BootDecision::Boot {
+                    bank,
+                    next_boot_bank,
+                    boot_attempts,
+                } => {
+                    self.history.push(MetaBootRecord::new(
+                        reset_flag,
+                        bank,
+                        BootAction::Boot,
+                        boot_attempts,
+                    ));
+                    self.bkpr[NEXT_BOOT_BANK_BKPR] = serialize_next_boot_bank(next_boot_bank);
+                    self.bkpr[BOOT_ATTEMPTS_BKPR] = boot_attempts;
+                    self.bkpr[BOOT_CONFIRMED_BKPR] = 0;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// ブートローダによるバンク選択と Backup Register をエミュレートする
+///
+/// 状態はファイルに永続化され、プロセスの起動がリセットに対応する。
+/// ファイルが存在しない場合はパワーオンリセット、[`Btmgr::reset`] によって終了した場合はそのリセット、
+/// それ以外の理由でプロセスが終了した場合はピンリセットとして扱う。
+pub struct Btmgr {
+    path: PathBuf,
+    state: Mutex<State>,
+}
+
+impl Btmgr {
+    /// `path` のファイルから状態を読み出し、ブートローダを実行した後の状態で `Btmgr` を構築する
+    ///
+    /// # Errors
+    /// 状態ファイルの読み書きに失敗した場合は [`io::Error`] を返す。
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let saved = match fs::read(&path) {
+            Ok(bytes) => State::decode(&bytes),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+        let (mut state, reset_flag) = match saved {
+            Some(state) if state.pending_reset_flag != 0 => {
+                let reset_flag = state.pending_reset_flag;
+                (state, reset_flag)
+            }
+            Some(state) => (state, ResetReason::PinReset.reset_flag()),
+            None => (State::power_on(), ResetReason::PowerOnReset.reset_flag()),
+        };
+        state.pending_reset_flag = 0;
+        state.boot(reset_flag);
+        fs::write(&path, state.encode())?;
+        Ok(Self {
+            path,
+            state: Mutex::new(state),
+        })
+    }
+
+    fn save(&self, state: &State) {
+        fs::write(&self.path, state.encode()).expect("failed to write btmgr state");
+    }
+
+    /// `reason` によるリセットを要求してプロセスを終了する
+    ///
+    /// 次にプロセスが起動したときに、このリセット要因でブートローダが実行される。
+    ///
+    /// # Panics
+    /// 状態ファイルの書き込みに失敗した場合は panic する。
+    pub fn reset(&self, reason: ResetReason) -> ! {
+        self.request_reset(reason);
+        process::exit(0)
+    }
+
+    /// 次にプロセスが起動したときのリセット要因を `reason` にする
+    fn request_reset(&self, reason: ResetReason) {
+        let mut state = self.state.lock().unwrap();
+        state.pending_reset_flag = reason.reset_flag();
+        self.save(&state);
     }
 }
 
 impl BtmgrBind for Btmgr {
     fn get_current_boot_bank(&self) -> BootBank {
-        BootBank::Bank1
+        let state = self.state.lock().unwrap();
+        from_meta_boot_bank(MetaBootBank::from_swap_bank(state.swap_bank))
     }
 
     fn get_next_boot_bank(&self) -> Option<BootBank> {
-        *self.next_boot_bank.lock().unwrap()
+        let state = self.state.lock().unwrap();
+        // 無効な値は None に潰す
+        deserialize_next_boot_bank(state.bkpr[NEXT_BOOT_BANK_BKPR])
+            .unwrap_or(None)
+            .map(from_meta_boot_bank)
     }
 
     fn set_next_boot_bank(&self, next_boot_bank: Option<BootBank>) {
-        *self.next_boot_bank.lock().unwrap() = next_boot_bank;
+        let mut state = self.state.lock().unwrap();
+        state.bkpr[NEXT_BOOT_BANK_BKPR] =
+            serialize_next_boot_bank(next_boot_bank.map(to_meta_boot_bank));
+        self.save(&state);
     }
 
     fn get_reset_flag(&self) -> u32 {
-        0
+        self.state.lock().unwrap().bkpr[RESET_FLAG_BKPR]
     }
 
     fn get_reset_reason(&self) -> i32 {
-        -1
+        match decode_reset_flag(self.get_reset_flag()) {
+            Ok(reason) => from_reset_reason(reason),
+            Err(_) => -1,
+        }
     }
 
     fn system_reset(&self) -> ! {
-        process::exit(0)
+        self.reset(ResetReason::SystemReset)
+    }
+
+    fn get_boot_attempts(&self) -> u32 {
+        self.state.lock().unwrap().bkpr[BOOT_ATTEMPTS_BKPR]
+    }
+
+    fn get_max_boot_attempts(&self) -> u32 {
+        sanitize_max_boot_attempts(self.state.lock().unwrap().bkpr[MAX_BOOT_ATTEMPTS_BKPR])
+    }
+
+    fn set_max_boot_attempts(&self, max_boot_attempts: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.bkpr[MAX_BOOT_ATTEMPTS_BKPR] = max_boot_attempts;
+        self.save(&state);
+    }
+
+    fn confirm_boot(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.bkpr[NEXT_BOOT_BANK_BKPR] = serialize_next_boot_bank(None);
+        state.bkpr[BOOT_CONFIRMED_BKPR] = BOOT_CONFIRMED_MARKER;
+        self.save(&state);
+    }
+
+    fn get_boot_history_len(&self) -> usize {
+        self.state.lock().unwrap().history.len()
+    }
+
+    fn get_boot_history(&self, index: usize) -> Option<BootRecord> {
+        let record = self.state.lock().unwrap().history.get(index);
+        record.map(from_meta_boot_record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// テストごとの状態ファイルのパス。パワーオンリセットから始めるため、既存のファイルは削除する
+    fn state_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "c2a-monazite-btmgr-dev-{name}-{}.bin",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    /// `reason` でリセットしてプロセスを起動し直す
+    fn restart(btmgr: &Btmgr, reason: ResetReason) -> Btmgr {
+        btmgr.request_reset(reason);
+        Btmgr::new(&btmgr.path).unwrap()
+    }
+
+    fn is_bank(bank: Option<BootBank>, expected: Option<BootBank>) -> bool {
+        bank.map(|bank| bank as i32) == expected.map(|bank| bank as i32)
+    }
+
+    fn history_action(btmgr: &Btmgr, index: usize) -> u32 {
+        btmgr.get_boot_history(index).unwrap().action
+    }
+
+    #[test]
+    fn state_round_trips_through_bytes() {
+        let mut state = State::power_on();
+        state.swap_bank = true;
+        state.pending_reset_flag = ResetReason::SystemReset.reset_flag();
+        state.bkpr[BOOT_ATTEMPTS_BKPR] = 3;
+        state.bkpr[BKPR_COUNT - 1] = 0xDEAD_BEEF;
+        state.history.push(MetaBootRecord::new(
+            ResetReason::PinReset.reset_flag(),
+            MetaBootBank::Bank2,
+            BootAction::Rollback,
+            2,
+        ));
+        let bytes = state.encode();
+        assert_eq!(bytes.len(), State::SIZE);
+
+        let decoded = State::decode(&bytes).unwrap();
+        assert!(decoded.swap_bank);
+        assert_eq!(decoded.pending_reset_flag, state.pending_reset_flag);
+        assert_eq!(decoded.bkpr, state.bkpr);
+        assert_eq!(decoded.history.get(0), state.history.get(0));
+        assert_eq!(decoded.encode(), bytes);
+
+        assert!(State::decode(&bytes[1..]).is_none());
+    }
+
+    #[test]
+    fn missing_state_file_is_power_on_reset() {
+        let path = state_path("power-on");
+        let btmgr = Btmgr::new(&path).unwrap();
+        assert_eq!(btmgr.get_reset_reason(), 0);
+        assert!(btmgr.get_current_boot_bank() == BootBank::Bank1);
+        assert_eq!(btmgr.get_boot_attempts(), 1);
+        assert_eq!(btmgr.get_boot_history_len(), 1);
+        // 起動回数の上限の既定値は 1 のため、起動確認がなければ次の起動でロールバックする
+        assert!(is_bank(btmgr.get_next_boot_bank(), Some(BootBank::Bank2)));
+
+        // 要求していない再起動はピンリセットとして扱う
+        btmgr.confirm_boot();
+        drop(btmgr);
+        let btmgr = Btmgr::new(&path).unwrap();
+        assert_eq!(btmgr.get_reset_reason(), 1);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rolls_back_after_max_boot_attempts() {
+        let path = state_path("rollback");
+        let mut btmgr = Btmgr::new(&path).unwrap();
+        btmgr.set_max_boot_attempts(3);
+        btmgr.set_next_boot_bank(None);
+        for attempts in 2..=3 {
+            btmgr = restart(&btmgr, ResetReason::IndependentWatchdogReset);
+            assert_eq!(btmgr.get_reset_reason(), 6);
+            assert!(btmgr.get_current_boot_bank() == BootBank::Bank1);
+            assert_eq!(btmgr.get_boot_attempts(), attempts);
+        }
+        // 上限に達したため、次に起動確認のないままリセットされると裏のバンクに切り替わる
+        assert!(is_bank(btmgr.get_next_boot_bank(), Some(BootBank::Bank2)));
+
+        btmgr = restart(&btmgr, ResetReason::IndependentWatchdogReset);
+        assert!(btmgr.get_current_boot_bank() == BootBank::Bank2);
+        assert_eq!(btmgr.get_boot_attempts(), 1);
+        // ロールバックした後、システムリセットで起動し直している
+        assert_eq!(btmgr.get_reset_reason(), 3);
+        assert_eq!(history_action(&btmgr, 0), 0);
+        assert_eq!(history_action(&btmgr, 1), 2);
+        let rollback = btmgr.get_boot_history(1).unwrap();
+        assert_eq!(rollback.boot_attempts, 3);
+        assert_eq!(rollback.boot_bank, BootBank::Bank2 as u32);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn requested_switch_is_not_rollback() {
+        let path = state_path("switch");
+        let mut btmgr = Btmgr::new(&path).unwrap();
+        btmgr.confirm_boot();
+        btmgr.set_next_boot_bank(Some(BootBank::Bank2));
+        btmgr = restart(&btmgr, ResetReason::SystemReset);
+        assert!(btmgr.get_current_boot_bank() == BootBank::Bank2);
+        assert_eq!(history_action(&btmgr, 1), 1);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn confirm_boot_clears_next_boot_bank() {
+        let path = state_path("confirm");
+        let mut btmgr = Btmgr::new(&path).unwrap();
+        btmgr.set_max_boot_attempts(2);
+        btmgr.set_next_boot_bank(None);
+        btmgr = restart(&btmgr, ResetReason::SystemReset);
+        assert_eq!(btmgr.get_boot_attempts(), 2);
+        assert!(is_bank(btmgr.get_next_boot_bank(), Some(BootBank::Bank2)));
+
+        btmgr.confirm_boot();
+        assert!(is_bank(btmgr.get_next_boot_bank(), None));
+        // 起動確認は状態ファイルに残り、次の起動では起動回数を数え直す
+        btmgr = restart(&btmgr, ResetReason::SystemReset);
+        assert!(btmgr.get_current_boot_bank() == BootBank::Bank1);
+        assert_eq!(btmgr.get_boot_attempts(), 1);
+        assert!(is_bank(btmgr.get_next_boot_bank(), None));
+        fs::remove_file(path).unwrap();
     }
 }
@@ -1,51 +1,140 @@
-use std::thread;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::Duration,
+};
 
 use anyhow::{anyhow, Result};
 use futures::{future, SinkExt, TryStreamExt};
 use tokio::{
-    net::{TcpListener, ToSocketAddrs},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
     sync::mpsc,
+    time,
 };
 
-pub fn new() -> (mpsc::Sender<Vec<u8>>, mpsc::Receiver<Vec<u8>>, Socket) {
-    let (tlm_tx, tlm_rx) = mpsc::channel(5);
-    let (cmd_tx, cmd_rx) = mpsc::channel(5);
-    let socket = Socket { tlm_rx, cmd_tx };
+/// クライアントがこの時間内にフレームを受け取らない場合は、止まっているものとして切断する
+const SEND_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 接続している地上局のクライアントの数
+#[derive(Default)]
+pub struct Connection {
+    clients: Mutex<usize>,
+    changed: Condvar,
+}
+
+impl Connection {
+    fn connect(&self) {
+        *self.clients.lock().unwrap() += 1;
+        self.changed.notify_all();
+    }
+
+    fn disconnect(&self) {
+        *self.clients.lock().unwrap() -= 1;
+        self.changed.notify_all();
+    }
+
+    /// いずれかのクライアントが接続されるまで待つ
+    pub fn wait(&self) {
+        let clients = self.clients.lock().unwrap();
+        let _clients = self
+            .changed
+            .wait_while(clients, |clients| *clients == 0)
+            .unwrap();
+    }
+}
+
+/// 全クライアントへのテレメトリの配信と、再接続したクライアントに送り直す直近のフレーム
+struct Hub {
+    clients: Vec<mpsc::Sender<Vec<u8>>>,
+    replay: VecDeque<Vec<u8>>,
+    replay_depth: usize,
+}
+
+impl Hub {
+    /// `tlm_bytes` を送り直すフレームに加え、配信先のクライアントを返す
+    fn publish(&mut self, tlm_bytes: &[u8]) -> Vec<mpsc::Sender<Vec<u8>>> {
+        if self.replay_depth > 0 {
+            if self.replay.len() == self.replay_depth {
+                self.replay.pop_front();
+            }
+            self.replay.push_back(tlm_bytes.to_vec());
+        }
+        // 切断したクライアントはここで取り除く
+        self.clients.retain(|client| !client.is_closed());
+        self.clients.clone()
+    }
+
+    /// 送り直すフレームと、それ以降のフレームを受け取る受信側を返す
+    fn subscribe(&mut self) -> (Vec<Vec<u8>>, mpsc::Receiver<Vec<u8>>) {
+        // 遅いクライアントの分を溜め込まず、C2A の送信キューに残すため 1 フレームだけ保持する
+        let (tlm_tx, tlm_rx) = mpsc::channel(1);
+        self.clients.push(tlm_tx);
+        (self.replay.iter().cloned().collect(), tlm_rx)
+    }
+
+    /// `stalled` を配信先から外す。受信側は受け取り済みのフレームを取り出した後に閉じる
+    fn remove(&mut self, stalled: &[&mpsc::Sender<Vec<u8>>]) {
+        self.clients
+            .retain(|client| !stalled.iter().any(|s| s.same_channel(client)));
+    }
+}
+
+/// `replay_depth` が 0 の場合、再接続したクライアントには接続後のフレームのみを送る
+pub fn new(
+    tlm_depth: usize,
+    cmd_depth: usize,
+    replay_depth: usize,
+) -> (mpsc::Sender<Vec<u8>>, mpsc::Receiver<Vec<u8>>, Socket) {
+    let (tlm_tx, tlm_rx) = mpsc::channel(tlm_depth);
+    let (cmd_tx, cmd_rx) = mpsc::channel(cmd_depth);
+    let hub = Hub {
+        clients: Vec::new(),
+        replay: VecDeque::with_capacity(replay_depth),
+        replay_depth,
+    };
+    let socket = Socket {
+        tlm_rx,
+        cmd_tx,
+        hub: Arc::new(Mutex::new(hub)),
+        connection: Arc::default(),
+    };
     (tlm_tx, cmd_rx, socket)
 }
 
 pub struct Socket {
     tlm_rx: mpsc::Receiver<Vec<u8>>,
     cmd_tx: mpsc::Sender<Vec<u8>>,
+    hub: Arc<Mutex<Hub>>,
+    connection: Arc<Connection>,
 }
 
 impl Socket {
-    pub async fn serve(mut self, addr: impl ToSocketAddrs) -> Result<()> {
+    pub fn connection(&self) -> Arc<Connection> {
+        self.connection.clone()
+    }
+
+    pub async fn serve(self, addr: impl ToSocketAddrs) -> Result<()> {
         let listener = TcpListener::bind(addr).await?;
+        let Self {
+            tlm_rx,
+            cmd_tx,
+            hub,
+            connection,
+        } = self;
+        tokio::spawn(distribute(tlm_rx, hub.clone()));
         loop {
-            let (incoming, _addr) = listener.accept().await?;
-            let wss = tokio_tungstenite::accept_async(incoming).await?;
-            let (mut sink, mut stream) = kble_socket::from_tungstenite(wss);
-            let uplink = async {
-                loop {
-                    let tlm_bytes = self
-                        .tlm_rx
-                        .recv()
-                        .await
-                        .ok_or_else(|| anyhow!("telemetry producer has gone"))?;
-                    sink.send(tlm_bytes.into()).await?;
-                }
+            let (incoming, addr) = listener.accept().await?;
+            let client = Client {
+                cmd_tx: cmd_tx.clone(),
+                hub: hub.clone(),
+                connection: connection.clone(),
             };
-            let downlink = async {
-                loop {
-                    let Some(cmd_bytes) = stream.try_next().await? else {
-                        break;
-                    };
-                    self.cmd_tx.send(cmd_bytes.into()).await?;
+            tokio::spawn(async move {
+                if let Err(e) = client.serve(incoming).await {
+                    eprintln!("ground client {addr} has disconnected: {e}");
                 }
-                anyhow::Ok(())
-            };
-            let _: Option<((), ())> = future::try_join(uplink, downlink).await.ok();
+            });
         }
     }
 
@@ -62,3 +151,126 @@ impl Socket {
         });
     }
 }
+
+/// C2A の送信したテレメトリを全クライアントに配信する
+///
+/// 最も遅いクライアントが受け取るまで次のフレームを取り出さないことで、送信キューの空きに反映する。
+/// [`SEND_TIMEOUT`] 以内に受け取らないクライアントは配信先から外し、他のクライアントへの配信を止めない。
+/// クライアントが接続していない場合は、送信キューが詰まらないようにそのまま取り出す。
+async fn distribute(mut tlm_rx: mpsc::Receiver<Vec<u8>>, hub: Arc<Mutex<Hub>>) {
+    while let Some(tlm_bytes) = tlm_rx.recv().await {
+        let clients = hub.lock().unwrap().publish(&tlm_bytes);
+        // 途中で切断したクライアントへの送信は失敗するが、次の配信で取り除かれる
+        let sent = future::join_all(
+            clients
+                .iter()
+                .map(|client| time::timeout(SEND_TIMEOUT, client.send(tlm_bytes.clone()))),
+        )
+        .await;
+        let stalled: Vec<_> = clients
+            .iter()
+            .zip(sent)
+            .filter_map(|(client, sent)| sent.is_err().then_some(client))
+            .collect();
+        if !stalled.is_empty() {
+            hub.lock().unwrap().remove(&stalled);
+        }
+    }
+}
+
+/// 接続した地上局のクライアント 1 つ分
+struct Client {
+    cmd_tx: mpsc::Sender<Vec<u8>>,
+    hub: Arc<Mutex<Hub>>,
+    connection: Arc<Connection>,
+}
+
+impl Client {
+    async fn serve(self, incoming: TcpStream) -> Result<()> {
+        let wss = tokio_tungstenite::accept_async(incoming).await?;
+        let (mut sink, mut stream) = kble_socket::from_tungstenite(wss);
+        // 送り直すフレームと以降のフレームの間に抜けや重複がないよう、まとめて取り出す
+        let (replay, mut tlm_rx) = self.hub.lock().unwrap().subscribe();
+        self.connection.connect();
+        let uplink = async {
+            // 送信が詰まったままのクライアントも切断する
+            for tlm_bytes in replay {
+                time::timeout(SEND_TIMEOUT, sink.send(tlm_bytes.into())).await??;
+            }
+            while let Some(tlm_bytes) = tlm_rx.recv().await {
+                time::timeout(SEND_TIMEOUT, sink.send(tlm_bytes.into())).await??;
+            }
+            // 配信先から外された
+            Err::<(), _>(anyhow!("client did not keep up with telemetry"))
+        };
+        let downlink = async {
+            loop {
+                let Some(cmd_bytes) = stream.try_next().await? else {
+                    break;
+                };
+                self.cmd_tx.send(cmd_bytes.into()).await?;
+            }
+            anyhow::Ok(())
+        };
+        // クライアントが切断した場合も uplink を止めるため、先に終わった方の結果を返す
+        futures::pin_mut!(uplink, downlink);
+        let (result, _) = future::select(uplink, downlink).await.factor_first();
+        self.connection.disconnect();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .start_paused(true)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn slow_client_keeps_frames_in_tlm_queue() {
+        let rt = runtime();
+        rt.block_on(async {
+            let (tlm_tx, _cmd_rx, socket) = new(4, 1, 0);
+            let (_, mut client) = socket.hub.lock().unwrap().subscribe();
+            tokio::spawn(distribute(socket.tlm_rx, socket.hub.clone()));
+            for i in 0..4 {
+                tlm_tx.try_send(vec![i]).unwrap();
+            }
+            tokio::task::yield_now().await;
+            // クライアントと配信中の 1 フレームずつを除き、送信キューに残る
+            assert_eq!(tlm_tx.capacity(), 2);
+            assert_eq!(client.recv().await, Some(vec![0]));
+            tokio::task::yield_now().await;
+            assert_eq!(tlm_tx.capacity(), 3);
+
+            // 切断したクライアントは待たない
+            drop(client);
+            tokio::task::yield_now().await;
+            assert_eq!(tlm_tx.capacity(), 4);
+        });
+    }
+
+    #[test]
+    fn stalled_client_does_not_block_others() {
+        runtime().block_on(async {
+            let (tlm_tx, _cmd_rx, socket) = new(4, 1, 0);
+            let (_, mut stalled) = socket.hub.lock().unwrap().subscribe();
+            let (_, mut healthy) = socket.hub.lock().unwrap().subscribe();
+            tokio::spawn(distribute(socket.tlm_rx, socket.hub.clone()));
+            for i in 0..8 {
+                tlm_tx.send(vec![i]).await.unwrap();
+                assert_eq!(healthy.recv().await, Some(vec![i]));
+            }
+            assert_eq!(socket.hub.lock().unwrap().clients.len(), 1);
+            // 止まったクライアントは受け取れた分を取り出すと閉じる
+            assert_eq!(stalled.recv().await, Some(vec![0]));
+            assert_eq!(stalled.recv().await, None);
+        });
+    }
+}
@@ -1,22 +1,116 @@
 mod kble;
 
-use std::{net::SocketAddr, sync::Mutex};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
 
-use c2a_monazite_ccsds_bind::{Ccsds as CcsdsBind, Error, RxStats};
+use c2a_monazite_ccsds_bind::{Ccsds as CcsdsBind, Error, RxStats, FIFO_SIZE};
+use ccsds_frame::{
+    parse_tc_frame, AosError, AosFrameBuilder, RxStats as FrameRxStats, AOS_MAX_FRAME_LEN,
+};
 use tokio::sync::mpsc::{self, error::TryRecvError};
+use traffic_capture::{Capture, Direction, Replay, Source};
+
+/// 実機と同じ Transfer Frame の処理を行うための状態
+struct Framing {
+    builder: AosFrameBuilder,
+    stats: FrameRxStats,
+}
+
+/// 送受信キューの設定
+#[derive(Clone)]
+pub struct Config {
+    /// 地上局に送信されていないテレメトリのフレームを保持できる数
+    pub tlm_queue_depth: usize,
+    /// C2A に読み出されていないコマンドのフレームを保持できる数
+    pub cmd_queue_depth: usize,
+    /// 地上局が接続されていない場合、フレームを送信キューに積まずに接続されるまで待つ
+    pub block_until_connected: bool,
+    /// 接続したクライアントに最初に送り直す直近のテレメトリのフレームの数（0 の場合は送り直さない）
+    pub replay_depth: usize,
+    /// 送受信したフレームを記録する
+    pub capture: Option<Capture>,
+    /// 地上局から受信したフレームに加えて、記録したコマンドのフレームを受信する
+    pub replay: Option<Replay>,
+}
+
+impl Default for Config {
+    /// 送信キューの深さは C2A の想定する送信バッファのフレーム数に合わせる
+    fn default() -> Self {
+        Self {
+            tlm_queue_depth: FIFO_SIZE,
+            cmd_queue_depth: 5,
+            block_until_connected: false,
+            replay_depth: 0,
+            capture: None,
+            replay: None,
+        }
+    }
+}
 
 pub struct Ccsds {
     tlm_tx: mpsc::Sender<Vec<u8>>,
     cmd_rx: Mutex<mpsc::Receiver<Vec<u8>>>,
+    connection: Arc<kble::Connection>,
+    block_until_connected: bool,
+    capture: Option<Capture>,
+    replay: Option<Replay>,
+    framing: Mutex<Framing>,
 }
 
 impl Ccsds {
+    /// `addr` で地上局からの kble の接続を待ち受ける `Ccsds` を構築する
+    ///
+    /// # Panics
+    /// `config` のキューの深さが 0 の場合は panic する。
     #[must_use]
-    pub fn new(addr: SocketAddr) -> Self {
-        let (tlm_tx, cmd_rx, socket) = kble::new();
+    pub fn new(addr: SocketAddr, config: Config) -> Self {
+        assert!(config.tlm_queue_depth > 0 && config.cmd_queue_depth > 0);
+        let (tlm_tx, cmd_rx, socket) = kble::new(
+            config.tlm_queue_depth,
+            config.cmd_queue_depth,
+            config.replay_depth,
+        );
+        let connection = socket.connection();
         let cmd_rx = Mutex::new(cmd_rx);
         socket.serve_in_background(addr);
-        Self { tlm_tx, cmd_rx }
+        let framing = Mutex::new(Framing {
+            builder: AosFrameBuilder::new(0, false),
+            stats: FrameRxStats::default(),
+        });
+        Self {
+            tlm_tx,
+            cmd_rx,
+            connection,
+            block_until_connected: config.block_until_connected,
+            capture: config.capture,
+            replay: config.replay,
+            framing,
+        }
+    }
+
+    fn record(&self, direction: Direction, frame: &[u8]) {
+        if let Some(capture) = &self.capture {
+            capture.record(Source::Ccsds, 0, direction, frame);
+        }
+    }
+}
+
+fn from_aos_error(err: AosError) -> Error {
+    match err {
+        AosError::TooShort | AosError::TooLong(_) => Error::TxSizeError,
+        AosError::InvalidVersion(_) => Error::TxInvalid,
+    }
+}
+
+fn from_frame_rx_stats(stats: FrameRxStats) -> RxStats {
+    RxStats {
+        corrupted_frames: stats.corrupted_frames,
+        overflowed_frames: stats.overflowed_frames,
+        found_starts: stats.found_starts,
+        skipped_frames: stats.skipped_frames,
+        last_frame_corrected_errors: stats.last_frame_corrected_errors,
     }
 }
 
@@ -30,41 +124,66 @@ impl CcsdsBind for Ccsds {
     }
 
     fn send(&self, data: &[u8]) -> Result<(), Error> {
-        self.tlm_tx
-            .try_send(data.to_vec())
-            .map_err(|_| Error::TxNoBuffer)
+        let mut frame = vec![0; AOS_MAX_FRAME_LEN];
+        let len = self
+            .framing
+            .lock()
+            .unwrap()
+            .builder
+            .rebuild(data, &mut frame)
+            .map_err(from_aos_error)?;
+        frame.truncate(len);
+        if self.block_until_connected {
+            self.connection.wait();
+        }
+        // 送信キューに積めなかったフレームは送信していないため記録しない
+        let permit = self.tlm_tx.try_reserve().map_err(|_| Error::TxNoBuffer)?;
+        self.record(Direction::Tx, &frame);
+        permit.send(frame);
+        Ok(())
     }
 
     fn receive(&self, buffer: &mut [u8]) -> Result<usize, Error> {
-        let cmd_bytes = match self.cmd_rx.lock().unwrap().try_recv() {
-            Ok(cmd_bytes) => cmd_bytes,
-            Err(TryRecvError::Empty) => return Ok(0),
-            _ => return Err(Error::Rx4Kbps),
-        };
-        if cmd_bytes.len() > buffer.len() {
-            // buffer is too short
-            return Err(Error::Rx4Kbps);
+        let mut cmd_rx = self.cmd_rx.lock().unwrap();
+        loop {
+            // 再生するフレームは、地上局から受信したフレームより先に受信する
+            let replayed = self
+                .replay
+                .as_ref()
+                .and_then(|replay| replay.pop(Source::Ccsds, 0));
+            let cmd_bytes = match replayed.map_or_else(|| cmd_rx.try_recv(), Ok) {
+                Ok(cmd_bytes) => cmd_bytes,
+                Err(TryRecvError::Empty) => return Ok(0),
+                _ => return Err(Error::Rx4Kbps),
+            };
+            // 1 つのメッセージを 1 つのフレームとして扱い、不正なフレームは実機と同様に読み捨てる
+            let mut framing = self.framing.lock().unwrap();
+            framing.stats.count_start();
+            let Ok(header) = parse_tc_frame(&cmd_bytes, true) else {
+                framing.stats.count_corrupted();
+                continue;
+            };
+            let len = header.frame_len;
+            if len > buffer.len() {
+                framing.stats.count_skipped();
+                continue;
+            }
+            framing.stats.count_received(0);
+            buffer[..len].copy_from_slice(&cmd_bytes[..len]);
+            self.record(Direction::Rx, &buffer[..len]);
+            return Ok(len);
         }
-        let len = cmd_bytes.len();
-        buffer[..len].copy_from_slice(&cmd_bytes[..]);
-        Ok(len)
     }
 
     fn tx_buffer_free_frames(&self) -> usize {
-        8
+        self.tlm_tx.capacity().min(FIFO_SIZE)
     }
 
     fn rx_stats(&self) -> RxStats {
-        RxStats {
-            corrupted_frames: 0,
-            overflowed_frames: 0,
-            found_starts: 0,
-            skipped_frames: 0,
-            last_frame_corrected_errors: 0,
-        }
+        from_frame_rx_stats(self.framing.lock().unwrap().stats)
     }
 
-    fn set_aos_scid(&self, _aos_scid: u8) {
-        // no-op
+    fn set_aos_scid(&self, aos_scid: u8) {
+        self.framing.lock().unwrap().builder.set_scid(aos_scid);
     }
 }
@@ -1,24 +1,84 @@
-use std::sync::Mutex;
+mod waveform;
+
+use std::{
+    sync::{mpsc, Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use c2a_monazite_gpio_bind::{Error, Gpio as GpioBind, Value};
 
+pub use waveform::Waveform;
+
+/// 入力の波形や出力の変化のタイムスタンプの基準とする時刻
+pub type Clock = Arc<dyn Fn() -> Duration + Send + Sync>;
+
+/// 出力ポートの値の変化
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct OutputEvent {
+    /// [`Clock`] で数えた変化した時刻
+    pub timestamp: Duration,
+    pub port: u8,
+    pub value: Value,
+}
+
+struct Input {
+    level: Value,
+    // 波形と、その開始時刻
+    waveform: Option<(Duration, Waveform)>,
+}
+
+impl Input {
+    fn value(&self, now: Duration) -> Value {
+        self.waveform
+            .as_ref()
+            .and_then(|(start, waveform)| waveform.value_at(now.saturating_sub(*start)))
+            .unwrap_or(self.level)
+    }
+}
+
 struct State {
     output: Vec<Value>,
-    num_inputs: usize,
+    input: Vec<Input>,
+    clock: Clock,
+    subscribers: Vec<mpsc::Sender<OutputEvent>>,
+}
+
+impl State {
+    fn input_mut(&mut self, port: u8) -> Result<&mut Input, Error> {
+        self.input.get_mut(port as usize).ok_or(Error::Port)
+    }
 }
 
 pub struct Gpio {
-    state: Mutex<State>,
+    state: Arc<Mutex<State>>,
 }
 
 impl Gpio {
+    /// 入力ポートは全て `High` とし、時刻は構築した時点からの実時間で数える
     #[must_use]
     pub fn new(num_outputs: usize, num_inputs: usize) -> Self {
+        let start = Instant::now();
+        let input = (0..num_inputs)
+            .map(|_| Input {
+                level: Value::High,
+                waveform: None,
+            })
+            .collect();
         Self {
-            state: Mutex::new(State {
+            state: Arc::new(Mutex::new(State {
                 output: vec![Value::Low; num_outputs],
-                num_inputs,
-            }),
+                input,
+                clock: Arc::new(move || start.elapsed()),
+                subscribers: Vec::new(),
+            })),
+        }
+    }
+
+    /// C2A から独立して入力を与え、出力を観測するためのハンドルを返す
+    #[must_use]
+    pub fn handle(&self) -> Handle {
+        Handle {
+            state: self.state.clone(),
         }
     }
 }
@@ -29,6 +89,74 @@ impl Default for Gpio {
     }
 }
 
+/// [`Gpio`] の入力を与え、出力を観測する
+///
+/// 複製したものは同じ [`Gpio`] を操作する。
+#[derive(Clone)]
+pub struct Handle {
+    state: Arc<Mutex<State>>,
+}
+
+impl Handle {
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// 時刻を数える方法を `clock` に変更する
+    ///
+    /// 設定済みの波形の開始時刻は変わらないため、波形を設定する前に変更する。
+    pub fn set_clock(&self, clock: Clock) {
+        self.lock().clock = clock;
+    }
+
+    /// 入力ポート `port` の値を `value` に固定する
+    ///
+    /// # Errors
+    /// 入力ポートが存在しない場合は [`Error::Port`] を返す。
+    pub fn set_input(&self, port: u8, value: Value) -> Result<(), Error> {
+        let mut state = self.lock();
+        let input = state.input_mut(port)?;
+        input.level = value;
+        input.waveform = None;
+        Ok(())
+    }
+
+    /// 入力ポート `port` に現在の時刻から始まる `waveform` を与える
+    ///
+    /// 波形の最初の変化までは現在の値を保つ。
+    ///
+    /// # Errors
+    /// 入力ポートが存在しない場合は [`Error::Port`] を返す。
+    pub fn set_waveform(&self, port: u8, waveform: Waveform) -> Result<(), Error> {
+        let mut state = self.lock();
+        let now = (state.clock)();
+        let input = state.input_mut(port)?;
+        input.level = input.value(now);
+        input.waveform = Some((now, waveform));
+        Ok(())
+    }
+
+    /// 出力ポートの値が変化するたびに [`OutputEvent`] を受け取る受信側を返す
+    #[must_use]
+    pub fn subscribe_outputs(&self) -> mpsc::Receiver<OutputEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.lock().subscribers.push(tx);
+        rx
+    }
+
+    /// 出力ポート `port` の現在の値
+    ///
+    /// # Errors
+    /// 出力ポートが存在しない場合は [`Error::Port`] を返す。
+    pub fn output(&self, port: u8) -> Result<Value, Error> {
+        self.lock()
+            .output
+            .get(port as usize)
+            .copied()
+            .ok_or(Error::Port)
+    }
+}
+
 impl GpioBind for Gpio {
     fn initialize(&self) -> Result<(), Error> {
         Ok(())
@@ -36,8 +164,20 @@ impl GpioBind for Gpio {
 
     fn set_output(&self, port: u8, value: Value) -> Result<(), Error> {
         let mut state = self.state.lock().unwrap();
-        let port = state.output.get_mut(port as usize).ok_or(Error::Port)?;
-        *port = value;
+        let output = state.output.get_mut(port as usize).ok_or(Error::Port)?;
+        if *output == value {
+            return Ok(());
+        }
+        *output = value;
+        let event = OutputEvent {
+            timestamp: (state.clock)(),
+            port,
+            value,
+        };
+        // 受信側が破棄された購読は取り除く
+        state
+            .subscribers
+            .retain(|subscriber| subscriber.send(event).is_ok());
         Ok(())
     }
 
@@ -49,11 +189,88 @@ impl GpioBind for Gpio {
 
     fn get_input(&self, port: u8) -> Result<Value, Error> {
         let state = self.state.lock().unwrap();
-        if (port as usize) < state.num_inputs {
-            // FIXME: Emulate inputs
-            Ok(Value::High)
-        } else {
-            Err(Error::Port)
-        }
+        let input = state.input.get(port as usize).ok_or(Error::Port)?;
+        Ok(input.value((state.clock)()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+
+    fn gpio() -> (Gpio, Arc<AtomicU64>) {
+        let gpio = Gpio::new(2, 2);
+        let now_ms = Arc::new(AtomicU64::new(0));
+        gpio.handle().set_clock({
+            let now_ms = now_ms.clone();
+            Arc::new(move || Duration::from_millis(now_ms.load(Ordering::Relaxed)))
+        });
+        (gpio, now_ms)
+    }
+
+    fn input(gpio: &Gpio, port: u8) -> bool {
+        gpio.get_input(port).ok().unwrap().into()
+    }
+
+    #[test]
+    fn waveform_starts_when_set() {
+        let (gpio, now_ms) = gpio();
+        now_ms.store(100, Ordering::Relaxed);
+        let waveform = Waveform::new([(Duration::from_millis(10), Value::Low)]);
+        assert!(gpio.handle().set_waveform(1, waveform).is_ok());
+        // 最初のステップまでは設定した時点の値を保つ
+        now_ms.store(105, Ordering::Relaxed);
+        assert!(input(&gpio, 1));
+        now_ms.store(110, Ordering::Relaxed);
+        assert!(!input(&gpio, 1));
+        assert!(input(&gpio, 0));
+        assert!(
+            gpio.handle()
+                .set_waveform(2, Waveform::square(Duration::ZERO, Duration::ZERO))
+                == Err(Error::Port)
+        );
+    }
+
+    #[test]
+    fn output_events_fire_only_on_change() {
+        let (gpio, now_ms) = gpio();
+        let events = gpio.handle().subscribe_outputs();
+        now_ms.store(5, Ordering::Relaxed);
+        assert!(gpio.set_output(0, Value::Low).is_ok());
+        assert!(gpio.set_output(0, Value::High).is_ok());
+        now_ms.store(7, Ordering::Relaxed);
+        assert!(gpio.set_output(0, Value::High).is_ok());
+        assert!(gpio.set_output(1, Value::High).is_ok());
+        assert!(gpio.set_output(2, Value::High) == Err(Error::Port));
+
+        let events: Vec<_> = events.try_iter().collect();
+        assert!(
+            events
+                == [
+                    OutputEvent {
+                        timestamp: Duration::from_millis(5),
+                        port: 0,
+                        value: Value::High,
+                    },
+                    OutputEvent {
+                        timestamp: Duration::from_millis(7),
+                        port: 1,
+                        value: Value::High,
+                    },
+                ]
+        );
+        assert!(gpio.handle().output(1) == Ok(Value::High));
+    }
+
+    #[test]
+    fn dropped_subscriber_is_removed() {
+        let (gpio, _) = gpio();
+        drop(gpio.handle().subscribe_outputs());
+        let events = gpio.handle().subscribe_outputs();
+        assert!(gpio.set_output(0, Value::High).is_ok());
+        assert_eq!(gpio.state.lock().unwrap().subscribers.len(), 1);
+        assert_eq!(events.try_iter().count(), 1);
     }
 }
@@ -0,0 +1,141 @@
+use std::time::Duration;
+
+use c2a_monazite_gpio_bind::Value;
+
+/// 入力ポートに与える値の時系列
+///
+/// 各ステップは波形の開始からの経過時間と、その時刻以降の値からなる。
+#[derive(Clone)]
+pub struct Waveform {
+    steps: Vec<(Duration, Value)>,
+    period: Option<Duration>,
+}
+
+impl Waveform {
+    /// 最後のステップの後はその値を保つ
+    #[must_use]
+    pub fn new(steps: impl IntoIterator<Item = (Duration, Value)>) -> Self {
+        let mut steps: Vec<_> = steps.into_iter().collect();
+        steps.sort_by_key(|(at, _)| *at);
+        Self {
+            steps,
+            period: None,
+        }
+    }
+
+    /// `period` ごとに波形を繰り返す
+    ///
+    /// `period` 以降のステップは無視する。`period` が 0 の場合は繰り返さない。
+    #[must_use]
+    pub fn repeat(mut self, period: Duration) -> Self {
+        self.period = (!period.is_zero()).then_some(period);
+        self
+    }
+
+    /// `high` と `low` の間 `High` と `Low` を交互に繰り返す矩形波
+    #[must_use]
+    pub fn square(high: Duration, low: Duration) -> Self {
+        Self::new([(Duration::ZERO, Value::High), (high, Value::Low)]).repeat(high + low)
+    }
+
+    /// 開始から `elapsed` 経過した時点の値。最初のステップより前の場合は `None` を返す
+    pub(crate) fn value_at(&self, elapsed: Duration) -> Option<Value> {
+        let Some(period) = self.period else {
+            return self.last_step(|at| at <= elapsed);
+        };
+        // 経過時間の余りは `period` 未満であり、`u64` に収まる
+        #[allow(clippy::cast_possible_truncation)]
+        let phase = Duration::from_nanos((elapsed.as_nanos() % period.as_nanos()) as u64);
+        let value = self.last_step(|at| at <= phase);
+        if value.is_some() || elapsed < period {
+            return value;
+        }
+        // 2 周目以降の最初のステップより前は、前の周期の最後の値とする
+        self.last_step(|at| at < period)
+    }
+
+    fn last_step(&self, pred: impl Fn(Duration) -> bool) -> Option<Value> {
+        self.steps
+            .iter()
+            .take_while(|(at, _)| pred(*at))
+            .last()
+            .map(|(_, value)| *value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn values(waveform: &Waveform, elapsed: impl IntoIterator<Item = u64>) -> Vec<Option<bool>> {
+        elapsed
+            .into_iter()
+            .map(|elapsed| waveform.value_at(ms(elapsed)).map(bool::from))
+            .collect()
+    }
+
+    #[test]
+    fn holds_last_step_without_repeat() {
+        let waveform = Waveform::new([(ms(3), Value::Low), (ms(1), Value::High)]);
+        assert_eq!(
+            values(&waveform, [0, 1, 2, 3, 100]),
+            [None, Some(true), Some(true), Some(false), Some(false)]
+        );
+    }
+
+    #[test]
+    fn repeat_wraps_around_period() {
+        // 周期より後のステップは無視する
+        let waveform = Waveform::new([
+            (ms(1), Value::High),
+            (ms(3), Value::Low),
+            (ms(7), Value::High),
+        ])
+        .repeat(ms(5));
+        assert_eq!(
+            values(&waveform, [0, 1, 3, 4, 5, 6, 8, 10, 11]),
+            [
+                None,
+                Some(true),
+                Some(false),
+                Some(false),
+                // 2 周目の最初のステップより前は、1 周目の最後の値を保つ
+                Some(false),
+                Some(true),
+                Some(false),
+                Some(false),
+                Some(true),
+            ]
+        );
+    }
+
+    #[test]
+    fn repeat_zero_does_not_repeat() {
+        let waveform = Waveform::new([(ms(1), Value::High), (ms(3), Value::Low)]).repeat(ms(0));
+        assert_eq!(
+            values(&waveform, [0, 1, 3, 6, 1000]),
+            [None, Some(true), Some(false), Some(false), Some(false)]
+        );
+    }
+
+    #[test]
+    fn square_alternates() {
+        let waveform = Waveform::square(ms(2), ms(3));
+        assert_eq!(
+            values(&waveform, [0, 1, 2, 4, 5, 7, 10]),
+            [
+                Some(true),
+                Some(true),
+                Some(false),
+                Some(false),
+                Some(true),
+                Some(false),
+                Some(true),
+            ]
+        );
+    }
+}
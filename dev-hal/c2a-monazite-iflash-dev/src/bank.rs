@@ -0,0 +1,90 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use c2a_monazite_iflash_bind::Error;
+
+pub const BANK_SIZE: usize = 1024 * 1024; // 1MB
+pub const SECTOR_SIZE: usize = 128 * 1024; // 128KB
+pub const ROW_SIZE: usize = 32; // 256bit
+
+const ERASED: u8 = 0xFF;
+
+/// ファイルに永続化された内蔵 Flash の 1 バンク分のイメージ
+pub struct Bank {
+    file: File,
+    image: Vec<u8>,
+}
+
+impl Bank {
+    /// `path` のイメージファイルを開く
+    ///
+    /// ファイルが存在しないかサイズが 1 バンク分でない場合は、全体を消去済みの状態で作り直す。
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let mut image = Vec::with_capacity(BANK_SIZE);
+        file.read_to_end(&mut image)?;
+        let mut bank = Self { file, image };
+        if bank.image.len() != BANK_SIZE {
+            bank.image = vec![ERASED; BANK_SIZE];
+            bank.file.set_len(0)?;
+            bank.persist(0..BANK_SIZE)?;
+        }
+        Ok(bank)
+    }
+
+    fn persist(&mut self, range: core::ops::Range<usize>) -> std::io::Result<()> {
+        self.file.seek(SeekFrom::Start(range.start as u64))?;
+        self.file.write_all(&self.image[range])?;
+        self.file.flush()
+    }
+
+    /// 変更したイメージをファイルに書き出す
+    ///
+    /// 書き出せなかった場合は、実機で操作が失敗した場合と同じく [`Error::Operation`] を返す。
+    fn persist_or_fail(&mut self, range: core::ops::Range<usize>) -> Result<(), Error> {
+        self.persist(range).map_err(|e| {
+            eprintln!("failed to write iflash image: {e}");
+            Error::Operation
+        })
+    }
+
+    /// `sector` 番目のセクタを消去する
+    ///
+    /// # Errors
+    /// イメージファイルへの書き込みに失敗した場合は [`Error::Operation`] を返す。
+    pub fn erase_sector(&mut self, sector: u8) -> Result<(), Error> {
+        let range = sector as usize * SECTOR_SIZE..(sector as usize + 1) * SECTOR_SIZE;
+        self.image[range.clone()].fill(ERASED);
+        self.persist_or_fail(range)
+    }
+
+    /// `offset` から 1 行（256bit）を書き込む
+    ///
+    /// # Errors
+    /// 書き込み先に消去されていないバイトが含まれる場合、何も書き込まずに [`Error::Operation`] を返す。
+    /// 実機では ECC のため、一度書き込んだ Flash word を消去せずに再度書き込むことはできない。
+    /// イメージファイルへの書き込みに失敗した場合も [`Error::Operation`] を返す。
+    pub fn program_row(&mut self, offset: usize, row: &[u8]) -> Result<(), Error> {
+        debug_assert_eq!(offset % ROW_SIZE, 0);
+        debug_assert_eq!(row.len(), ROW_SIZE);
+        let range = offset..offset + ROW_SIZE;
+        if self.image[range.clone()].iter().any(|&b| b != ERASED) {
+            return Err(Error::Operation);
+        }
+        self.image[range.clone()].copy_from_slice(row);
+        self.persist_or_fail(range)
+    }
+
+    /// `offset` から `buf` の長さ分のデータを読み出す
+    pub fn read(&self, offset: usize, buf: &mut [u8]) {
+        buf.copy_from_slice(&self.image[offset..offset + buf.len()]);
+    }
+}
@@ -1,27 +1,362 @@
-use std::convert::Infallible;
+mod bank;
+
+use std::{
+    convert::Infallible,
+    io,
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 
 use c2a_monazite_iflash_bind::{Error, Iflash as IflashBind};
 
-pub struct Iflash(core::marker::PhantomData<()>);
+use bank::Bank;
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Idle { last_error: Option<Error> },
+    Erasing(u8),
+    Programming { dest: usize, src: usize, len: usize },
+}
+
+/// 内蔵 Flash の操作にかかる時間
+#[derive(Clone, Copy)]
+pub struct Timing {
+    /// 1 セクタの消去にかかる時間
+    pub sector_erase: Duration,
+    /// 1 行（256bit）の書き込みにかかる時間
+    pub row_program: Duration,
+}
+
+impl Timing {
+    /// すべての操作が即座に完了する
+    pub const IMMEDIATE: Self = Self {
+        sector_erase: Duration::ZERO,
+        row_program: Duration::ZERO,
+    };
+}
+
+impl Default for Timing {
+    /// STM32H753 の典型的な値におおよそ合わせたもの
+    fn default() -> Self {
+        Self {
+            sector_erase: Duration::from_secs(2),
+            row_program: Duration::from_micros(16),
+        }
+    }
+}
+
+/// monazite-rt の `iflash::Inner` と同じ状態遷移をファイル上のイメージに対して行う
+///
+/// 実機では EOP 割り込みで状態が進むが、ここでは [`Timing`] に従って各ステップの完了時刻を決め、
+/// 状態を参照するたびにその時刻までのステップを進める。
+struct Inner {
+    buffer: Vec<u8>,
+    bank: Bank,
+    state: State,
+    timing: Timing,
+    // 実行中のステップが完了する時刻
+    deadline: Instant,
+}
+
+impl Inner {
+    const SECTOR_FIRST: u8 = 0;
+    const SECTOR_LAST: u8 = 6; // sector 7 is for the bootloader
+
+    const FLASH_BANK_SIZE: usize = bank::BANK_SIZE;
+    const FLASH_ROW_SIZE: usize = bank::ROW_SIZE;
+
+    // monazite-rt の IFLASH_BUF と同じサイズ
+    const PROGRAM_BUFFER_SIZE: usize = 128;
+
+    fn new(bank: Bank, timing: Timing) -> Self {
+        Self {
+            buffer: vec![0; Self::PROGRAM_BUFFER_SIZE],
+            bank,
+            state: State::Idle { last_error: None },
+            timing,
+            deadline: Instant::now(),
+        }
+    }
+
+    fn step_duration(&self) -> Duration {
+        match self.state {
+            State::Idle { .. } => Duration::ZERO,
+            State::Erasing(_) => self.timing.sector_erase,
+            State::Programming { .. } => self.timing.row_program,
+        }
+    }
+
+    /// `now` までに完了しているステップをすべて進める
+    fn poll(&mut self, now: Instant) {
+        while !matches!(self.state, State::Idle { .. }) && self.deadline <= now {
+            self.step();
+            self.deadline += self.step_duration();
+        }
+    }
+
+    /// 実行中の操作を 1 ステップ（1 セクタの消去または 1 行の書き込み）進める
+    fn step(&mut self) {
+        match self.state {
+            State::Idle { .. } => {}
+            State::Erasing(sector) => {
+                if let Err(err) = self.bank.erase_sector(sector) {
+                    self.state = State::Idle {
+                        last_error: Some(err),
+                    };
+                    return;
+                }
+                if sector == Self::SECTOR_LAST {
+                    self.state = State::Idle { last_error: None };
+                } else {
+                    self.state = State::Erasing(sector + 1);
+                }
+            }
+            State::Programming { dest, src, len } => {
+                let row = &self.buffer[src..src + Self::FLASH_ROW_SIZE];
+                if let Err(err) = self.bank.program_row(dest, row) {
+                    self.state = State::Idle {
+                        last_error: Some(err),
+                    };
+                    return;
+                }
+                let dest = dest + Self::FLASH_ROW_SIZE;
+                let src = src + Self::FLASH_ROW_SIZE;
+                let len = len - Self::FLASH_ROW_SIZE;
+                if len == 0 {
+                    self.state = State::Idle { last_error: None };
+                } else {
+                    self.state = State::Programming { dest, src, len };
+                }
+            }
+        }
+    }
+
+    fn start(&mut self, state: State, now: Instant) {
+        self.state = state;
+        self.deadline = now + self.step_duration();
+    }
+
+    fn erase(&mut self, now: Instant) -> nb::Result<(), Infallible> {
+        if let State::Idle { .. } = self.state {
+            self.start(State::Erasing(Self::SECTOR_FIRST), now);
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    fn program(&mut self, offset: usize, data: &[u8], now: Instant) -> nb::Result<(), Error> {
+        if offset % Self::FLASH_ROW_SIZE != 0 {
+            return Err(Error::NotAligned.into());
+        }
+        if data.len() % Self::FLASH_ROW_SIZE != 0 {
+            return Err(Error::NotAligned.into());
+        }
+        if data.len() > self.buffer.len() {
+            return Err(Error::OutOfBounds.into());
+        }
+        if offset + data.len() > Self::FLASH_BANK_SIZE {
+            return Err(Error::OutOfBounds.into());
+        }
+        if let State::Idle { .. } = self.state {
+            if data.is_empty() {
+                // 長さ 0 の書き込みは何もせずに完了とする
+                self.state = State::Idle { last_error: None };
+                return Ok(());
+            }
+            self.buffer[..data.len()].copy_from_slice(data);
+            self.start(
+                State::Programming {
+                    dest: offset,
+                    src: 0,
+                    len: data.len(),
+                },
+                now,
+            );
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    fn status(&self) -> nb::Result<(), Error> {
+        match self.state {
+            State::Idle { last_error: None } => Ok(()),
+            State::Idle {
+                last_error: Some(last_error),
+            } => Err(nb::Error::Other(last_error)),
+            State::Erasing(_) | State::Programming { .. } => Err(nb::Error::WouldBlock),
+        }
+    }
+}
+
+/// 書き込み先のバンク（Bank 2）のイメージをファイルに永続化する内蔵 Flash のエミュレータ
+pub struct Iflash {
+    inner: Mutex<Inner>,
+}
 
 impl Iflash {
-    #[allow(clippy::new_without_default)]
-    #[must_use]
-    pub fn new() -> Self {
-        Self(core::marker::PhantomData)
+    /// `path` のファイルをバンクのイメージとして `Iflash` を構築する
+    ///
+    /// ファイルが存在しない場合は、消去済みのイメージで新たに作成する。
+    /// 各操作には [`Timing::default`] の時間がかかる。
+    ///
+    /// # Errors
+    /// イメージファイルの読み書きに失敗した場合は [`io::Error`] を返す。
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::with_timing(path, Timing::default())
+    }
+
+    /// 各操作に `timing` の時間がかかる `Iflash` を構築する
+    ///
+    /// # Errors
+    /// イメージファイルの読み書きに失敗した場合は [`io::Error`] を返す。
+    pub fn with_timing(path: impl AsRef<Path>, timing: Timing) -> io::Result<Self> {
+        let bank = Bank::open(path.as_ref())?;
+        Ok(Self {
+            inner: Mutex::new(Inner::new(bank, timing)),
+        })
+    }
+
+    /// バンクの先頭から `offset` バイトの位置のデータを読み出す
+    ///
+    /// # Panics
+    /// 読み出し範囲がバンクの範囲外の場合は panic する。
+    pub fn read(&self, offset: usize, buf: &mut [u8]) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.poll(Instant::now());
+        inner.bank.read(offset, buf);
     }
 }
 
 impl IflashBind for Iflash {
     fn start_erase(&self) -> nb::Result<(), Infallible> {
-        Ok(())
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+        inner.poll(now);
+        inner.erase(now)
     }
 
-    fn start_program(&self, _offset: usize, _data: &[u8]) -> nb::Result<(), Error> {
-        Ok(())
+    fn start_program(&self, offset: usize, data: &[u8]) -> nb::Result<(), Error> {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+        inner.poll(now);
+        inner.program(offset, data, now)
     }
 
     fn status(&self) -> nb::Result<(), Error> {
-        Ok(())
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+        inner.poll(now);
+        inner.status()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::*;
+
+    const TIMING: Timing = Timing {
+        sector_erase: Duration::from_millis(10),
+        row_program: Duration::from_millis(1),
+    };
+    const ROW: usize = Inner::FLASH_ROW_SIZE;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    /// テストごとのイメージファイルで `Inner` を構築する
+    fn inner(name: &str) -> (Inner, PathBuf) {
+        let path = std::env::temp_dir().join(format!(
+            "c2a-monazite-iflash-dev-{name}-{}.bin",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        let bank = Bank::open(&path).unwrap();
+        (Inner::new(bank, TIMING), path)
+    }
+
+    #[test]
+    fn erase_blocks_until_all_sectors_are_erased() {
+        let (mut inner, path) = inner("erase");
+        let t0 = Instant::now();
+        assert!(inner.erase(t0) == Ok(()));
+        assert!(inner.status() == Err(nb::Error::WouldBlock));
+        assert!(inner.erase(t0) == Err(nb::Error::WouldBlock));
+        assert!(inner.program(0, &[0; ROW], t0) == Err(nb::Error::WouldBlock));
+
+        // 7 セクタ分の時間が経つまで完了しない
+        inner.poll(t0 + ms(69));
+        assert!(inner.state == State::Erasing(Inner::SECTOR_LAST));
+        assert!(inner.status() == Err(nb::Error::WouldBlock));
+        inner.poll(t0 + ms(70));
+        assert!(inner.status() == Ok(()));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn program_writes_one_row_per_step() {
+        let (mut inner, path) = inner("program");
+        let t0 = Instant::now();
+        let data: Vec<u8> = (0..).take(2 * ROW).collect();
+        assert!(inner.program(ROW, &data, t0) == Ok(()));
+        assert!(inner.status() == Err(nb::Error::WouldBlock));
+
+        inner.poll(t0 + ms(1));
+        assert!(inner.status() == Err(nb::Error::WouldBlock));
+        let mut buf = [0; 2 * ROW];
+        inner.bank.read(ROW, &mut buf);
+        assert_eq!(buf[..ROW], data[..ROW]);
+        assert!(buf[ROW..].iter().all(|&b| b == 0xFF));
+
+        inner.poll(t0 + ms(2));
+        assert!(inner.status() == Ok(()));
+        inner.bank.read(ROW, &mut buf);
+        assert_eq!(buf[..], data[..]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn programming_unerased_row_fails() {
+        let (mut inner, path) = inner("reprogram");
+        let t0 = Instant::now();
+        assert!(inner.program(0, &[0x55; ROW], t0) == Ok(()));
+        inner.poll(t0 + ms(1));
+        assert!(inner.status() == Ok(()));
+
+        // 2 行目は消去済みだが、1 行目で失敗して以降は書き込まない
+        assert!(inner.program(0, &[0xAA; 2 * ROW], t0 + ms(1)) == Ok(()));
+        inner.poll(t0 + ms(10));
+        assert!(inner.status() == Err(nb::Error::Other(Error::Operation)));
+        let mut buf = [0; 2 * ROW];
+        inner.bank.read(0, &mut buf);
+        assert!(buf[..ROW].iter().all(|&b| b == 0x55));
+        assert!(buf[ROW..].iter().all(|&b| b == 0xFF));
+
+        // 消去すれば書き込める
+        assert!(inner.erase(t0 + ms(10)) == Ok(()));
+        inner.poll(t0 + ms(80));
+        assert!(inner.program(0, &[0xAA; ROW], t0 + ms(80)) == Ok(()));
+        inner.poll(t0 + ms(81));
+        assert!(inner.status() == Ok(()));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_misaligned_and_out_of_bounds_program() {
+        let (mut inner, path) = inner("bounds");
+        let t0 = Instant::now();
+        assert!(inner.program(1, &[0; ROW], t0) == Err(nb::Error::Other(Error::NotAligned)));
+        assert!(inner.program(0, &[0; ROW + 1], t0) == Err(nb::Error::Other(Error::NotAligned)));
+        assert!(
+            inner.program(Inner::FLASH_BANK_SIZE - ROW, &[0; 2 * ROW], t0)
+                == Err(nb::Error::Other(Error::OutOfBounds))
+        );
+        assert!(inner.status() == Ok(()));
+        fs::remove_file(path).unwrap();
     }
 }
@@ -1,47 +1,441 @@
-use c2a_monazite_ramecc_bind::Ramecc as RameccBind;
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
-pub struct Ramecc(core::marker::PhantomData<()>);
+use c2a_monazite_ramecc_bind::{
+    ErrorCount, Fault, Ramecc as RameccBind, Region, ScrubbingProgress, MAX_SCRUBBING_WORDS,
+    SCRUB_TARGET_ALL,
+};
+use ram_scrub::{
+    Targets, Walker, AXI_SRAM, BSRAM, DTCM, ITCM, MAX_CHUNK_WORDS, SRAM1_0, SRAM1_1, SRAM2_0,
+    SRAM2_1, SRAM3, SRAM4,
+};
+
+// C2A に設定させる値の範囲は、ram-scrub で扱える範囲と一致していなければならない
+const _: () = assert!(SCRUB_TARGET_ALL == Targets::ALL.bits());
+const _: () = assert!(MAX_SCRUBBING_WORDS as usize == MAX_CHUNK_WORDS);
+
+// DTCM 以外のメモリ領域
+const REGIONS: [(Range<usize>, Region); 9] = [
+    (AXI_SRAM, Region::AxiSram),
+    (SRAM1_0, Region::Sram1_0),
+    (SRAM1_1, Region::Sram1_1),
+    (SRAM2_0, Region::Sram2_0),
+    (SRAM2_1, Region::Sram2_1),
+    (SRAM3, Region::Sram3),
+    (SRAM4, Region::Sram4),
+    (ITCM, Region::Itcm),
+    (BSRAM, Region::Bsram),
+];
+
+/// `address` を含むメモリ領域
+fn region_of(address: usize) -> Option<Region> {
+    if DTCM.contains(&address) {
+        // DTCM は 4 byte ごとに D0TCM と D1TCM が交互に並ぶ
+        return Some(if (address / 4) % 2 == 0 {
+            Region::D0Tcm
+        } else {
+            Region::D1Tcm
+        });
+    }
+    REGIONS
+        .iter()
+        .find(|(range, _)| range.contains(&address))
+        .map(|(_, region)| *region)
+}
+
+// monazite-rt と同じく、SysTick ごとに数える
+const SCRUB_TICK: Duration = Duration::from_millis(1);
+const MEMORY_SCRUB_INTERVAL_TICKS: u32 = 1000;
+const MEMORY_SCRUB_WORDS: usize = 100;
+const MEMORY_SCRUB_TARGETS: Targets = Targets::ALL;
+
+/// 時刻を数える方法
+pub type Clock = Arc<dyn Fn() -> Duration + Send + Sync>;
+
+/// 注入する ECC エラーの種類
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorKind {
+    /// 訂正された 1 bit のエラー
+    Single,
+    /// 訂正できない 2 bit のエラー
+    Double,
+    /// バイト単位の書き込み時に検出された 2 bit のエラー
+    DoubleOnByteWrite,
+}
+
+/// ECC で保護された RAM の範囲外のアドレス
+#[derive(Debug)]
+pub struct InvalidAddress;
+
+#[derive(Default)]
+struct Counters {
+    single: u32,
+    double: u32,
+    double_on_byte_write: u32,
+}
+
+impl Counters {
+    fn count(&mut self, kind: ErrorKind) {
+        let counter = match kind {
+            ErrorKind::Single => &mut self.single,
+            ErrorKind::Double => &mut self.double,
+            ErrorKind::DoubleOnByteWrite => &mut self.double_on_byte_write,
+        };
+        *counter = counter.wrapping_add(1);
+    }
+
+    fn to_error_count(&self) -> ErrorCount {
+        ErrorCount {
+            single_error: self.single,
+            double_error: self.double,
+            double_error_on_byte_write: self.double_on_byte_write,
+        }
+    }
+}
+
+/// `now` までの `SysTick` の数
+fn ticks(now: Duration) -> u64 {
+    u64::try_from(now.as_nanos() / SCRUB_TICK.as_nanos()).unwrap_or(u64::MAX)
+}
+
+/// `SysTick` を数えてスクラブする範囲を進める
+struct Scrub {
+    interval: u32,
+    // 前回進めた時点の SysTick の数
+    ticks: u64,
+    // 次のスクラブまでに数えた SysTick の数
+    counter: u64,
+    walker: Walker,
+}
+
+impl Scrub {
+    /// `now` までの `SysTick` の分だけスクラブを進める
+    fn advance(&mut self, now: Duration) {
+        let ticks = ticks(now);
+        let elapsed = ticks.saturating_sub(self.ticks);
+        self.ticks = self.ticks.max(ticks);
+        // monazite-rt と同じく、間隔が 0 の場合は SysTick ごとにスクラブする
+        let interval = u64::from(self.interval.max(1));
+        // 間隔を数えた回数より小さく変更した場合も、次の SysTick で 1 回だけスクラブする
+        let first = interval.saturating_sub(self.counter).max(1);
+        let Some(rest) = elapsed.checked_sub(first) else {
+            self.counter += elapsed;
+            return;
+        };
+        self.counter = rest % interval;
+        self.walker.skip_chunks(1 + rest / interval);
+    }
+}
+
+struct State {
+    clock: Clock,
+    scrub: Scrub,
+    errors: Counters,
+    dtcm_errors: Counters,
+    region_errors: [Counters; Region::ALL.len()],
+    last_fault: Option<Fault>,
+}
+
+impl State {
+    fn scrub(&mut self) -> &Scrub {
+        let now = (self.clock)();
+        self.scrub.advance(now);
+        &self.scrub
+    }
+}
+
+/// RAM のスクラブの進み具合と、注入した ECC エラーを数える
+pub struct Ramecc {
+    state: Arc<Mutex<State>>,
+}
 
 impl Ramecc {
+    /// 時刻は構築した時点からの実時間で数える
     #[allow(clippy::new_without_default)]
     #[must_use]
     pub fn new() -> Self {
-        Ramecc(core::marker::PhantomData)
+        let start = Instant::now();
+        let mut walker = Walker::new(MEMORY_SCRUB_TARGETS, MEMORY_SCRUB_WORDS);
+        // monazite-rt と同じく、構築した時点で最初の範囲を読み出し始める
+        walker.next();
+        Ramecc {
+            state: Arc::new(Mutex::new(State {
+                clock: Arc::new(move || start.elapsed()),
+                scrub: Scrub {
+                    interval: MEMORY_SCRUB_INTERVAL_TICKS,
+                    ticks: 0,
+                    counter: 0,
+                    walker,
+                },
+                errors: Counters::default(),
+                dtcm_errors: Counters::default(),
+                region_errors: Default::default(),
+                last_fault: None,
+            })),
+        }
+    }
+
+    /// C2A から独立して ECC エラーを注入するためのハンドルを返す
+    #[must_use]
+    pub fn handle(&self) -> Handle {
+        Handle {
+            state: self.state.clone(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+}
+
+/// [`Ramecc`] に ECC エラーを注入する
+///
+/// 複製したものは同じ [`Ramecc`] を操作する。
+#[derive(Clone)]
+pub struct Handle {
+    state: Arc<Mutex<State>>,
+}
+
+impl Handle {
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// 時刻を数える方法を `clock` に変更する
+    ///
+    /// スクラブは変更した時点の時刻から進める。
+    pub fn set_clock(&self, clock: Clock) {
+        let mut state = self.lock();
+        state.scrub.ticks = ticks(clock());
+        state.clock = clock;
+    }
+
+    /// `address` で `kind` のエラーが検出され、そのデータが `data` だったものとして数える
+    ///
+    /// monazite-rt と同じく、DTCM のエラーは他の RAM とは別に数える。
+    /// ワードが 64 bit の AXI SRAM と ITCM 以外では、`data` の下位 32 bit だけを記録する。
+    ///
+    /// # Errors
+    /// `address` が ECC で保護された RAM の範囲外の場合は [`InvalidAddress`] を返す。
+    pub fn inject(&self, address: usize, kind: ErrorKind, data: u64) -> Result<(), InvalidAddress> {
+        let region = region_of(address).ok_or(InvalidAddress)?;
+        let mut state = self.lock();
+        if matches!(region, Region::D0Tcm | Region::D1Tcm) {
+            state.dtcm_errors.count(kind);
+        } else {
+            state.errors.count(kind);
+        }
+        state.region_errors[region as usize].count(kind);
+        #[allow(clippy::cast_possible_truncation)]
+        let fault = Fault {
+            region: region as u32,
+            address: address as u32,
+            data_low: data as u32,
+            data_high: if matches!(region, Region::AxiSram | Region::Itcm) {
+                (data >> 32) as u32
+            } else {
+                0
+            },
+        };
+        state.last_fault = Some(fault);
+        Ok(())
     }
 }
 
 impl RameccBind for Ramecc {
     fn scrubbing_loops(&self) -> u32 {
-        0
+        self.lock().scrub().walker.progress().loops
     }
 
     fn scrubbing_interval(&self) -> u32 {
-        0
+        self.lock().scrub.interval
     }
 
     fn single_errors(&self) -> u32 {
-        0
+        self.lock().errors.single
     }
 
     fn double_errors(&self) -> u32 {
-        0
+        self.lock().errors.double
     }
 
     fn double_errors_on_byte_write(&self) -> u32 {
-        0
+        self.lock().errors.double_on_byte_write
     }
 
     fn dtcm_single_errors(&self) -> u32 {
-        0
+        self.lock().dtcm_errors.single
     }
 
     fn dtcm_double_errors(&self) -> u32 {
-        0
+        self.lock().dtcm_errors.double
     }
 
     fn dtcm_double_errors_on_byte_write(&self) -> u32 {
-        0
+        self.lock().dtcm_errors.double_on_byte_write
+    }
+
+    fn set_scrubbing_interval(&self, scrubbing_interval_tick: u32) {
+        let mut state = self.lock();
+        // 変更前の間隔で進んだ分を反映してから変更する
+        state.scrub();
+        state.scrub.interval = scrubbing_interval_tick;
+    }
+
+    fn region_errors(&self, region: Region) -> ErrorCount {
+        self.lock().region_errors[region as usize].to_error_count()
+    }
+
+    fn last_fault(&self) -> Option<Fault> {
+        self.lock().last_fault
+    }
+
+    fn scrubbing_targets(&self) -> u32 {
+        self.lock().scrub.walker.targets().bits()
+    }
+
+    fn set_scrubbing_targets(&self, targets: u32) {
+        let Some(targets) = Targets::from_bits(targets) else {
+            return;
+        };
+        let mut state = self.lock();
+        state.scrub();
+        if targets != state.scrub.walker.targets() {
+            state.scrub.walker.set_targets(targets);
+        }
+    }
+
+    fn scrubbing_words(&self) -> u32 {
+        #[allow(clippy::cast_possible_truncation)]
+        let words = self.lock().scrub.walker.chunk_words() as u32;
+        words
+    }
+
+    fn set_scrubbing_words(&self, words: u32) {
+        let mut state = self.lock();
+        state.scrub();
+        state.scrub.walker.set_chunk_words(words as usize);
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn scrubbing_progress(&self) -> ScrubbingProgress {
+        let progress = self.lock().scrub().walker.progress();
+        ScrubbingProgress {
+            scrubbed_bytes: progress.scrubbed_bytes as u32,
+            total_bytes: progress.total_bytes as u32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+
+    const CHUNK_BYTES: usize = MEMORY_SCRUB_WORDS * 4;
+
+    fn ramecc() -> (Ramecc, Arc<AtomicU64>) {
+        let ramecc = Ramecc::new();
+        let now_ms = Arc::new(AtomicU64::new(0));
+        ramecc.handle().set_clock({
+            let now_ms = now_ms.clone();
+            Arc::new(move || Duration::from_millis(now_ms.load(Ordering::Relaxed)))
+        });
+        (ramecc, now_ms)
+    }
+
+    /// 構築した時点で読み出し始めた範囲を除いて、スクラブした回数
+    fn scrubbed_chunks(ramecc: &Ramecc) -> usize {
+        ramecc.scrubbing_progress().scrubbed_bytes as usize / CHUNK_BYTES - 1
+    }
+
+    #[test]
+    fn interval_zero_scrubs_every_tick() {
+        let (ramecc, now_ms) = ramecc();
+        ramecc.set_scrubbing_interval(0);
+        assert_eq!(ramecc.scrubbing_interval(), 0);
+        now_ms.store(5, Ordering::Relaxed);
+        assert_eq!(scrubbed_chunks(&ramecc), 5);
     }
 
-    fn set_scrubbing_interval(&self, _scrubbing_interval_tick: u32) {}
+    #[test]
+    fn interval_change_keeps_counted_ticks() {
+        let (ramecc, now_ms) = ramecc();
+        ramecc.set_scrubbing_interval(10);
+        now_ms.store(4, Ordering::Relaxed);
+        ramecc.set_scrubbing_interval(6);
+        now_ms.store(5, Ordering::Relaxed);
+        assert_eq!(scrubbed_chunks(&ramecc), 0);
+        // 変更前に数えた 4 回と合わせて 6 回目の SysTick でスクラブする
+        now_ms.store(6, Ordering::Relaxed);
+        assert_eq!(scrubbed_chunks(&ramecc), 1);
+
+        now_ms.store(11, Ordering::Relaxed);
+        ramecc.set_scrubbing_interval(3);
+        // 数えた回数が新しい間隔以上でも、次の SysTick で 1 回だけスクラブする
+        now_ms.store(12, Ordering::Relaxed);
+        assert_eq!(scrubbed_chunks(&ramecc), 2);
+        now_ms.store(15, Ordering::Relaxed);
+        assert_eq!(scrubbed_chunks(&ramecc), 3);
+    }
+
+    #[test]
+    fn dtcm_words_alternate_between_d0_and_d1() {
+        assert_eq!(region_of(DTCM.start), Some(Region::D0Tcm));
+        assert_eq!(region_of(DTCM.start + 3), Some(Region::D0Tcm));
+        assert_eq!(region_of(DTCM.start + 4), Some(Region::D1Tcm));
+        assert_eq!(region_of(DTCM.start + 8), Some(Region::D0Tcm));
+        assert_eq!(region_of(DTCM.end - 1), Some(Region::D1Tcm));
+        assert_eq!(region_of(DTCM.end), None);
+        assert_eq!(region_of(SRAM4.end - 1), Some(Region::Sram4));
+    }
+
+    #[test]
+    fn inject_rejects_unprotected_address() {
+        let (ramecc, _) = ramecc();
+        let handle = ramecc.handle();
+        assert!(handle.inject(AXI_SRAM.end, ErrorKind::Single, 0).is_err());
+        assert!(handle.inject(0x0800_0000, ErrorKind::Double, 0).is_err());
+        assert_eq!(ramecc.single_errors(), 0);
+        assert_eq!(ramecc.double_errors(), 0);
+        assert!(ramecc.last_fault().is_none());
+    }
+
+    #[test]
+    fn inject_counts_dtcm_separately() {
+        let (ramecc, _) = ramecc();
+        let handle = ramecc.handle();
+        handle.inject(DTCM.start + 4, ErrorKind::Double, 0).unwrap();
+        handle.inject(SRAM3.start, ErrorKind::Double, 0).unwrap();
+        assert_eq!(ramecc.dtcm_double_errors(), 1);
+        assert_eq!(ramecc.double_errors(), 1);
+        assert_eq!(ramecc.region_errors(Region::D1Tcm).double_error, 1);
+        assert_eq!(ramecc.region_errors(Region::D0Tcm).double_error, 0);
+        assert_eq!(ramecc.region_errors(Region::Sram3).double_error, 1);
+    }
+
+    #[test]
+    fn data_high_is_recorded_only_for_64bit_regions() {
+        let (ramecc, _) = ramecc();
+        let handle = ramecc.handle();
+        let data = 0x1234_5678_9ABC_DEF0;
+        for (address, region, data_high) in [
+            (AXI_SRAM.start + 8, Region::AxiSram, 0x1234_5678),
+            (ITCM.start + 8, Region::Itcm, 0x1234_5678),
+            (SRAM1_0.start + 8, Region::Sram1_0, 0),
+            (DTCM.start + 8, Region::D0Tcm, 0),
+            (BSRAM.start + 8, Region::Bsram, 0),
+        ] {
+            handle.inject(address, ErrorKind::Single, data).unwrap();
+            let fault = ramecc.last_fault().unwrap();
+            assert_eq!(fault.region, region as u32);
+            assert_eq!(fault.address as usize, address);
+            assert_eq!(fault.data_low, 0x9ABC_DEF0);
+            assert_eq!(fault.data_high, data_high, "{region:?}");
+        }
+    }
 }
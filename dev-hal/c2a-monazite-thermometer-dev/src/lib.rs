@@ -1,17 +1,172 @@
+mod source;
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
 use c2a_monazite_thermometer_bind::Thermometer as ThermometerBind;
 
-pub struct Thermometer(core::marker::PhantomData<()>);
+pub use sim_trace::Trace;
+pub use source::{Source, ThermalModel};
+
+/// 温度を生成する時刻
+pub type Clock = Arc<dyn Fn() -> Duration + Send + Sync>;
+
+/// 温度センサの故障
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Fault {
+    /// 故障した時点の値のまま変わらない
+    Stuck,
+    /// 指定した値のまま変わらない
+    StuckAt(f32),
+    /// NaN を返す
+    Nan,
+}
+
+struct State {
+    source: Source,
+    // `source` を設定した時刻
+    start: Duration,
+    clock: Clock,
+    // 故障している間、`source` の代わりに返す値
+    fault: Option<f32>,
+}
+
+impl State {
+    fn sample(&mut self) -> f32 {
+        let elapsed = (self.clock)().saturating_sub(self.start);
+        // 故障している間も、センサが測っている物体の温度は変化し続ける
+        let value = self.source.sample(elapsed);
+        self.fault.unwrap_or(value)
+    }
+}
+
+pub struct Thermometer {
+    state: Arc<Mutex<State>>,
+}
 
 impl Thermometer {
+    /// 一定の温度を返し、時刻は構築した時点からの実時間で数える
     #[allow(clippy::new_without_default)]
     #[must_use]
     pub fn new() -> Self {
-        Self(core::marker::PhantomData)
+        let start = Instant::now();
+        Self {
+            state: Arc::new(Mutex::new(State {
+                source: Source::Constant(123.456),
+                start: Duration::ZERO,
+                clock: Arc::new(move || start.elapsed()),
+                fault: None,
+            })),
+        }
+    }
+
+    /// C2A から独立して温度を設定し、故障を注入するためのハンドルを返す
+    #[must_use]
+    pub fn handle(&self) -> Handle {
+        Handle {
+            state: self.state.clone(),
+        }
+    }
+}
+
+/// [`Thermometer`] の温度を設定し、故障を注入する
+///
+/// 複製したものは同じ [`Thermometer`] を操作する。
+#[derive(Clone)]
+pub struct Handle {
+    state: Arc<Mutex<State>>,
+}
+
+impl Handle {
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// 時刻を数える方法を `clock` に変更する
+    ///
+    /// 設定済みの [`Source`] の開始時刻は変わらないため、[`Handle::set_source`] の前に変更する。
+    pub fn set_clock(&self, clock: Clock) {
+        self.lock().clock = clock;
+    }
+
+    /// 現在の時刻から始まる `source` で温度を生成する
+    pub fn set_source(&self, source: Source) {
+        let mut state = self.lock();
+        state.start = (state.clock)();
+        state.source = source;
+    }
+
+    /// 温度を `value` に固定する
+    pub fn set_value(&self, value: f32) {
+        self.set_source(Source::Constant(value));
+    }
+
+    /// `fault` を注入する。`None` の場合は故障から回復させる
+    pub fn set_fault(&self, fault: Option<Fault>) {
+        let mut state = self.lock();
+        state.fault = match fault {
+            None => None,
+            Some(Fault::Stuck) => {
+                let value = state.sample();
+                Some(value)
+            }
+            Some(Fault::StuckAt(value)) => Some(value),
+            Some(Fault::Nan) => Some(f32::NAN),
+        };
     }
 }
 
 impl ThermometerBind for Thermometer {
     fn value(&self) -> f32 {
-        123.456
+        self.state.lock().unwrap().sample()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+
+    fn thermometer() -> (Thermometer, Arc<AtomicU64>) {
+        let thermometer = Thermometer::new();
+        let now_secs = Arc::new(AtomicU64::new(0));
+        let handle = thermometer.handle();
+        handle.set_clock({
+            let now_secs = now_secs.clone();
+            Arc::new(move || Duration::from_secs(now_secs.load(Ordering::Relaxed)))
+        });
+        handle.set_source(Source::Trace(Trace::new([
+            (Duration::ZERO, 0.0),
+            (Duration::from_secs(10), 100.0),
+        ])));
+        (thermometer, now_secs)
+    }
+
+    #[test]
+    fn stuck_keeps_value_at_fault() {
+        let (thermometer, now_secs) = thermometer();
+        now_secs.store(5, Ordering::Relaxed);
+        thermometer.handle().set_fault(Some(Fault::Stuck));
+        now_secs.store(8, Ordering::Relaxed);
+        assert!((thermometer.value() - 50.0).abs() < 1e-3);
+
+        thermometer.handle().set_fault(Some(Fault::StuckAt(-1.5)));
+        assert!((thermometer.value() + 1.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn recovers_to_current_source_value() {
+        let (thermometer, now_secs) = thermometer();
+        thermometer.handle().set_fault(Some(Fault::Nan));
+        now_secs.store(3, Ordering::Relaxed);
+        assert!(thermometer.value().is_nan());
+
+        // 故障している間に進んだ時刻の温度に戻る
+        now_secs.store(8, Ordering::Relaxed);
+        thermometer.handle().set_fault(None);
+        assert!((thermometer.value() - 80.0).abs() < 1e-3);
     }
 }
@@ -0,0 +1,108 @@
+use std::{sync::Arc, time::Duration};
+
+use sim_trace::Trace;
+
+/// 温度（℃）を生成する方法
+#[derive(Clone)]
+pub enum Source {
+    /// 一定の温度
+    Constant(f32),
+    /// ヒーターの状態に応じて変化する温度
+    Thermal(ThermalModel),
+    /// 時系列を線形補間した温度
+    Trace(Trace),
+}
+
+impl Source {
+    /// 設定してから `elapsed` 経過した時点の温度
+    pub(crate) fn sample(&mut self, elapsed: Duration) -> f32 {
+        match self {
+            Self::Constant(value) => *value,
+            Self::Thermal(model) => model.advance(elapsed),
+            #[allow(clippy::cast_possible_truncation)]
+            Self::Trace(trace) => trace.value_at(elapsed) as f32,
+        }
+    }
+}
+
+/// ヒーターで加熱される物体の一次遅れの熱モデル
+///
+/// 温度はヒーターが ON の間は `equilibrium` に、OFF の間は `ambient` に時定数 `time_constant` で近づく。
+/// ヒーターの状態は温度を読み出すたびに確認し、次に読み出すまで変わらないものとする。
+#[derive(Clone)]
+pub struct ThermalModel {
+    ambient: f32,
+    equilibrium: f32,
+    time_constant: Duration,
+    heater: Arc<dyn Fn() -> bool + Send + Sync>,
+    temperature: f32,
+    heater_on: bool,
+    elapsed: Duration,
+}
+
+impl ThermalModel {
+    /// 温度 `initial` から始まるモデルを構築する
+    ///
+    /// `heater` はヒーターが ON かどうかを返す。SILS では GPIO の出力ポートを読み出す。
+    #[must_use]
+    pub fn new(
+        initial: f32,
+        ambient: f32,
+        equilibrium: f32,
+        time_constant: Duration,
+        heater: Arc<dyn Fn() -> bool + Send + Sync>,
+    ) -> Self {
+        Self {
+            ambient,
+            equilibrium,
+            time_constant,
+            heater_on: heater(),
+            heater,
+            temperature: initial,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    fn advance(&mut self, elapsed: Duration) -> f32 {
+        let dt = elapsed.saturating_sub(self.elapsed).as_secs_f32();
+        let target = if self.heater_on {
+            self.equilibrium
+        } else {
+            self.ambient
+        };
+        let tau = self.time_constant.as_secs_f32().max(f32::MIN_POSITIVE);
+        self.temperature = target + (self.temperature - target) * (-dt / tau).exp();
+        self.elapsed = self.elapsed.max(elapsed);
+        self.heater_on = (self.heater)();
+        self.temperature
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn thermal_model_follows_first_order_step_response() {
+        let heater = Arc::new(AtomicBool::new(true));
+        let mut model = ThermalModel::new(20.0, 20.0, 80.0, Duration::from_secs(10), {
+            let heater = heater.clone();
+            Arc::new(move || heater.load(Ordering::Relaxed))
+        });
+        // 時定数だけ経過すると、差が 1/e になる
+        let warm = 80.0 - 60.0 * (-1.0f32).exp();
+        assert!((model.advance(Duration::from_secs(10)) - warm).abs() < 1e-3);
+        // 同じ時刻を読み出し直しても変わらない
+        assert!((model.advance(Duration::from_secs(10)) - warm).abs() < 1e-3);
+
+        heater.store(false, Ordering::Relaxed);
+        // OFF にしたことは次に読み出した時点で反映される
+        assert!((model.advance(Duration::from_secs(10)) - warm).abs() < 1e-3);
+        let cooled = 20.0 + (warm - 20.0) * (-1.0f32).exp();
+        assert!((model.advance(Duration::from_secs(20)) - cooled).abs() < 1e-3);
+        let settled = model.advance(Duration::from_secs(1000));
+        assert!((settled - 20.0).abs() < 1e-3);
+    }
+}
@@ -1,79 +1,163 @@
-use std::{
-    collections::VecDeque,
-    ops::{Deref, DerefMut},
-};
+use std::collections::VecDeque;
 
 use tokio::sync::{Mutex, Notify};
 
-pub struct Buffer {
-    deque: Mutex<VecDeque<u8>>,
-    notify: Notify,
+/// 受信バッファが溢れ、バイト列が失われた
+pub struct Overrun;
+
+struct Inner {
+    deque: VecDeque<u8>,
+    capacity: usize,
+    // 溢れたことを次の読み出しで通知する
+    overrun: bool,
+    // 溢れたことを今回の読み出しで通知する
+    report_overrun: bool,
 }
 
-fn write_internal<D>(mut deque: D, data: &[u8])
-where
-    D: Deref<Target = VecDeque<u8>> + DerefMut,
-{
-    let data = if deque.capacity() < data.len() {
-        &data[data.len() - deque.capacity()..]
-    } else {
-        data
-    };
-    if deque.capacity() < deque.len() + data.len() {
-        let overflow = deque.len() + data.len() - deque.capacity();
-        deque.drain(..overflow);
+impl Inner {
+    fn new(capacity: usize) -> Self {
+        Self {
+            deque: VecDeque::with_capacity(capacity),
+            capacity,
+            overrun: false,
+            report_overrun: false,
+        }
     }
-    deque.extend(data);
-}
-fn read_internal<D>(mut deque: D, buf: &mut [u8]) -> usize
-where
-    D: Deref<Target = VecDeque<u8>> + DerefMut,
-{
-    let read = deque.len().min(buf.len());
-    for (s, d) in deque.drain(..).zip(buf.iter_mut()) {
-        *d = s;
+
+    fn free(&self) -> usize {
+        self.capacity - self.deque.len()
     }
-    read
+
+    fn write_lossy(&mut self, data: &[u8]) {
+        let len = self.free().min(data.len());
+        if len < data.len() {
+            self.overrun = true;
+        }
+        self.deque.extend(&data[..len]);
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> usize {
+        let read = self.deque.len().min(buf.len());
+        for (s, d) in self.deque.drain(..read).zip(buf.iter_mut()) {
+            *d = s;
+        }
+        read
+    }
+}
+
+/// 容量の決まったバイト列の FIFO
+///
+/// monazite-rt の直結 UART のリングバッファと同じく、容量を超えて書き込んだバイト列は古いものを残して失われる。
+pub struct Buffer {
+    inner: Mutex<Inner>,
+    notify: Notify,
 }
+
 impl Buffer {
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            deque: Mutex::new(VecDeque::with_capacity(capacity)),
+            inner: Mutex::new(Inner::new(capacity)),
             notify: Notify::new(),
         }
     }
 
     pub fn reinitialize(&self, capacity: usize) {
-        let mut deque = self.deque.blocking_lock();
-        *deque = VecDeque::with_capacity(capacity);
+        let mut inner = self.inner.blocking_lock();
+        *inner = Inner::new(capacity);
     }
 
-    pub fn blocking_write(&self, data: &[u8]) {
-        let deque = self.deque.blocking_lock();
-        write_internal(deque, data);
+    /// `data` 全体が収まる場合のみ書き込み、書き込んだかどうかを返す
+    pub fn blocking_try_write(&self, data: &[u8]) -> bool {
+        let mut inner = self.inner.blocking_lock();
+        if inner.free() < data.len() {
+            return false;
+        }
+        inner.deque.extend(data);
         self.notify.notify_waiters();
+        true
     }
 
+    /// `data` のうち収まる分を書き込み、収まらなかった分は失われたものとして次の読み出しで通知する
     pub async fn write(&self, data: &[u8]) {
-        let deque = self.deque.lock().await;
-        write_internal(deque, data);
+        self.inner.lock().await.write_lossy(data);
         self.notify.notify_waiters();
     }
 
-    pub fn nonblocking_read(&self, buf: &mut [u8]) -> usize {
-        let deque = self.deque.blocking_lock();
-        read_internal(deque, buf)
+    /// [`Buffer::write`] と同じく書き込む。非同期ランタイムの外から使う
+    pub fn blocking_write(&self, data: &[u8]) {
+        self.inner.blocking_lock().write_lossy(data);
+        self.notify.notify_waiters();
+    }
+
+    /// # Errors
+    /// 前回の読み出しまでにバッファが溢れていた場合は、一度だけ [`Overrun`] を返す。
+    pub fn nonblocking_read(&self, buf: &mut [u8]) -> Result<usize, Overrun> {
+        let mut inner = self.inner.blocking_lock();
+        // monazite-rt と同じく、溢れる前に受信していたバイト列を読み出した後でエラーを返す
+        if inner.report_overrun {
+            inner.report_overrun = false;
+            return Err(Overrun);
+        }
+        inner.report_overrun = inner.overrun;
+        inner.overrun = false;
+        Ok(inner.read(buf))
     }
 
     pub async fn read(&self, buf: &mut [u8]) -> usize {
         loop {
-            let deque = self.deque.lock().await;
-            if deque.is_empty() {
-                drop(deque);
+            let mut inner = self.inner.lock().await;
+            if inner.deque.is_empty() {
+                drop(inner);
                 self.notify.notified().await;
                 continue;
             }
-            return read_internal(deque, buf);
+            return inner.read(buf);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn try_write_rejects_data_that_does_not_fit() {
+        let buffer = Buffer::with_capacity(4);
+        assert!(buffer.blocking_try_write(b"abc"));
+        // 一部だけを書き込むことはしない
+        assert!(!buffer.blocking_try_write(b"de"));
+        assert!(buffer.blocking_try_write(b"d"));
+        let mut buf = [0; 8];
+        assert!(matches!(buffer.nonblocking_read(&mut buf), Ok(4)));
+        assert_eq!(&buf[..4], b"abcd");
+        assert!(matches!(buffer.nonblocking_read(&mut buf), Ok(0)));
+    }
+
+    #[test]
+    fn overrun_is_reported_once_after_buffered_data() {
+        let buffer = Buffer::with_capacity(4);
+        buffer.blocking_write(b"abc");
+        buffer.blocking_write(b"def");
+        let mut buf = [0; 8];
+        assert!(matches!(buffer.nonblocking_read(&mut buf), Ok(4)));
+        assert_eq!(&buf[..4], b"abcd");
+        assert!(matches!(buffer.nonblocking_read(&mut buf), Err(Overrun)));
+        assert!(matches!(buffer.nonblocking_read(&mut buf), Ok(0)));
+
+        buffer.blocking_write(b"g");
+        assert!(matches!(buffer.nonblocking_read(&mut buf), Ok(1)));
+        assert!(matches!(buffer.nonblocking_read(&mut buf), Ok(0)));
+    }
+
+    #[test]
+    fn reinitialize_discards_overrun() {
+        let buffer = Buffer::with_capacity(1);
+        buffer.blocking_write(b"ab");
+        buffer.reinitialize(2);
+        let mut buf = [0; 2];
+        assert!(matches!(buffer.nonblocking_read(&mut buf), Ok(0)));
+        assert!(buffer.blocking_try_write(b"ab"));
+        assert!(matches!(buffer.nonblocking_read(&mut buf), Ok(2)));
+        assert!(matches!(buffer.nonblocking_read(&mut buf), Ok(0)));
+    }
+}
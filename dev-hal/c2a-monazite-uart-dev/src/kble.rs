@@ -1,85 +1,93 @@
-use std::{net::SocketAddr, sync::Arc, thread};
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, thread};
 
 use anyhow::Result;
 use axum::{
-    extract::{ws::WebSocket, Path, State, WebSocketUpgrade},
+    extract::{ws::WebSocket, Path, Query, State, WebSocketUpgrade},
     http::StatusCode,
     response::Response,
     routing::get,
     Router,
 };
-use futures::{SinkExt, StreamExt};
 use kble_socket::from_axum;
-use tokio::sync::OwnedMutexGuard;
+use tokio::{runtime::Handle, sync::OwnedMutexGuard};
 
-use crate::{Mux, OuterChannel};
+use crate::{
+    transport::{self, Transport},
+    Mux, OuterChannel, PeerSettings,
+};
+
+type Transports = Arc<HashMap<u8, Transport>>;
 
 pub struct Server {
     mux: Arc<Mux>,
+    transports: Transports,
 }
 
 impl Server {
-    pub fn new(mux: Arc<Mux>) -> Self {
-        Self { mux }
+    /// `transports` に含まれないチャネルは kble で公開する
+    pub fn new(mux: Arc<Mux>, transports: HashMap<u8, Transport>) -> Self {
+        Self {
+            mux,
+            transports: Arc::new(transports),
+        }
     }
 
     pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        for (&ch, transport) in self.transports.iter() {
+            let mux = self.mux.clone();
+            let transport = transport.clone();
+            tokio::spawn(async move {
+                if let Err(e) = transport::serve(mux, ch, transport).await {
+                    eprintln!("UART channel {ch} transport has exited: {e}");
+                }
+            });
+        }
         let app = Router::new()
             .route("/channels/:ch", get(handle_channel))
-            .with_state(self.mux);
+            .with_state((self.mux, self.transports));
         axum::Server::bind(&addr)
             .serve(app.into_make_service())
             .await?;
         Ok(())
     }
 
-    pub fn serve_in_background(self, addr: SocketAddr) {
+    /// サーバーを動かしているランタイムのハンドルを返す
+    pub fn serve_in_background(self, addr: SocketAddr) -> Handle {
         let rt = tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()
             .unwrap();
+        let handle = rt.handle().clone();
         thread::spawn(move || {
             let fut = self.serve(addr);
             if let Err(e) = rt.block_on(fut) {
                 eprintln!("kble server has exited: {e}");
             }
         });
+        handle
     }
 }
 
 async fn handle_channel(
     upgrade: WebSocketUpgrade,
-    State(mux): State<Arc<Mux>>,
+    State((mux, transports)): State<(Arc<Mux>, Transports)>,
     Path(ch): Path<u8>,
+    Query(peer): Query<PeerSettings>,
 ) -> Result<Response, StatusCode> {
+    if !matches!(transports.get(&ch), None | Some(Transport::Kble)) {
+        return Err(StatusCode::NOT_FOUND);
+    }
     let Some(channel) = mux.try_get_outer(ch) else {
         return Err(StatusCode::CONFLICT);
     };
+    // 接続先の回線設定はクエリパラメータ（`?baudrate=9600&parity=even&stop_bits=2` など）で宣言する
+    channel.line.set_peer(peer);
     Ok(upgrade.on_upgrade(|ws| handle_ws(ws, channel)))
 }
 
 async fn handle_ws(ws: WebSocket, channel: OwnedMutexGuard<OuterChannel>) {
-    let (mut sink, mut stream) = from_axum(ws);
-    let tx = channel.tx.clone();
-    let rx = channel.rx.clone();
-    let tx_fut = async {
-        loop {
-            let mut buf = vec![0u8; 2048];
-            let len = tx.read(&mut buf).await;
-            buf.truncate(len);
-            sink.send(buf.into()).await?;
-        }
-        #[allow(unreachable_code)]
-        anyhow::Ok(())
-    };
-    let rx_fut = async {
-        loop {
-            let Some(chunk) = stream.next().await else {
-                break;
-            };
-            rx.write(&chunk?).await;
-        }
-        anyhow::Ok(())
-    };
-    futures::future::try_join(tx_fut, rx_fut).await.ok();
+    let (sink, stream) = from_axum(ws);
+    transport::pump(sink, stream, &channel).await;
+    // 切断したクライアントの回線設定が、次に接続するクライアントに引き継がれないようにする
+    channel.line.set_peer(PeerSettings::default());
 }
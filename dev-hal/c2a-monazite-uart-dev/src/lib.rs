@@ -1,23 +1,57 @@
 mod buffer;
 mod kble;
+mod line;
+mod pace;
+mod simulator;
+mod transport;
 
 use std::net::SocketAddr;
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Instant};
 
 use c2a_monazite_uart_bind::{ChannelId, Error as UartError, Uart as UartBind};
 use kble::Server;
-use tokio::sync::{Mutex, OwnedMutexGuard, RwLock};
+use tokio::{
+    runtime::Handle,
+    sync::{Mutex, OwnedMutexGuard, RwLock},
+};
+use traffic_capture::{Capture, Direction, Replay, Source};
 
-use buffer::Buffer;
+use buffer::{Buffer, Overrun};
+use line::Line;
+pub use line::{LineSettings, Parity, PeerSettings, StopBits};
+pub use simulator::{Clock, Simulator};
+pub use transport::Transport;
 
 pub struct OuterChannel {
     pub tx: Arc<Buffer>,
     pub rx: Arc<Buffer>,
+    pub line: Arc<Line>,
 }
 
 struct InnerChannel {
     tx: Arc<Buffer>,
     rx: Arc<Buffer>,
+    line: Arc<Line>,
+}
+
+/// チャネルごとのバッファの容量
+#[derive(Clone, Copy)]
+pub struct BufferSize {
+    pub tx: usize,
+    pub rx: usize,
+}
+
+impl Default for BufferSize {
+    /// monazite-rt の直結 UART のリングバッファに合わせたもの
+    fn default() -> Self {
+        Self { tx: 4096, rx: 8192 }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct ChannelConfig {
+    buffer_size: BufferSize,
+    paced: bool,
 }
 
 struct ChannelPair {
@@ -26,14 +60,16 @@ struct ChannelPair {
 }
 
 impl ChannelPair {
-    fn with_capacity(capacity: usize) -> Self {
-        let tx = Arc::new(Buffer::with_capacity(capacity));
-        let rx = Arc::new(Buffer::with_capacity(capacity));
+    fn with_capacity(capacity: BufferSize, line: LineSettings) -> Self {
+        let tx = Arc::new(Buffer::with_capacity(capacity.tx));
+        let rx = Arc::new(Buffer::with_capacity(capacity.rx));
+        let line = Arc::new(Line::new(line));
         let inner = InnerChannel {
             tx: tx.clone(),
             rx: rx.clone(),
+            line: line.clone(),
         };
-        let outer = OuterChannel { tx, rx };
+        let outer = OuterChannel { tx, rx, line };
 
         Self {
             inner,
@@ -41,25 +77,56 @@ impl ChannelPair {
         }
     }
 
-    fn reinitialize(&mut self, capacity: usize) {
-        self.inner.tx.reinitialize(capacity);
-        self.inner.rx.reinitialize(capacity);
+    fn reinitialize(&mut self, capacity: BufferSize, line: LineSettings) {
+        self.inner.tx.reinitialize(capacity.tx);
+        self.inner.rx.reinitialize(capacity.rx);
+        self.inner.line.configure(line);
     }
 }
 
 #[derive(Default)]
 pub struct Mux {
     channels: RwLock<HashMap<u8, ChannelPair>>,
+    configs: std::sync::Mutex<HashMap<u8, ChannelConfig>>,
+    capture: std::sync::RwLock<Option<Capture>>,
+    replay: std::sync::RwLock<Option<Replay>>,
 }
 
 impl Mux {
-    fn init_channel(&self, ch: u8) {
-        const BUFFER_SIZE: usize = 2048; // FIXME: make configurable
+    fn config(&self, ch: u8) -> ChannelConfig {
+        self.configs
+            .lock()
+            .unwrap()
+            .get(&ch)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn init_channel(&self, ch: u8, baudrate: u32) -> Result<(), UartError> {
+        if baudrate == 0 {
+            return Err(UartError::Baudrate);
+        }
+        let config = self.config(ch);
+        let line = LineSettings::new(baudrate);
         let mut channels = self.channels.blocking_write();
-        channels
+        let pair = channels
             .entry(ch)
-            .and_modify(|channel| channel.reinitialize(BUFFER_SIZE))
-            .or_insert_with(|| ChannelPair::with_capacity(BUFFER_SIZE));
+            .and_modify(|channel| channel.reinitialize(config.buffer_size, line))
+            .or_insert_with(|| ChannelPair::with_capacity(config.buffer_size, line));
+        pair.inner.line.set_paced(config.paced);
+        Ok(())
+    }
+
+    fn set_buffer_size(&self, ch: u8, buffer_size: BufferSize) {
+        let mut configs = self.configs.lock().unwrap();
+        configs.entry(ch).or_default().buffer_size = buffer_size;
+    }
+
+    fn set_paced(&self, ch: u8, paced: bool) {
+        self.configs.lock().unwrap().entry(ch).or_default().paced = paced;
+        if let Some(pair) = self.channels.blocking_read().get(&ch) {
+            pair.inner.line.set_paced(paced);
+        }
     }
 
     fn receive(&self, ch: u8, buf: &mut [u8]) -> Result<usize, UartError> {
@@ -67,7 +134,26 @@ impl Mux {
         let Some(pair) = channels.get(&ch) else {
             return Err(UartError::Channel);
         };
-        Ok(pair.inner.rx.nonblocking_read(buf))
+        if let Some(replay) = &*self.replay.read().unwrap() {
+            // 記録したバイト列は接続先が送信したものとして、回線設定の食い違いも再現する
+            while let Some(data) = replay.pop(Source::Uart, ch) {
+                pair.inner
+                    .rx
+                    .blocking_write(&pair.inner.line.receive(&data));
+            }
+        }
+        if let Some(err) = pair.inner.line.take_error() {
+            return Err(err);
+        }
+        let len = pair
+            .inner
+            .rx
+            .nonblocking_read(buf)
+            .map_err(|Overrun| UartError::FifoOverrun)?;
+        if len > 0 {
+            self.record(ch, Direction::Rx, &buf[..len]);
+        }
+        Ok(len)
     }
 
     fn send(&self, ch: u8, data: &[u8]) -> Result<(), UartError> {
@@ -75,8 +161,30 @@ impl Mux {
         let Some(pair) = channels.get(&ch) else {
             return Err(UartError::Channel);
         };
-        pair.inner.tx.blocking_write(data);
-        Ok(())
+        if pair
+            .inner
+            .tx
+            .blocking_try_write(&pair.inner.line.transmit(data))
+        {
+            self.record(ch, Direction::Tx, data);
+            Ok(())
+        } else {
+            Err(UartError::FifoFull)
+        }
+    }
+
+    fn set_capture(&self, capture: Capture) {
+        *self.capture.write().unwrap() = Some(capture);
+    }
+
+    fn set_replay(&self, replay: Replay) {
+        *self.replay.write().unwrap() = Some(replay);
+    }
+
+    fn record(&self, ch: u8, direction: Direction, data: &[u8]) {
+        if let Some(capture) = &*self.capture.read().unwrap() {
+            capture.record(Source::Uart, ch, direction, data);
+        }
     }
 
     pub fn try_get_outer(&self, ch: u8) -> Option<OwnedMutexGuard<OuterChannel>> {
@@ -88,27 +196,93 @@ impl Mux {
 
 pub struct Uart {
     mux: Arc<Mux>,
+    rt: Handle,
+    clock: std::sync::Mutex<Clock>,
 }
 
 impl Uart {
+    /// 全てのチャネルを `addr` で待ち受ける kble で公開する
     #[must_use]
     pub fn new(addr: SocketAddr) -> Self {
+        Self::with_transports(addr, [])
+    }
+
+    /// `transports` で指定したチャネルはその方法で、それ以外のチャネルは `addr` で待ち受ける kble で公開する
+    #[must_use]
+    pub fn with_transports(
+        addr: SocketAddr,
+        transports: impl IntoIterator<Item = (ChannelId, Transport)>,
+    ) -> Self {
         let mux = Arc::new(Mux::default());
-        let server = Server::new(mux.clone());
-        server.serve_in_background(addr);
-        Self { mux }
+        let transports = transports
+            .into_iter()
+            .map(|(ch, transport)| (u8::from(ch), transport))
+            .collect();
+        let server = Server::new(mux.clone(), transports);
+        let rt = server.serve_in_background(addr);
+        let start = Instant::now();
+        Self {
+            mux,
+            rt,
+            clock: std::sync::Mutex::new(Arc::new(move || start.elapsed())),
+        }
+    }
+
+    /// [`Simulator`] の時刻を数える方法を `clock` に変更する
+    ///
+    /// 既定では構築した時点からの実時間で数える。接続済みの [`Simulator`] には反映されないため、[`Uart::attach`] の前に変更する。
+    pub fn set_clock(&self, clock: Clock) {
+        *self.lock_clock() = clock;
+    }
+
+    fn lock_clock(&self) -> std::sync::MutexGuard<'_, Clock> {
+        self.clock.lock().unwrap()
+    }
+
+    /// チャネル `ch` に外部の接続先の代わりに `simulator` を接続する
+    ///
+    /// チャネルは kble などの他の方法では使えなくなる。
+    pub fn attach(&self, ch: ChannelId, simulator: impl Simulator) {
+        let mux = self.mux.clone();
+        let clock = self.lock_clock().clone();
+        self.rt
+            .spawn(simulator::run(mux, ch.into(), simulator, clock));
+    }
+
+    /// チャネル `ch` のバッファの容量を設定する
+    ///
+    /// 次に `initialize`/`reopen` した時点から反映される。
+    pub fn set_buffer_size(&self, ch: ChannelId, buffer_size: BufferSize) {
+        self.mux.set_buffer_size(ch.into(), buffer_size);
+    }
+
+    /// 全てのチャネルで C2A が送受信したバイト列を `capture` に記録する
+    pub fn set_capture(&self, capture: Capture) {
+        self.mux.set_capture(capture);
+    }
+
+    /// 各チャネルで接続先から受信したバイト列に加えて、`replay` に記録したバイト列を受信する
+    ///
+    /// 記録したバイト列は、C2A がそのチャネルから受信しようとした時点で受信バッファに書き込む。
+    pub fn set_replay(&self, replay: Replay) {
+        self.mux.set_replay(replay);
+    }
+
+    /// `paced` が `true` の場合、チャネル `ch` の送受信を `initialize`/`reopen` で指定したボーレートに合わせる
+    ///
+    /// 既定ではホストで可能な限り速く転送する。
+    pub fn set_paced(&self, ch: ChannelId, paced: bool) {
+        self.mux.set_paced(ch.into(), paced);
     }
 }
 
 impl UartBind for Uart {
-    fn initialize(&self, ch: ChannelId, _baudrate: u32) -> Result<(), UartError> {
-        self.mux.init_channel(ch.into());
-        Ok(())
+    fn initialize(&self, ch: ChannelId, baudrate: u32) -> Result<(), UartError> {
+        self.mux.init_channel(ch.into(), baudrate)
     }
 
-    fn reopen(&self, ch: ChannelId, _baudrate: u32) -> Result<(), UartError> {
-        self.mux.init_channel(ch.into());
-        Ok(())
+    fn reopen(&self, ch: ChannelId, baudrate: u32) -> Result<(), UartError> {
+        self.mux.init_channel(ch.into(), baudrate)
     }
 
     fn send(&self, ch: ChannelId, data: &[u8]) -> Result<(), UartError> {
@@ -119,3 +293,32 @@ impl UartBind for Uart {
         self.mux.receive(ch.into(), buffer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn send_reports_fifo_full() {
+        let mux = Mux::default();
+        mux.set_buffer_size(0, BufferSize { tx: 4, rx: 4 });
+        assert!(mux.init_channel(0, 9600).is_ok());
+        assert!(mux.send(0, b"abc").is_ok());
+        assert!(mux.send(0, b"de") == Err(UartError::FifoFull));
+        assert!(mux.send(0, b"d").is_ok());
+        assert!(mux.send(1, b"a") == Err(UartError::Channel));
+    }
+
+    #[test]
+    fn receive_reports_overrun_after_buffered_data() {
+        let mux = Mux::default();
+        mux.set_buffer_size(0, BufferSize { tx: 4, rx: 4 });
+        assert!(mux.init_channel(0, 9600).is_ok());
+        let outer = mux.try_get_outer(0).unwrap();
+        outer.rx.blocking_write(b"abcdef");
+        let mut buf = [0; 8];
+        assert!(mux.receive(0, &mut buf) == Ok(4));
+        assert!(mux.receive(0, &mut buf) == Err(UartError::FifoOverrun));
+        assert!(mux.receive(0, &mut buf) == Ok(0));
+    }
+}
@@ -0,0 +1,259 @@
+use std::{borrow::Cow, sync::Mutex, time::Duration};
+
+use c2a_monazite_uart_bind::Error as UartError;
+use serde::Deserialize;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Parity {
+    #[default]
+    None,
+    Odd,
+    Even,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default, Deserialize)]
+pub enum StopBits {
+    #[default]
+    #[serde(rename = "1")]
+    One,
+    #[serde(rename = "2")]
+    Two,
+}
+
+/// UART の回線設定（データ長は 8 bit 固定）
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LineSettings {
+    pub baudrate: u32,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+}
+
+impl LineSettings {
+    /// monazite の UART と同じく、パリティなし・ストップビット 1 とする
+    #[must_use]
+    pub fn new(baudrate: u32) -> Self {
+        Self {
+            baudrate,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+        }
+    }
+
+    /// スタートビット・データ・パリティ・ストップビットからなる 1 バイトの転送にかかる時間
+    #[must_use]
+    pub fn byte_time(&self) -> Duration {
+        let parity_bits = u64::from(self.parity != Parity::None);
+        let stop_bits = match self.stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        Duration::from_secs(1 + 8 + parity_bits + stop_bits) / self.baudrate.max(1)
+    }
+
+    /// 自身が受信側のときに、送信側の設定 `peer` との食い違いによって生じるエラー
+    fn mismatch(self, peer: Self) -> Option<UartError> {
+        if self.baudrate != peer.baudrate {
+            Some(UartError::Baudrate)
+        } else if self.parity != peer.parity {
+            Some(UartError::Parity)
+        } else if peer.stop_bits < self.stop_bits {
+            // 送信側のストップビットが受信側より長い分には問題ない
+            Some(UartError::StopBit)
+        } else {
+            None
+        }
+    }
+}
+
+/// kble の接続先が宣言する回線設定
+///
+/// 省略した項目は C2A 側の設定と同じものとみなす。
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize)]
+pub struct PeerSettings {
+    pub baudrate: Option<u32>,
+    pub parity: Option<Parity>,
+    pub stop_bits: Option<StopBits>,
+}
+
+impl PeerSettings {
+    fn resolve(&self, local: LineSettings) -> LineSettings {
+        LineSettings {
+            baudrate: self.baudrate.unwrap_or(local.baudrate),
+            parity: self.parity.unwrap_or(local.parity),
+            stop_bits: self.stop_bits.unwrap_or(local.stop_bits),
+        }
+    }
+}
+
+struct State {
+    local: LineSettings,
+    peer: PeerSettings,
+    paced: bool,
+    // C2A がまだ読み出していない受信時のエラー
+    error: Option<UartError>,
+}
+
+/// 回線設定の食い違いによって化けたバイト列
+fn garble(data: &[u8]) -> Vec<u8> {
+    data.iter().map(|byte| !byte.rotate_left(3)).collect()
+}
+
+/// チャネルの両端の回線設定
+///
+/// 両端の設定が食い違う場合、受信したバイト列に対応するエラーを記録し、送信したバイト列は化けさせる。
+pub struct Line {
+    state: Mutex<State>,
+}
+
+impl Line {
+    pub fn new(local: LineSettings) -> Self {
+        Self {
+            state: Mutex::new(State {
+                local,
+                peer: PeerSettings::default(),
+                paced: false,
+                error: None,
+            }),
+        }
+    }
+
+    /// C2A 側の設定を変更し、記録されたエラーを破棄する
+    pub fn configure(&self, local: LineSettings) {
+        let mut state = self.state.lock().unwrap();
+        state.local = local;
+        state.error = None;
+    }
+
+    pub fn set_peer(&self, peer: PeerSettings) {
+        self.state.lock().unwrap().peer = peer;
+    }
+
+    /// `paced` が `true` の場合、C2A 側のボーレートに合わせて転送する
+    pub fn set_paced(&self, paced: bool) {
+        self.state.lock().unwrap().paced = paced;
+    }
+
+    /// ペーシングする場合、1 バイトの転送にかかる時間
+    pub fn byte_time(&self) -> Option<Duration> {
+        let state = self.state.lock().unwrap();
+        state.paced.then(|| state.local.byte_time())
+    }
+
+    /// 記録されたエラーを取り出す
+    pub fn take_error(&self) -> Option<UartError> {
+        self.state.lock().unwrap().error.take()
+    }
+
+    /// C2A が送信したバイト列を、接続先が受信するバイト列に変換する
+    pub fn transmit<'a>(&self, data: &'a [u8]) -> Cow<'a, [u8]> {
+        let state = self.state.lock().unwrap();
+        let peer = state.peer.resolve(state.local);
+        if peer.mismatch(state.local).is_some() {
+            Cow::Owned(garble(data))
+        } else {
+            Cow::Borrowed(data)
+        }
+    }
+
+    /// 接続先が送信したバイト列を、C2A が受信するバイト列に変換する
+    ///
+    /// ボーレートが食い違う場合は化けたバイト列を、パリティやストップビットが食い違う場合は何も受信しない。
+    pub fn receive<'a>(&self, data: &'a [u8]) -> Cow<'a, [u8]> {
+        let mut state = self.state.lock().unwrap();
+        let peer = state.peer.resolve(state.local);
+        let Some(error) = state.local.mismatch(peer) else {
+            return Cow::Borrowed(data);
+        };
+        state.error = Some(error);
+        match error {
+            UartError::Baudrate => Cow::Owned(garble(data)),
+            _ => Cow::Borrowed(&[]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(peer: PeerSettings) -> Line {
+        let line = Line::new(LineSettings::new(115_200));
+        line.set_peer(peer);
+        line
+    }
+
+    #[test]
+    fn matching_settings_pass_through() {
+        // 省略した項目は C2A 側と同じとみなす
+        let line = line(PeerSettings {
+            baudrate: Some(115_200),
+            ..PeerSettings::default()
+        });
+        assert_eq!(*line.transmit(b"abc"), *b"abc");
+        assert_eq!(*line.receive(b"abc"), *b"abc");
+        assert!(line.take_error().is_none());
+    }
+
+    #[test]
+    fn baudrate_mismatch_garbles_data() {
+        let line = line(PeerSettings {
+            baudrate: Some(9600),
+            ..PeerSettings::default()
+        });
+        let garbled = garble(b"abc");
+        assert_ne!(garbled, b"abc");
+        assert_eq!(*line.transmit(b"abc"), *garbled);
+        assert_eq!(*line.receive(b"abc"), *garbled);
+        assert!(line.take_error() == Some(UartError::Baudrate));
+        assert!(line.take_error().is_none());
+    }
+
+    #[test]
+    fn parity_mismatch_drops_received_data() {
+        let line = line(PeerSettings {
+            parity: Some(Parity::Even),
+            ..PeerSettings::default()
+        });
+        assert_eq!(*line.transmit(b"abc"), *garble(b"abc"));
+        assert!(line.receive(b"abc").is_empty());
+        assert!(line.take_error() == Some(UartError::Parity));
+    }
+
+    #[test]
+    fn shorter_peer_stop_bits_are_rejected() {
+        let line = line(PeerSettings::default());
+        line.configure(LineSettings {
+            stop_bits: StopBits::Two,
+            ..LineSettings::new(115_200)
+        });
+        line.set_peer(PeerSettings {
+            stop_bits: Some(StopBits::One),
+            ..PeerSettings::default()
+        });
+        assert!(line.receive(b"abc").is_empty());
+        assert!(line.take_error() == Some(UartError::StopBit));
+        // 接続先が受信する場合は、送信側の C2A のストップビットの方が長いため問題ない
+        assert_eq!(*line.transmit(b"abc"), *b"abc");
+
+        line.configure(LineSettings::new(115_200));
+        line.set_peer(PeerSettings {
+            stop_bits: Some(StopBits::Two),
+            ..PeerSettings::default()
+        });
+        assert_eq!(*line.receive(b"abc"), *b"abc");
+        assert!(line.take_error().is_none());
+    }
+
+    #[test]
+    fn configure_discards_error() {
+        let line = line(PeerSettings {
+            baudrate: Some(9600),
+            ..PeerSettings::default()
+        });
+        line.receive(b"abc");
+        line.configure(LineSettings::new(9600));
+        assert!(line.take_error().is_none());
+        assert_eq!(*line.receive(b"abc"), *b"abc");
+    }
+}
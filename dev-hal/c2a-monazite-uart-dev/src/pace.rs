@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use tokio::time::{self, Instant};
+
+// ペーシングする場合に一度に転送するバイト列のおおよその時間
+const TICK: Duration = Duration::from_millis(1);
+
+/// ボーレートに合わせてバイト列の転送を待つ
+pub struct Pacer {
+    // 転送中のバイト列が送り終わる時刻
+    next: Instant,
+}
+
+impl Pacer {
+    pub fn new() -> Self {
+        Self {
+            next: Instant::now(),
+        }
+    }
+
+    /// 1 バイトの転送に `byte_time` かかる場合に、一度に転送するバイト数
+    pub fn chunk_len(byte_time: Duration) -> usize {
+        usize::try_from(TICK.as_nanos() / byte_time.as_nanos().max(1))
+            .unwrap_or(usize::MAX)
+            .max(1)
+    }
+
+    /// 直前のバイト列に続けて `len` バイトを送り終わるまで待つ
+    ///
+    /// 回線が空いていた場合は、呼び出した時刻から送り始めたものとする。
+    pub async fn wait(&mut self, byte_time: Duration, len: usize) {
+        let now = Instant::now();
+        // タイマーはミリ秒単位で遅れて起こすため、`TICK` 以内の遅れは回線が空いていたとはみなさない
+        if now > self.next + TICK {
+            self.next = now;
+        }
+        self.next += byte_time * u32::try_from(len).unwrap_or(u32::MAX);
+        time::sleep_until(self.next).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+
+    use super::*;
+    use crate::LineSettings;
+
+    fn block_on_paused(fut: impl Future<Output = ()>) {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .start_paused(true)
+            .build()
+            .unwrap();
+        rt.block_on(fut);
+    }
+
+    #[test]
+    fn releases_bytes_at_baudrate() {
+        block_on_paused(async {
+            for (baudrate, chunk_len) in [(9600, 1), (115_200, 11), (1_000_000, 100)] {
+                let byte_time = LineSettings::new(baudrate).byte_time();
+                assert_eq!(Pacer::chunk_len(byte_time), chunk_len, "{baudrate}");
+                let start = Instant::now();
+                let mut pacer = Pacer::new();
+                let mut sent = 0;
+                while start.elapsed() < Duration::from_secs(1) {
+                    pacer.wait(byte_time, chunk_len).await;
+                    sent += chunk_len;
+                }
+                // 1 バイトはスタートビットとストップビットを含めて 10 bit
+                let expected = usize::try_from(baudrate / 10).unwrap();
+                assert!(sent.abs_diff(expected) <= chunk_len, "{baudrate}: {sent}");
+            }
+        });
+    }
+
+    #[test]
+    fn idle_line_does_not_burst() {
+        block_on_paused(async {
+            let byte_time = LineSettings::new(9600).byte_time();
+            let mut pacer = Pacer::new();
+            pacer.wait(byte_time, 1).await;
+            time::sleep(Duration::from_millis(100)).await;
+            // 空いていた間の分をまとめて送らず、呼び出した時刻から送り始める
+            let start = Instant::now();
+            pacer.wait(byte_time, 2).await;
+            let elapsed = start.elapsed();
+            assert!(elapsed >= byte_time * 2 && elapsed < byte_time * 2 + TICK);
+        });
+    }
+}
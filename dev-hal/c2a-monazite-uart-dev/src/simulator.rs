@@ -0,0 +1,177 @@
+use std::{pin::pin, sync::Arc, time::Duration};
+
+use futures::future::{self, Either};
+use tokio::time::{self, Interval, MissedTickBehavior};
+
+use crate::{transport::acquire, Mux};
+
+/// UART のチャネルに接続するコンポーネント（センサやアクチュエータ）のモデル
+///
+/// C2A から見ると、kble などで接続した外部のプロセスと区別できない。
+/// `now` は [`Clock`] で数えたモデルの時刻で、接続した時点を 0 とする。
+pub trait Simulator: Send + 'static {
+    /// C2A が送信したバイト列 `data` を受け取る
+    ///
+    /// `reply` に書き込んだバイト列は C2A が受信する。
+    fn on_receive(&mut self, now: Duration, data: &[u8], reply: &mut Vec<u8>);
+
+    /// [`Clock`] が進むたびに呼ばれる
+    ///
+    /// 時刻が進んだかどうかは実時間で 10 ミリ秒ごとに確認するため、その間に進んだ分はまとめて渡す。
+    /// `reply` に書き込んだバイト列は C2A が受信する。既定では何もしない。
+    fn on_tick(&mut self, now: Duration, reply: &mut Vec<u8>) {
+        let _ = (now, reply);
+    }
+}
+
+/// [`Simulator`] の時刻を数える方法
+pub type Clock = Arc<dyn Fn() -> Duration + Send + Sync>;
+
+// 時刻が進んだかどうかを確認する実時間の間隔
+const TICK_PERIOD: Duration = Duration::from_millis(10);
+
+struct Ticker {
+    clock: Clock,
+    // 接続した時点の時刻
+    start: Duration,
+    // 最後に `on_tick` に渡した時刻
+    last: Duration,
+    interval: Interval,
+}
+
+impl Ticker {
+    fn new(clock: Clock) -> Self {
+        let start = clock();
+        let mut interval = time::interval(TICK_PERIOD);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Self {
+            clock,
+            start,
+            last: Duration::ZERO,
+            interval,
+        }
+    }
+
+    fn now(&self) -> Duration {
+        (self.clock)().saturating_sub(self.start)
+    }
+
+    /// 次に時刻が進むまで待ち、進んだ後の時刻を返す
+    async fn tick(&mut self) -> Duration {
+        loop {
+            self.interval.tick().await;
+            let now = self.now();
+            if now > self.last {
+                self.last = now;
+                return now;
+            }
+        }
+    }
+}
+
+/// チャネル `ch` が初期化されるのを待ち、`simulator` を接続する
+///
+/// チャネルが再初期化されても接続したままになる。
+pub async fn run(mux: Arc<Mux>, ch: u8, mut simulator: impl Simulator, clock: Clock) {
+    let channel = acquire(&mux, ch).await;
+    let mut ticker = Ticker::new(clock);
+    let mut buf = vec![0u8; 2048];
+    let mut reply = Vec::new();
+    loop {
+        let event = {
+            let read = pin!(channel.tx.read(&mut buf));
+            let tick = pin!(ticker.tick());
+            match future::select(read, tick).await {
+                Either::Left((len, _)) => Either::Left(len),
+                Either::Right((now, _)) => Either::Right(now),
+            }
+        };
+        match event {
+            Either::Left(len) => simulator.on_receive(ticker.now(), &buf[..len], &mut reply),
+            Either::Right(now) => simulator.on_tick(now, &mut reply),
+        }
+        if !reply.is_empty() {
+            channel.rx.write(&channel.line.receive(&reply)).await;
+            reply.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::Future,
+        sync::atomic::{AtomicU64, Ordering},
+    };
+
+    use super::*;
+
+    fn block_on_paused(fut: impl Future<Output = ()>) {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .start_paused(true)
+            .build()
+            .unwrap();
+        rt.block_on(fut);
+    }
+
+    fn clock() -> (Clock, Arc<AtomicU64>) {
+        let now_ms = Arc::new(AtomicU64::new(500));
+        let clock = {
+            let now_ms = now_ms.clone();
+            Arc::new(move || Duration::from_millis(now_ms.load(Ordering::Relaxed)))
+        };
+        (clock, now_ms)
+    }
+
+    #[test]
+    fn ticks_only_when_clock_advances() {
+        let (clock, now_ms) = clock();
+        block_on_paused(async {
+            let mut ticker = Ticker::new(clock);
+            now_ms.store(600, Ordering::Relaxed);
+            assert_eq!(ticker.tick().await, Duration::from_millis(100));
+            // 時刻が進まない間は待ち続ける
+            assert!(time::timeout(Duration::from_secs(1), ticker.tick())
+                .await
+                .is_err());
+            now_ms.store(800, Ordering::Relaxed);
+            assert_eq!(ticker.tick().await, Duration::from_millis(300));
+        });
+    }
+
+    struct Echo;
+
+    impl Simulator for Echo {
+        fn on_receive(&mut self, _now: Duration, data: &[u8], reply: &mut Vec<u8>) {
+            reply.extend_from_slice(data);
+        }
+
+        fn on_tick(&mut self, now: Duration, reply: &mut Vec<u8>) {
+            reply.extend_from_slice(&now.as_secs().to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn simulator_replies_to_c2a() {
+        let mux = Arc::new(Mux::default());
+        assert!(mux.init_channel(0, 9600).is_ok());
+        let (tx, rx) = {
+            let channels = mux.channels.blocking_read();
+            let inner = &channels[&0].inner;
+            (inner.tx.clone(), inner.rx.clone())
+        };
+        let (clock, now_ms) = clock();
+        block_on_paused(async {
+            tokio::spawn(run(mux.clone(), 0, Echo, clock));
+            let mut buf = [0; 16];
+            tx.write(b"ping").await;
+            let len = rx.read(&mut buf).await;
+            assert_eq!(&buf[..len], b"ping");
+
+            now_ms.store(3500, Ordering::Relaxed);
+            let len = rx.read(&mut buf).await;
+            assert_eq!(&buf[..len], 3u64.to_le_bytes());
+        });
+    }
+}
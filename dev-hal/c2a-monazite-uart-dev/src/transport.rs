@@ -0,0 +1,269 @@
+use std::{
+    fs::File,
+    net::SocketAddr,
+    os::fd::{AsRawFd, OwnedFd},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{Context as _, Result};
+use bytes::{Bytes, BytesMut};
+use futures::{future, Sink, SinkExt, Stream, StreamExt, TryStreamExt};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, TcpStream},
+    sync::OwnedMutexGuard,
+    time,
+};
+use tokio_util::codec::{BytesCodec, FramedRead, FramedWrite};
+
+use crate::{pace::Pacer, Mux, OuterChannel};
+
+// チャネルが初期化されるのを待つ間隔
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+// 接続に失敗した場合に再接続を試みるまでの間隔
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// チャネルを外部に公開する方法
+#[derive(Clone, Debug, Default)]
+pub enum Transport {
+    /// `/channels/:ch` で kble の WebSocket 接続を待ち受ける
+    #[default]
+    Kble,
+    /// 指定したアドレスで TCP 接続を待ち受け、バイト列をそのまま送受信する
+    TcpListen(SocketAddr),
+    /// 指定したアドレスに TCP で接続し、バイト列をそのまま送受信する
+    ///
+    /// 切断された場合は再接続を試みる。
+    TcpConnect(SocketAddr),
+    /// 擬似端末を開き、そのスレーブ側をシリアルデバイスとして公開する
+    ///
+    /// `link` を指定した場合は、スレーブ側のデバイスへのシンボリックリンクをそこに作成する。
+    Pty { link: Option<PathBuf> },
+}
+
+/// C2A が送信したバイト列を `sink` に、`stream` から受信したバイト列を C2A に転送する
+///
+/// いずれかの向きが終了するまで転送を続ける。
+pub async fn pump<Tx, Rx>(mut sink: Tx, mut stream: Rx, channel: &OuterChannel)
+where
+    Tx: Sink<Bytes, Error = anyhow::Error> + Unpin,
+    Rx: Stream<Item = Result<Bytes>> + Unpin,
+{
+    let tx = channel.tx.clone();
+    let rx = channel.rx.clone();
+    let tx_fut = async {
+        let mut pacer = Pacer::new();
+        loop {
+            let mut buf = vec![0u8; 2048];
+            let max_len = channel.line.byte_time().map_or(buf.len(), Pacer::chunk_len);
+            let len = tx.read(&mut buf[..max_len]).await;
+            buf.truncate(len);
+            // 読み出している間に設定が変わることがあるため、読み出した後の設定で送り終わるまで待つ
+            if let Some(byte_time) = channel.line.byte_time() {
+                pacer.wait(byte_time, len).await;
+            }
+            sink.send(buf.into()).await?;
+        }
+        #[allow(unreachable_code)]
+        anyhow::Ok(())
+    };
+    let rx_fut = async {
+        let mut pacer = Pacer::new();
+        loop {
+            let Some(chunk) = stream.next().await else {
+                break;
+            };
+            let data = channel.line.receive(&chunk?).into_owned();
+            let Some(byte_time) = channel.line.byte_time() else {
+                rx.write(&data).await;
+                continue;
+            };
+            for data in data.chunks(Pacer::chunk_len(byte_time)) {
+                pacer.wait(byte_time, data.len()).await;
+                rx.write(data).await;
+            }
+        }
+        anyhow::Ok(())
+    };
+    futures::pin_mut!(tx_fut, rx_fut);
+    if let (Err(e), _) = future::select(tx_fut, rx_fut).await.factor_first() {
+        eprintln!("UART transport has been closed: {e}");
+    }
+}
+
+/// バイト列をそのまま送受信する `reader` と `writer` を転送する
+async fn pump_io<R, W>(reader: R, writer: W, channel: OwnedMutexGuard<OuterChannel>)
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let sink = SinkExt::<Bytes>::sink_map_err(
+        FramedWrite::new(writer, BytesCodec::new()),
+        |e: std::io::Error| anyhow::Error::from(e),
+    );
+    let stream = FramedRead::new(reader, BytesCodec::new())
+        .map_ok(BytesMut::freeze)
+        .map_err(anyhow::Error::from);
+    pump(sink, stream, &channel).await;
+}
+
+/// チャネル `ch` が初期化され、他に使われていない状態になるまで待つ
+pub async fn acquire(mux: &Mux, ch: u8) -> OwnedMutexGuard<OuterChannel> {
+    loop {
+        if let Some(channel) = mux.try_get_outer(ch) {
+            return channel;
+        }
+        time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// チャネル `ch` を `transport` で公開し続ける
+///
+/// # Errors
+/// 待ち受けや擬似端末の作成に失敗した場合はエラーを返す。
+pub async fn serve(mux: Arc<Mux>, ch: u8, transport: Transport) -> Result<()> {
+    match transport {
+        // kble は HTTP サーバーのルーティングで扱う
+        Transport::Kble => Ok(()),
+        Transport::TcpListen(addr) => serve_tcp_listen(&mux, ch, addr).await,
+        Transport::TcpConnect(addr) => serve_tcp_connect(&mux, ch, addr).await,
+        Transport::Pty { link } => serve_pty(&mux, ch, link.as_deref()).await,
+    }
+}
+
+async fn serve_tcp_listen(mux: &Mux, ch: u8, addr: SocketAddr) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    accept_tcp(listener, mux, ch).await
+}
+
+/// 接続を受け付けるたびに、チャネル `ch` を転送するタスクを起動する
+///
+/// 転送している間も受け付けを続け、kble と同じく、チャネルが使えない場合はすぐに切断して接続を拒否する。
+async fn accept_tcp(listener: TcpListener, mux: &Mux, ch: u8) -> Result<()> {
+    loop {
+        let (incoming, peer) = listener.accept().await?;
+        let Some(channel) = mux.try_get_outer(ch) else {
+            eprintln!("UART channel {ch} is not available for {peer}");
+            continue;
+        };
+        let (reader, writer) = incoming.into_split();
+        tokio::spawn(pump_io(reader, writer, channel));
+    }
+}
+
+async fn serve_tcp_connect(mux: &Mux, ch: u8, addr: SocketAddr) -> Result<()> {
+    loop {
+        let channel = acquire(mux, ch).await;
+        match TcpStream::connect(addr).await {
+            Ok(stream) => {
+                let (reader, writer) = stream.into_split();
+                pump_io(reader, writer, channel).await;
+            }
+            Err(e) => {
+                drop(channel);
+                eprintln!("failed to connect UART channel {ch} to {addr}: {e}");
+                time::sleep(RECONNECT_INTERVAL).await;
+            }
+        }
+    }
+}
+
+async fn serve_pty(mux: &Mux, ch: u8, link: Option<&Path>) -> Result<()> {
+    let pty = nix::pty::openpty(None, None).context("failed to open a pseudo-terminal")?;
+    // 端末としての加工をせず、バイト列をそのまま通す
+    let mut termios = nix::sys::termios::tcgetattr(&pty.slave)?;
+    nix::sys::termios::cfmakeraw(&mut termios);
+    nix::sys::termios::tcsetattr(&pty.slave, nix::sys::termios::SetArg::TCSANOW, &termios)?;
+    let path = nix::unistd::ttyname(pty.slave.as_raw_fd())?;
+    if let Some(link) = link {
+        // 前回の実行で作成したリンクが残っていれば置き換える
+        let _ = std::fs::remove_file(link);
+        std::os::unix::fs::symlink(&path, link)?;
+    }
+    eprintln!("UART channel {ch} is available at {}", path.display());
+    // スレーブ側を開いたままにしておくことで、接続するツールがいない間もマスター側を読み書きできる
+    let _slave: OwnedFd = pty.slave;
+    let master = File::from(pty.master);
+    loop {
+        let channel = acquire(mux, ch).await;
+        // `tokio::fs::File` は読み込み中に書き込めないため、読み書きで別のファイルにする
+        let reader = tokio::fs::File::from_std(master.try_clone()?);
+        let writer = tokio::fs::File::from_std(master.try_clone()?);
+        pump_io(reader, writer, channel).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{ErrorKind, Read as _, Write as _},
+        thread,
+    };
+
+    use super::*;
+
+    const READ_TIMEOUT: Duration = Duration::from_millis(500);
+
+    fn connect(addr: SocketAddr) -> std::net::TcpStream {
+        let stream = std::net::TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(READ_TIMEOUT)).unwrap();
+        stream
+    }
+
+    /// `stream` が切断されていれば `true` を返す
+    fn is_closed(stream: &mut std::net::TcpStream) -> bool {
+        match stream.read(&mut [0; 8]) {
+            Ok(0) => true,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => false,
+            other => panic!("unexpected read: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn tcp_listen_refuses_second_client_while_first_is_connected() {
+        let mux = Arc::new(Mux::default());
+        assert!(mux.init_channel(0, 9600).is_ok());
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server_mux = mux.clone();
+        thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            rt.block_on(async {
+                let listener = TcpListener::from_std(listener)?;
+                accept_tcp(listener, &server_mux, 0).await
+            })
+        });
+
+        let mut first = connect(addr);
+        first.write_all(b"hi").unwrap();
+        let mut buf = [0; 8];
+        for _ in 0..50 {
+            if mux.receive(0, &mut buf) == Ok(2) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(&buf[..2], b"hi");
+
+        let mut second = connect(addr);
+        assert!(is_closed(&mut second));
+        // 最初のクライアントの転送は続く
+        assert!(mux.send(0, b"ok").is_ok());
+        first.read_exact(&mut buf[..2]).unwrap();
+        assert_eq!(&buf[..2], b"ok");
+
+        // 最初のクライアントが切断すると、次の接続を受け付ける
+        drop(first);
+        let accepted = (0..50).any(|_| {
+            thread::sleep(Duration::from_millis(10));
+            !is_closed(&mut connect(addr))
+        });
+        assert!(accepted);
+    }
+}
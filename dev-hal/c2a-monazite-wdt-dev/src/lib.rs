@@ -1,12 +1,116 @@
-use c2a_monazite_wdt_bind::Wdt as WdtBind;
+mod model;
 
-pub struct Wdt(core::marker::PhantomData<()>);
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use c2a_monazite_wdt_bind::{UnsupportedWindow, Wdt as WdtBind};
+use model::{Violation, Watchdog};
+use wwdg_window::Config as WwdgConfig;
+
+// 実機の PCLK3 の周波数
+const PCLK3: u32 = 100_000_000;
+
+/// リセットを要求したウォッチドッグ
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Source {
+    /// Independent Watchdog
+    Independent,
+    /// Window Watchdog
+    Window,
+}
+
+struct State {
+    watchdog: Watchdog,
+    // ウィンドウ外でクリアされた場合に記録する
+    violation: Option<Violation>,
+    // 実機の WWDG と同じく、一度ウィンドウ付きで有効化すると戻らない
+    windowed: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+    start: Instant,
+}
+
+impl Shared {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// 実時間で動作する Independent Watchdog と Window Watchdog をエミュレートする
+///
+/// 実機と同様に、一度有効化すると無効化できない。
+pub struct Wdt {
+    shared: Arc<Shared>,
+}
 
 impl Wdt {
-    #[allow(clippy::new_without_default)]
+    /// 有効化した後にタイムアウトまでに [`WdtBind::clear`] されなかった場合や、
+    /// ウィンドウが開く前にクリアされた場合、リセットを要求したウォッチドッグを渡して `on_expire` を呼び出す
+    ///
+    /// 実機と同じく、ウィンドウ付きで有効化した後は [`Source::Window`] によるリセットとして扱う。
+    /// SILS では dev の `Btmgr` の `reset` を呼び出し、対応するリセット要因を記録する。
     #[must_use]
-    pub fn new() -> Self {
-        Self(core::marker::PhantomData)
+    pub fn new(on_expire: impl FnOnce(Source) + Send + 'static) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                watchdog: Watchdog::default(),
+                violation: None,
+                windowed: false,
+            }),
+            changed: Condvar::new(),
+            start: Instant::now(),
+        });
+        let watched = shared.clone();
+        thread::spawn(move || {
+            let (violation, source) = watch(&watched);
+            match violation {
+                Violation::Expired => eprintln!("watchdog has expired"),
+                Violation::TooEarly => eprintln!("watchdog has been cleared too early"),
+            }
+            on_expire(source);
+        });
+        Self { shared }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut State, Duration)) {
+        f(&mut self.shared.state.lock().unwrap(), self.shared.now());
+        self.shared.changed.notify_all();
+    }
+}
+
+/// リセットを要求されるまで待つ
+fn watch(shared: &Shared) -> (Violation, Source) {
+    let mut state = shared.state.lock().unwrap();
+    let source = |state: &State| {
+        if state.windowed {
+            Source::Window
+        } else {
+            Source::Independent
+        }
+    };
+    loop {
+        if let Some(violation) = state.violation {
+            return (violation, source(&state));
+        }
+        let Some(deadline) = state.watchdog.deadline() else {
+            state = shared.changed.wait(state).unwrap();
+            continue;
+        };
+        let now = shared.now();
+        if now >= deadline {
+            return (Violation::Expired, source(&state));
+        }
+        state = shared
+            .changed
+            .wait_timeout(state, deadline - now)
+            .unwrap()
+            .0;
     }
 }
 
@@ -16,10 +120,67 @@ impl WdtBind for Wdt {
     }
 
     fn clear(&self) {
-        // no-op
+        self.update(|state, now| {
+            if let Err(violation) = state.watchdog.clear(now) {
+                state.violation.get_or_insert(violation);
+            }
+        });
     }
 
-    fn enable(&self, _time: u32) {
-        // no-op
+    /// `time` はタイムアウトまでの時間（ミリ秒）
+    ///
+    /// 実機と同じく、ウィンドウ付きで有効化した後は WWDG で数えられる時間に丸め、
+    /// 数えられないほど長い場合は最も長い時間でタイムアウトする。
+    fn enable(&self, time: u32) {
+        self.update(|state, now| {
+            let timeout = if state.windowed {
+                WwdgConfig::new(PCLK3, 0, time)
+                    .unwrap_or(WwdgConfig::LONGEST)
+                    .timeout(PCLK3)
+            } else {
+                Duration::from_millis(u64::from(time))
+            };
+            state.watchdog.enable(now, timeout, Duration::ZERO);
+        });
+    }
+
+    /// 実機の WWDG と同じウィンドウを設定し、設定できないウィンドウは拒否する
+    fn enable_windowed(&self, min_time: u32, time: u32) -> Result<(), UnsupportedWindow> {
+        let config = WwdgConfig::new(PCLK3, min_time, time).ok_or(UnsupportedWindow)?;
+        self.update(|state, now| {
+            state
+                .watchdog
+                .enable(now, config.timeout(PCLK3), config.min_time(PCLK3));
+            state.windowed = true;
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_windows_unsupported_by_wwdg() {
+        let wdt = Wdt::new(|_| {});
+        let deadline = |wdt: &Wdt| wdt.shared.state.lock().unwrap().watchdog.deadline();
+        assert!(matches!(
+            wdt.enable_windowed(1, 400),
+            Err(UnsupportedWindow)
+        ));
+        assert!(matches!(
+            wdt.enable_windowed(299, 300),
+            Err(UnsupportedWindow)
+        ));
+        // 拒否した場合は有効化しない
+        assert!(!wdt.shared.state.lock().unwrap().windowed);
+        assert_eq!(deadline(&wdt), None);
+
+        assert!(wdt.enable_windowed(100, 300).is_ok());
+        assert!(deadline(&wdt).unwrap() <= wdt.shared.now() + Duration::from_millis(300));
+        // WWDG で数えられる時間を超えるタイムアウトは、最も長い時間に丸める
+        wdt.enable(20_000);
+        assert!(deadline(&wdt).unwrap() <= wdt.shared.now() + Duration::from_millis(336));
     }
 }
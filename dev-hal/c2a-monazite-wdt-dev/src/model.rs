@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+/// ウォッチドッグがリセットを要求した理由
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Violation {
+    /// タイムアウトまでにクリアされなかった
+    Expired,
+    /// ウィンドウが開く前にクリアされた
+    TooEarly,
+}
+
+/// 時刻を与えて動かすウォッチドッグ
+///
+/// 時刻は任意の基準からの経過時間で、単調に増加するものとする。
+#[derive(Default)]
+pub struct Watchdog {
+    timeout: Duration,
+    // 0 の場合はいつクリアしてもよい
+    min_time: Duration,
+    // 有効化されていない場合は `None`
+    last_clear: Option<Duration>,
+}
+
+impl Watchdog {
+    /// 前回のクリアから `min_time` 以上 `timeout` 未満の間にクリアしなければならない状態で、`now` から動かす
+    pub fn enable(&mut self, now: Duration, timeout: Duration, min_time: Duration) {
+        self.timeout = timeout;
+        self.min_time = min_time;
+        self.last_clear = Some(now);
+    }
+
+    /// 有効化されていない場合は何もしない
+    ///
+    /// # Errors
+    /// クリアが早すぎるか遅すぎる場合は [`Violation`] を返す。
+    pub fn clear(&mut self, now: Duration) -> Result<(), Violation> {
+        let Some(last_clear) = self.last_clear else {
+            return Ok(());
+        };
+        let elapsed = now.saturating_sub(last_clear);
+        if elapsed >= self.timeout {
+            return Err(Violation::Expired);
+        }
+        if elapsed < self.min_time {
+            return Err(Violation::TooEarly);
+        }
+        self.last_clear = Some(now);
+        Ok(())
+    }
+
+    /// クリアされなければタイムアウトする時刻
+    pub fn deadline(&self) -> Option<Duration> {
+        self.last_clear.map(|last_clear| last_clear + self.timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn disabled_watchdog_never_fires() {
+        let mut wdt = Watchdog::default();
+        assert_eq!(wdt.deadline(), None);
+        assert_eq!(wdt.clear(ms(1_000_000)), Ok(()));
+    }
+
+    #[test]
+    fn independent_mode_accepts_any_clear_before_timeout() {
+        let mut wdt = Watchdog::default();
+        wdt.enable(ms(100), ms(1000), Duration::ZERO);
+        assert_eq!(wdt.deadline(), Some(ms(1100)));
+        assert_eq!(wdt.clear(ms(100)), Ok(()));
+        assert_eq!(wdt.clear(ms(1099)), Ok(()));
+        assert_eq!(wdt.deadline(), Some(ms(2099)));
+        assert_eq!(wdt.clear(ms(2099)), Err(Violation::Expired));
+    }
+
+    #[test]
+    fn window_mode_rejects_early_and_late_clear() {
+        let mut wdt = Watchdog::default();
+        wdt.enable(ms(0), ms(1000), ms(600));
+        assert_eq!(wdt.clear(ms(599)), Err(Violation::TooEarly));
+        assert_eq!(wdt.clear(ms(600)), Ok(()));
+        assert_eq!(wdt.clear(ms(1599)), Ok(()));
+        assert_eq!(wdt.clear(ms(2599)), Err(Violation::Expired));
+    }
+
+    #[test]
+    fn rejected_clear_does_not_restart_timer() {
+        let mut wdt = Watchdog::default();
+        wdt.enable(ms(0), ms(1000), ms(600));
+        assert_eq!(wdt.clear(ms(100)), Err(Violation::TooEarly));
+        assert_eq!(wdt.deadline(), Some(ms(1000)));
+    }
+
+    #[test]
+    fn enable_restarts_with_new_window() {
+        let mut wdt = Watchdog::default();
+        wdt.enable(ms(0), ms(1000), ms(600));
+        wdt.enable(ms(500), ms(2000), Duration::ZERO);
+        assert_eq!(wdt.deadline(), Some(ms(2500)));
+        assert_eq!(wdt.clear(ms(501)), Ok(()));
+    }
+}
@@ -49,6 +49,31 @@ typedef enum
   BTMGR_UNKNOWN = -1, //!< 不明
 } BTMGR_RESET_REASON;
 
+/**
+ * @enum BTMGR_BOOT_ACTION
+ * @brief ブートローダが行った動作を示す列挙型
+ */
+typedef enum
+{
+  BTMGR_BOOT_ACTION_BOOT = 0,     //!< アプリケーションを起動した
+  BTMGR_BOOT_ACTION_SWITCH = 1,   //!< アプリケーションの要求によりバンクを切り替えた
+  BTMGR_BOOT_ACTION_ROLLBACK = 2, //!< 起動確認がない、またはイメージの検証に失敗したためにバンクを切り替えた
+} BTMGR_BOOT_ACTION;
+
+/**
+ * @struct BTMGR_BootRecord
+ * @brief ブートローダの 1 回の実行の記録
+ */
+typedef struct
+{
+  uint32_t sequence;      //!< 通し番号
+  uint32_t reset_flag;    //!< STM のリセットステータスレジスタの生の値
+  int32_t  reset_reason;  //!< リセット要因（BTMGR_RESET_REASON）
+  uint32_t boot_bank;     //!< 起動した、または切り替え先のバンク（BTMGR_BOOT_BANK）
+  uint32_t action;        //!< ブートローダが行った動作（BTMGR_BOOT_ACTION）
+  uint32_t boot_attempts; //!< 起動確認のない連続した起動回数
+} BTMGR_BootRecord;
+
 /**
  * @brief 現在のコードがどのバンクから起動しているかを取得する
  * @return BTMGR_BOOT_BANK
@@ -83,4 +108,45 @@ BTMGR_RESET_REASON BTMGR_get_reset_reason(void);
  */
 void BTMGR_system_reset(void);
 
+/**
+ * @brief 起動確認のないまま連続して起動した回数を取得する
+ * @note  起動確認をした後の起動から数え直す
+ * @return 起動回数
+ */
+uint32_t BTMGR_get_boot_attempts(void);
+
+/**
+ * @brief ロールバックするまでに許す、起動確認のない連続した起動回数を取得する
+ * @return 起動回数の上限
+ */
+uint32_t BTMGR_get_max_boot_attempts(void);
+
+/**
+ * @brief ロールバックするまでに許す、起動確認のない連続した起動回数を設定する
+ * @note  起動確認のないまま max_boot_attempts 回起動した後のリセットで、裏のバンクに切り替わる
+ * @param max_boot_attempts 起動回数の上限（1 以上 255 以下）
+ */
+BTMGR_BOOT_ERR_CODE BTMGR_set_max_boot_attempts(uint32_t max_boot_attempts);
+
+/**
+ * @brief 現在の起動が成功したことをブートローダに伝える
+ * @note  起動回数を数え直し、次回の起動時にロールバックしないよう next_boot_bank を BTMGR_BANK_UNCHANGED に戻す
+ */
+void BTMGR_confirm_boot(void);
+
+/**
+ * @brief 読み出せるブート履歴の数を取得する
+ * @note  ブート履歴は Backup SRAM に保持され、パワーオンリセット・ブラウンアウトリセットで消去される
+ * @return ブート履歴の数
+ */
+uint32_t BTMGR_get_boot_history_len(void);
+
+/**
+ * @brief ブート履歴を取得する
+ * @param index 最新の記録から遡る数（0 が最新）
+ * @param[out] record 記録の書き込み先
+ * @return index が範囲外の場合は BTMGR_INVALID_PARAM_ERR
+ */
+BTMGR_BOOT_ERR_CODE BTMGR_get_boot_history(uint32_t index, BTMGR_BootRecord* record);
+
 #endif /* BTMGR_H_ */
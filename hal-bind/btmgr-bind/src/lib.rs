@@ -5,6 +5,9 @@ mod bind;
 use core::ffi::{c_int, c_uint};
 
 use atomic_once_cell::AtomicOnceCell;
+use bootmeta::MAX_BOOT_ATTEMPTS_LIMIT;
+
+pub use bind::BTMGR_BootRecord as BootRecord;
 
 #[allow(clippy::cast_possible_wrap)]
 #[derive(Clone, Copy, PartialEq)]
@@ -21,6 +24,13 @@ pub trait Btmgr: Sync {
     fn get_reset_flag(&self) -> u32;
     fn get_reset_reason(&self) -> i32;
     fn system_reset(&self) -> !;
+    fn get_boot_attempts(&self) -> u32;
+    fn get_max_boot_attempts(&self) -> u32;
+    fn set_max_boot_attempts(&self, max_boot_attempts: u32);
+    fn confirm_boot(&self);
+    fn get_boot_history_len(&self) -> usize;
+    /// 最新の記録から `index` 個遡ったブート履歴を返す
+    fn get_boot_history(&self, index: usize) -> Option<BootRecord>;
 }
 
 #[no_mangle]
@@ -71,3 +81,51 @@ pub extern "C" fn BTMGR_system_reset() {
     let btmgr = C2A_MONAZITE_BTMGR.get();
     btmgr.system_reset();
 }
+
+#[no_mangle]
+pub extern "C" fn BTMGR_get_boot_attempts() -> c_uint {
+    let btmgr = C2A_MONAZITE_BTMGR.get();
+    btmgr.get_boot_attempts() as c_uint
+}
+
+#[no_mangle]
+pub extern "C" fn BTMGR_get_max_boot_attempts() -> c_uint {
+    let btmgr = C2A_MONAZITE_BTMGR.get();
+    btmgr.get_max_boot_attempts() as c_uint
+}
+
+#[no_mangle]
+pub extern "C" fn BTMGR_set_max_boot_attempts(max_boot_attempts: c_uint) -> c_int {
+    let btmgr = C2A_MONAZITE_BTMGR.get();
+    if !(1..=MAX_BOOT_ATTEMPTS_LIMIT).contains(&max_boot_attempts) {
+        return bind::BTMGR_BOOT_ERR_CODE_BTMGR_INVALID_PARAM_ERR.0;
+    }
+    btmgr.set_max_boot_attempts(max_boot_attempts);
+    bind::BTMGR_BOOT_ERR_CODE_BTMGR_OK.0
+}
+
+#[no_mangle]
+pub extern "C" fn BTMGR_confirm_boot() {
+    let btmgr = C2A_MONAZITE_BTMGR.get();
+    btmgr.confirm_boot();
+}
+
+#[no_mangle]
+pub extern "C" fn BTMGR_get_boot_history_len() -> c_uint {
+    let btmgr = C2A_MONAZITE_BTMGR.get();
+    btmgr.get_boot_history_len() as c_uint
+}
+
+/// # Safety
+/// `record` は有効なメモリ領域を指している必要がある。
+#[no_mangle]
+pub unsafe extern "C" fn BTMGR_get_boot_history(index: c_uint, record: *mut BootRecord) -> c_int {
+    let btmgr = C2A_MONAZITE_BTMGR.get();
+    match btmgr.get_boot_history(index as usize) {
+        Some(boot_record) => {
+            *record = boot_record;
+            bind::BTMGR_BOOT_ERR_CODE_BTMGR_OK.0
+        }
+        None => bind::BTMGR_BOOT_ERR_CODE_BTMGR_INVALID_PARAM_ERR.0,
+    }
+}
@@ -8,6 +8,9 @@ use atomic_once_cell::AtomicOnceCell;
 
 pub use bind::CCSDS_RxStats as RxStats;
 
+/// C2A が想定する送信バッファのフレーム数
+pub const FIFO_SIZE: usize = bind::CCSDS_FIFO_SIZE as usize;
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 #[repr(i32)]
 pub enum Error {
@@ -45,6 +48,7 @@ pub trait Ccsds: Sync {
     /// 受信に失敗した場合は [`Error`] が返る。
     fn receive(&self, buffer: &mut [u8]) -> Result<usize, Error>;
 
+    /// 送信バッファに空きのあるフレーム数（最大 [`FIFO_SIZE`]）
     fn tx_buffer_free_frames(&self) -> usize;
 
     fn rx_stats(&self) -> RxStats;
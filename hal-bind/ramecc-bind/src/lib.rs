@@ -2,10 +2,58 @@
 
 mod bind;
 
-use core::ffi::c_uint;
+use core::ffi::{c_int, c_uint};
 
 use atomic_once_cell::AtomicOnceCell;
 
+pub use bind::{
+    RAMECC_ErrorCount as ErrorCount, RAMECC_Fault as Fault,
+    RAMECC_ScrubbingProgress as ScrubbingProgress,
+    RAMECC_MAX_SCRUBBING_WORDS as MAX_SCRUBBING_WORDS,
+};
+
+/// ECC で保護されたメモリ領域
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum Region {
+    AxiSram = bind::RAMECC_REGION_RAMECC_AXI_SRAM.0,
+    Sram1_0 = bind::RAMECC_REGION_RAMECC_SRAM1_0.0,
+    Sram1_1 = bind::RAMECC_REGION_RAMECC_SRAM1_1.0,
+    Sram2_0 = bind::RAMECC_REGION_RAMECC_SRAM2_0.0,
+    Sram2_1 = bind::RAMECC_REGION_RAMECC_SRAM2_1.0,
+    Sram3 = bind::RAMECC_REGION_RAMECC_SRAM3.0,
+    Sram4 = bind::RAMECC_REGION_RAMECC_SRAM4.0,
+    /// DTCM の偶数ワード
+    D0Tcm = bind::RAMECC_REGION_RAMECC_D0TCM.0,
+    /// DTCM の奇数ワード
+    D1Tcm = bind::RAMECC_REGION_RAMECC_D1TCM.0,
+    Itcm = bind::RAMECC_REGION_RAMECC_ITCM.0,
+    /// Backup SRAM
+    Bsram = bind::RAMECC_REGION_RAMECC_BSRAM.0,
+}
+
+impl Region {
+    pub const ALL: [Self; 11] = [
+        Self::AxiSram,
+        Self::Sram1_0,
+        Self::Sram1_1,
+        Self::Sram2_0,
+        Self::Sram2_1,
+        Self::Sram3,
+        Self::Sram4,
+        Self::D0Tcm,
+        Self::D1Tcm,
+        Self::Itcm,
+        Self::Bsram,
+    ];
+
+    /// `RAMECC_REGION` の値から変換する。範囲外の場合は `None` を返す
+    #[must_use]
+    pub fn from_raw(region: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| *r as u32 == region)
+    }
+}
+
 pub trait Ramecc: Sync {
     fn scrubbing_loops(&self) -> u32;
     fn single_errors(&self) -> u32;
@@ -16,8 +64,32 @@ pub trait Ramecc: Sync {
     fn dtcm_double_errors_on_byte_write(&self) -> u32;
     fn scrubbing_interval(&self) -> u32;
     fn set_scrubbing_interval(&self, scrubbing_interval_tick: u32);
+    /// `region` で検出された ECC エラーの数を返す
+    fn region_errors(&self, region: Region) -> ErrorCount;
+    /// 最後に検出された ECC エラーを返す。検出されていない場合は `None` を返す
+    fn last_fault(&self) -> Option<Fault>;
+    /// スクラブするメモリ領域（`RAMECC_SCRUB_TARGET` の論理和）を返す
+    fn scrubbing_targets(&self) -> u32;
+    /// `targets` は 0 でなく、`RAMECC_SCRUB_TARGET` 以外のビットを含まない
+    fn set_scrubbing_targets(&self, targets: u32);
+    fn scrubbing_words(&self) -> u32;
+    /// `words` は 1 以上 [`MAX_SCRUBBING_WORDS`] 以下
+    fn set_scrubbing_words(&self, words: u32);
+    fn scrubbing_progress(&self) -> ScrubbingProgress;
 }
 
+/// `RAMECC_SCRUB_TARGET` のすべてのビット
+pub const SCRUB_TARGET_ALL: u32 = bind::RAMECC_SCRUB_TARGET_RAMECC_SCRUB_AXI_SRAM.0
+    | bind::RAMECC_SCRUB_TARGET_RAMECC_SCRUB_SRAM1_0.0
+    | bind::RAMECC_SCRUB_TARGET_RAMECC_SCRUB_SRAM1_1.0
+    | bind::RAMECC_SCRUB_TARGET_RAMECC_SCRUB_SRAM2_0.0
+    | bind::RAMECC_SCRUB_TARGET_RAMECC_SCRUB_SRAM2_1.0
+    | bind::RAMECC_SCRUB_TARGET_RAMECC_SCRUB_SRAM3.0
+    | bind::RAMECC_SCRUB_TARGET_RAMECC_SCRUB_SRAM4.0
+    | bind::RAMECC_SCRUB_TARGET_RAMECC_SCRUB_DTCM.0
+    | bind::RAMECC_SCRUB_TARGET_RAMECC_SCRUB_ITCM.0
+    | bind::RAMECC_SCRUB_TARGET_RAMECC_SCRUB_BSRAM.0;
+
 #[no_mangle]
 pub static C2A_MONAZITE_RAMECC: AtomicOnceCell<&'static dyn Ramecc> = AtomicOnceCell::new();
 
@@ -74,3 +146,69 @@ pub extern "C" fn RAMECC_set_scrubbing_interval(scrubbing_interval_tick: c_uint)
     let ramecc = C2A_MONAZITE_RAMECC.get();
     ramecc.set_scrubbing_interval(scrubbing_interval_tick);
 }
+
+/// # Safety
+/// `count` は有効なメモリ領域を指している必要がある。
+#[no_mangle]
+pub unsafe extern "C" fn RAMECC_get_region_error(region: c_uint, count: *mut ErrorCount) -> c_int {
+    let ramecc = C2A_MONAZITE_RAMECC.get();
+    let Some(region) = Region::from_raw(region) else {
+        return bind::RAMECC_ERR_CODE_RAMECC_INVALID_PARAM_ERR.0;
+    };
+    *count = ramecc.region_errors(region);
+    bind::RAMECC_ERR_CODE_RAMECC_OK.0
+}
+
+/// # Safety
+/// `fault` は有効なメモリ領域を指している必要がある。
+#[no_mangle]
+pub unsafe extern "C" fn RAMECC_get_last_fault(fault: *mut Fault) -> c_int {
+    let ramecc = C2A_MONAZITE_RAMECC.get();
+    match ramecc.last_fault() {
+        Some(last_fault) => {
+            *fault = last_fault;
+            bind::RAMECC_ERR_CODE_RAMECC_OK.0
+        }
+        None => bind::RAMECC_ERR_CODE_RAMECC_NO_FAULT_ERR.0,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn RAMECC_get_scrubbing_targets() -> c_uint {
+    let ramecc = C2A_MONAZITE_RAMECC.get();
+    ramecc.scrubbing_targets()
+}
+
+#[no_mangle]
+pub extern "C" fn RAMECC_set_scrubbing_targets(targets: c_uint) -> c_int {
+    let ramecc = C2A_MONAZITE_RAMECC.get();
+    if targets == 0 || targets & !SCRUB_TARGET_ALL != 0 {
+        return bind::RAMECC_ERR_CODE_RAMECC_INVALID_PARAM_ERR.0;
+    }
+    ramecc.set_scrubbing_targets(targets);
+    bind::RAMECC_ERR_CODE_RAMECC_OK.0
+}
+
+#[no_mangle]
+pub extern "C" fn RAMECC_get_scrubbing_words() -> c_uint {
+    let ramecc = C2A_MONAZITE_RAMECC.get();
+    ramecc.scrubbing_words()
+}
+
+#[no_mangle]
+pub extern "C" fn RAMECC_set_scrubbing_words(words: c_uint) -> c_int {
+    let ramecc = C2A_MONAZITE_RAMECC.get();
+    if !(1..=MAX_SCRUBBING_WORDS).contains(&words) {
+        return bind::RAMECC_ERR_CODE_RAMECC_INVALID_PARAM_ERR.0;
+    }
+    ramecc.set_scrubbing_words(words);
+    bind::RAMECC_ERR_CODE_RAMECC_OK.0
+}
+
+/// # Safety
+/// `progress` は有効なメモリ領域を指している必要がある。
+#[no_mangle]
+pub unsafe extern "C" fn RAMECC_get_scrubbing_progress(progress: *mut ScrubbingProgress) {
+    let ramecc = C2A_MONAZITE_RAMECC.get();
+    *progress = ramecc.scrubbing_progress();
+}
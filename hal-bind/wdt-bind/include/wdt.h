@@ -0,0 +1,32 @@
+/**
+ * @file
+ * @brief WDT API 依存の関数を宣言
+ */
+#ifndef WDT_USER_H_
+#define WDT_USER_H_
+
+#include <src_core/hal/wdt.h>
+
+/**
+ * @enum  WDT_WINDOW_ERR_CODE
+ * @brief WDT_set_window の返り値
+ * @note  ウィンドウを使う場合は WDT_enable, WDT_set_timer も返す
+ */
+typedef enum
+{
+  WDT_WINDOW_UNSUPPORTED_ERR = -4,   //!< ハードウェアが設定できないウィンドウ
+  WDT_WINDOW_INVALID_PARAM_ERR = -3, //!< 不正なパラメータ
+  WDT_WINDOW_OK = 0,                 //!< 正常終了
+} WDT_WINDOW_ERR_CODE;
+
+/**
+ * @brief 前回のクリアから min_time ミリ秒経つまでのクリアもリセットの対象にする
+ * @note  ウィンドウは MCU に 1 つしかないウォッチドッグの設定であり、wdt_config ごとには持たない。
+ *        wdt_config は有効化されている場合に timer_setting で再設定するために使う
+ * @param wdt_config WDT_Config
+ * @param min_time ウィンドウが開くまでの時間（0 以上 timer_setting 未満。0 の場合はウィンドウを使わない）
+ * @return WDT_WINDOW_ERR_CODE
+ */
+int WDT_set_window(WDT_Config* wdt_config, int min_time);
+
+#endif
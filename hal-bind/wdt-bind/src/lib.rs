@@ -1,8 +1,11 @@
 #![no_std]
 
-use c2a_core::hal::wdt as bind;
+mod bind;
 
-use core::ffi::c_int;
+use core::{
+    ffi::c_int,
+    sync::atomic::{AtomicU32, Ordering},
+};
 
 use atomic_once_cell::AtomicOnceCell;
 
@@ -10,6 +13,32 @@ pub trait Wdt: Sync {
     fn initialize(&self);
     fn clear(&self);
     fn enable(&self, time: u32);
+    /// 前回のクリアから `min_time` ミリ秒以上 `time` ミリ秒未満の間にクリアしなければリセットするよう有効化する
+    ///
+    /// `min_time` は 1 以上 `time` 未満とする。
+    /// # Errors
+    /// ハードウェアが `min_time` と `time` のウィンドウを設定できない場合は [`UnsupportedWindow`] を返す。
+    fn enable_windowed(&self, min_time: u32, time: u32) -> Result<(), UnsupportedWindow>;
+}
+
+/// ハードウェアが設定できないウィンドウ
+#[derive(Debug)]
+pub struct UnsupportedWindow;
+
+// `WDT_set_window` で設定したウィンドウが開くまでの時間（ミリ秒）。0 の場合はウィンドウを使わない
+//
+// ウィンドウは MCU に 1 つしかないウォッチドッグの設定なので、`WDT_Config` ごとには持たない。
+static WINDOW_MIN_TIME: AtomicU32 = AtomicU32::new(0);
+
+fn start(wdt: &dyn Wdt, min_time: u32, time: u32) -> c_int {
+    if min_time == 0 {
+        wdt.enable(time);
+        return bind::WDT_WINDOW_ERR_CODE_WDT_WINDOW_OK.0;
+    }
+    match wdt.enable_windowed(min_time, time) {
+        Ok(()) => bind::WDT_WINDOW_ERR_CODE_WDT_WINDOW_OK.0,
+        Err(UnsupportedWindow) => bind::WDT_WINDOW_ERR_CODE_WDT_WINDOW_UNSUPPORTED_ERR.0,
+    }
 }
 
 #[no_mangle]
@@ -52,10 +81,14 @@ pub unsafe extern "C" fn WDT_enable(wdt_config: *mut bind::WDT_Config) -> c_int
     let wdt = C2A_MONAZITE_WDT.get();
     let config = &mut *wdt_config;
     #[allow(clippy::cast_sign_loss)]
-    wdt.enable(config.timer_setting as u32);
+    let ret = start(
+        *wdt,
+        WINDOW_MIN_TIME.load(Ordering::Relaxed),
+        config.timer_setting as u32,
+    );
     config.is_clear_enable = 1;
     config.is_wdt_enable = 1;
-    0
+    ret
 }
 
 /// # Safety
@@ -64,10 +97,45 @@ pub unsafe extern "C" fn WDT_enable(wdt_config: *mut bind::WDT_Config) -> c_int
 pub unsafe extern "C" fn WDT_set_timer(wdt_config: *mut bind::WDT_Config, time: c_int) -> c_int {
     let wdt = C2A_MONAZITE_WDT.get();
     let config = &mut *wdt_config;
+    let min_time = WINDOW_MIN_TIME.load(Ordering::Relaxed);
+    // ウィンドウを使う場合は、ウィンドウが開くより後にタイムアウトしなければならない
+    if min_time != 0 && u32::try_from(time).map_or(true, |time| time <= min_time) {
+        return bind::WDT_WINDOW_ERR_CODE_WDT_WINDOW_INVALID_PARAM_ERR.0;
+    }
     config.timer_setting = time;
     if config.is_wdt_enable == 1 {
         #[allow(clippy::cast_sign_loss)]
-        wdt.enable(config.timer_setting as u32);
+        return start(*wdt, min_time, config.timer_setting as u32);
     }
     0
 }
+
+/// 前回のクリアから `min_time` ミリ秒経つまでのクリアもリセットの対象にする（0 の場合は対象にしない）
+///
+/// ウィンドウは `wdt_config` によらず共通で、`wdt_config` は有効化されている場合の再設定にだけ使う。
+/// `min_time` が 0 以上 `timer_setting` 未満でない場合は何もせず、エラーを返す。
+///
+/// # Safety
+/// `wdt_config` は [`bind::WDT_Config`] を指す有効なポインタでなければならない
+#[no_mangle]
+pub unsafe extern "C" fn WDT_set_window(
+    wdt_config: *mut bind::WDT_Config,
+    min_time: c_int,
+) -> c_int {
+    let wdt = C2A_MONAZITE_WDT.get();
+    let config = &mut *wdt_config;
+    if !(0..config.timer_setting).contains(&min_time) {
+        return bind::WDT_WINDOW_ERR_CODE_WDT_WINDOW_INVALID_PARAM_ERR.0;
+    }
+    #[allow(clippy::cast_sign_loss)]
+    let min_time = min_time as u32;
+    if config.is_wdt_enable == 1 {
+        #[allow(clippy::cast_sign_loss)]
+        let ret = start(*wdt, min_time, config.timer_setting as u32);
+        if ret != bind::WDT_WINDOW_ERR_CODE_WDT_WINDOW_OK.0 {
+            return ret;
+        }
+    }
+    WINDOW_MIN_TIME.store(min_time, Ordering::Relaxed);
+    bind::WDT_WINDOW_ERR_CODE_WDT_WINDOW_OK.0
+}
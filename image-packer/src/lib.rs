@@ -0,0 +1,200 @@
+use anyhow::{anyhow, ensure, Context as _, Result};
+use bootmeta::{Digest, ImageTrailer, APP_REGION_SIZE};
+use object::{
+    elf::{FileHeader32, PT_LOAD},
+    read::elf::{FileHeader as _, ProgramHeader as _},
+    Endianness,
+};
+
+/// アプリケーション領域の先頭アドレス
+///
+/// 起動するバンクはいつもこのアドレスにマップされる。
+pub const APP_REGION_BASE: u32 = 0x0800_0000;
+
+/// トレーラに書き込むダイジェストのアルゴリズム
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Algorithm {
+    Crc32,
+    Sha256,
+}
+
+impl Algorithm {
+    fn digest(self, image: &[u8]) -> Digest {
+        match self {
+            Algorithm::Crc32 => Digest::crc32(image),
+            Algorithm::Sha256 => Digest::sha256(image),
+        }
+    }
+}
+
+/// ELF の書き込まれるセグメントをロードアドレスの位置に並べ、末尾に [`ImageTrailer`] を付けたアプリケーション領域全体を返す
+///
+/// セグメントの間とイメージの後ろは、消去されたフラッシュと同じ 0xFF で埋める。
+/// イメージの長さは、最も後ろにあるセグメントの末尾までとする。
+///
+/// # Errors
+/// 32 ビットの ELF として読めない場合や、セグメントがトレーラを除いたアプリケーション領域に収まらない場合はエラーを返す。
+pub fn pack(elf: &[u8], version: u32, algorithm: Algorithm) -> Result<Vec<u8>> {
+    let header = FileHeader32::<Endianness>::parse(elf).context("failed to parse ELF header")?;
+    let endian = header.endian()?;
+    let mut region = vec![0xFF; APP_REGION_SIZE];
+    let mut length = 0;
+    for segment in header
+        .program_headers(endian, elf)
+        .context("failed to parse program headers")?
+    {
+        if segment.p_type(endian) != PT_LOAD {
+            continue;
+        }
+        let data = segment
+            .data(endian, elf)
+            .map_err(|()| anyhow!("segment data is out of the file"))?;
+        if data.is_empty() {
+            // .bss など、フラッシュに書き込まれないセグメント
+            continue;
+        }
+        // .data のように RAM で実行されるセグメントも、フラッシュ上ではロードアドレスに置かれる
+        let address = segment.p_paddr(endian);
+        let start = address
+            .checked_sub(APP_REGION_BASE)
+            .map(|offset| offset as usize)
+            .filter(|start| start + data.len() <= ImageTrailer::OFFSET)
+            .with_context(|| {
+                format!(
+                    "segment at {address:#010x} ({} bytes) is outside the application region",
+                    data.len()
+                )
+            })?;
+        region[start..start + data.len()].copy_from_slice(data);
+        length = length.max(start + data.len());
+    }
+    ensure!(length > 0, "ELF has no segments to write");
+
+    let image = &region[..length];
+    let trailer = ImageTrailer::new(image, version, algorithm.digest(image));
+    region[ImageTrailer::OFFSET..].copy_from_slice(&trailer.to_bytes());
+    Ok(region)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bootmeta::verify_image;
+
+    struct Segment<'a> {
+        p_type: u32,
+        vaddr: u32,
+        paddr: u32,
+        data: &'a [u8],
+    }
+
+    fn load(vaddr: u32, paddr: u32, data: &[u8]) -> Segment<'_> {
+        Segment {
+            p_type: PT_LOAD,
+            vaddr,
+            paddr,
+            data,
+        }
+    }
+
+    /// プログラムヘッダだけを持つリトルエンディアンの 32 ビット ELF
+    fn elf(segments: &[Segment]) -> Vec<u8> {
+        const EHSIZE: u16 = 52;
+        const PHENTSIZE: u16 = 32;
+        let phnum = u16::try_from(segments.len()).unwrap();
+        let mut bytes = b"\x7fELF\x01\x01\x01".to_vec();
+        bytes.resize(16, 0);
+        for half in [2, 40] {
+            // ET_EXEC, EM_ARM
+            bytes.extend_from_slice(&u16::to_le_bytes(half));
+        }
+        for word in [1, APP_REGION_BASE, u32::from(EHSIZE), 0, 0] {
+            // e_version, e_entry, e_phoff, e_shoff, e_flags
+            bytes.extend_from_slice(&u32::to_le_bytes(word));
+        }
+        for half in [EHSIZE, PHENTSIZE, phnum, 0, 0, 0] {
+            bytes.extend_from_slice(&u16::to_le_bytes(half));
+        }
+        let mut offset = u32::from(EHSIZE) + u32::from(PHENTSIZE) * u32::from(phnum);
+        for segment in segments {
+            let size = u32::try_from(segment.data.len()).unwrap();
+            for word in [
+                segment.p_type,
+                offset,
+                segment.vaddr,
+                segment.paddr,
+                size,
+                size,
+                0,
+                4,
+            ] {
+                bytes.extend_from_slice(&u32::to_le_bytes(word));
+            }
+            offset += size;
+        }
+        for segment in segments {
+            bytes.extend_from_slice(segment.data);
+        }
+        bytes
+    }
+
+    #[test]
+    fn packed_image_passes_verification() {
+        let text = [0x11; 0x100];
+        let data = [0x22; 0x10];
+        let elf = elf(&[
+            load(APP_REGION_BASE, APP_REGION_BASE, &text),
+            // .data は RAM で実行されるが、.text の後ろに置かれる
+            load(0x2400_0000, APP_REGION_BASE + 0x200, &data),
+            // .bss
+            load(0x2400_0010, 0x2400_0010, &[]),
+        ]);
+        for algorithm in [Algorithm::Crc32, Algorithm::Sha256] {
+            let region = pack(&elf, 7, algorithm).unwrap();
+            let trailer = verify_image(&region).unwrap();
+            assert_eq!(trailer.version, 7);
+            assert_eq!(trailer.length, 0x210);
+            assert_eq!(region[..0x100], text);
+            assert!(region[0x100..0x200].iter().all(|&b| b == 0xFF));
+            assert_eq!(region[0x200..0x210], data);
+            assert!(region[0x210..ImageTrailer::OFFSET]
+                .iter()
+                .all(|&b| b == 0xFF));
+        }
+    }
+
+    #[test]
+    fn non_load_segments_are_ignored() {
+        const PT_NOTE: u32 = 4;
+        let elf = elf(&[
+            load(APP_REGION_BASE, APP_REGION_BASE, &[0x11; 4]),
+            Segment {
+                p_type: PT_NOTE,
+                vaddr: 0,
+                paddr: 0,
+                data: &[0x22; 4],
+            },
+        ]);
+        let region = pack(&elf, 0, Algorithm::Crc32).unwrap();
+        assert_eq!(verify_image(&region).unwrap().length, 4);
+    }
+
+    #[test]
+    fn rejects_segments_outside_region() {
+        #[allow(clippy::cast_possible_truncation)]
+        let trailer = APP_REGION_BASE + ImageTrailer::OFFSET as u32;
+        for paddr in [0x2400_0000, APP_REGION_BASE - 4, trailer - 2] {
+            let elf = elf(&[load(paddr, paddr, &[0; 4])]);
+            assert!(pack(&elf, 0, Algorithm::Crc32).is_err(), "{paddr:#x}");
+        }
+        // トレーラの直前までは書き込める
+        let elf = elf(&[load(trailer - 4, trailer - 4, &[0; 4])]);
+        assert!(pack(&elf, 0, Algorithm::Crc32).is_ok());
+    }
+
+    #[test]
+    fn rejects_empty_and_invalid_elf() {
+        assert!(pack(&elf(&[]), 0, Algorithm::Crc32).is_err());
+        assert!(pack(b"not an elf", 0, Algorithm::Crc32).is_err());
+    }
+}
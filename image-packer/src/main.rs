@@ -0,0 +1,34 @@
+use std::{env, fs, path::PathBuf};
+
+use anyhow::{Context as _, Result};
+use image_packer::{pack, Algorithm};
+
+const USAGE: &str = "usage: image-packer [--crc32] [--image-version <VERSION>] <ELF> <OUTPUT>";
+
+fn main() -> Result<()> {
+    let mut algorithm = Algorithm::Sha256;
+    let mut version = 0;
+    let mut paths = Vec::new();
+    let mut args = env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--crc32") => algorithm = Algorithm::Crc32,
+            Some("--image-version") => {
+                version = args
+                    .next()
+                    .and_then(|v| v.to_str()?.parse().ok())
+                    .context(USAGE)?;
+            }
+            Some("-h" | "--help") => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    let [elf, output] = <[PathBuf; 2]>::try_from(paths).ok().context(USAGE)?;
+
+    let elf = fs::read(&elf).with_context(|| format!("failed to read {}", elf.display()))?;
+    let region = pack(&elf, version, algorithm)?;
+    fs::write(&output, region).with_context(|| format!("failed to write {}", output.display()))
+}
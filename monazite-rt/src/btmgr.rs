@@ -1,11 +1,15 @@
-use bootmeta::{BootBank as MetaBootBank, BootMeta, FlashOptionBytes};
-use c2a_monazite_btmgr_bind::{BootBank, Btmgr as BtmgrBind};
+use bootmeta::{
+    decode_reset_flag, BootAction, BootBank as MetaBootBank, BootHistory, BootMeta,
+    BootRecord as MetaBootRecord, FlashOptionBytes,
+};
+use c2a_monazite_btmgr_bind::{BootBank, BootRecord, Btmgr as BtmgrBind};
 use cortex_m::interrupt::Mutex;
 use stm32h7xx_hal::rcc::ResetReason;
 
 pub struct Btmgr {
     bootmeta: Mutex<BootMeta>,
     flash_option_bytes: FlashOptionBytes,
+    history: Mutex<BootHistory<&'static mut [u32]>>,
 }
 
 fn to_meta_boot_bank(boot_bank: BootBank) -> MetaBootBank {
@@ -39,11 +43,32 @@ fn from_reset_reason(reset_reason: ResetReason) -> i32 {
     }
 }
 
+fn from_meta_boot_record(record: MetaBootRecord) -> BootRecord {
+    BootRecord {
+        sequence: record.sequence,
+        reset_flag: record.reset_flag,
+        reset_reason: decode_reset_flag(record.reset_flag)
+            .map_or(-1, |reason| from_reset_reason(reason.into())),
+        boot_bank: from_meta_boot_bank(record.bank) as u32,
+        action: match record.action {
+            BootAction::Boot => 0,
+            BootAction::Switch => 1,
+            BootAction::Rollback => 2,
+        },
+        boot_attempts: u32::from(record.boot_attempts),
+    }
+}
+
 impl Btmgr {
-    pub fn new(bootmeta: BootMeta, flash_option_bytes: FlashOptionBytes) -> Self {
+    pub fn new(
+        bootmeta: BootMeta,
+        flash_option_bytes: FlashOptionBytes,
+        history: BootHistory<&'static mut [u32]>,
+    ) -> Self {
         Self {
             bootmeta: Mutex::new(bootmeta),
             flash_option_bytes,
+            history: Mutex::new(history),
         }
     }
 }
@@ -94,4 +119,42 @@ impl BtmgrBind for Btmgr {
     fn system_reset(&self) -> ! {
         cortex_m::peripheral::SCB::sys_reset();
     }
+
+    fn get_boot_attempts(&self) -> u32 {
+        cortex_m::interrupt::free(|cs| {
+            let bootmeta = self.bootmeta.borrow(cs);
+            bootmeta.boot_attempts()
+        })
+    }
+
+    fn get_max_boot_attempts(&self) -> u32 {
+        cortex_m::interrupt::free(|cs| {
+            let bootmeta = self.bootmeta.borrow(cs);
+            bootmeta.max_boot_attempts()
+        })
+    }
+
+    fn set_max_boot_attempts(&self, max_boot_attempts: u32) {
+        cortex_m::interrupt::free(|cs| {
+            let bootmeta = self.bootmeta.borrow(cs);
+            bootmeta.set_max_boot_attempts(max_boot_attempts);
+        });
+    }
+
+    fn confirm_boot(&self) {
+        cortex_m::interrupt::free(|cs| {
+            let bootmeta = self.bootmeta.borrow(cs);
+            bootmeta.set_next_boot_bank(None);
+            bootmeta.set_boot_confirmed(true);
+        });
+    }
+
+    fn get_boot_history_len(&self) -> usize {
+        cortex_m::interrupt::free(|cs| self.history.borrow(cs).len())
+    }
+
+    fn get_boot_history(&self, index: usize) -> Option<BootRecord> {
+        cortex_m::interrupt::free(|cs| self.history.borrow(cs).get(index))
+            .map(from_meta_boot_record)
+    }
 }
@@ -1,45 +1,220 @@
-use c2a_monazite_ccsds_bind::{Ccsds as CcsdsBind, Error, RxStats};
+use core::cell::RefCell;
 
-pub struct Ccsds;
+use ccsds_frame::{
+    AosError, AosFrameBuilder, Deframer, RxStats as FrameRxStats, SyncMode, AOS_MAX_FRAME_LEN, ASM,
+};
+use cortex_m::interrupt::Mutex;
+
+use c2a_monazite_ccsds_bind::{Ccsds as CcsdsBind, Error, RxStats, FIFO_SIZE};
+
+use crate::uart::direct;
+
+// 一度に UART の受信バッファから読み出すバイト数
+const RX_CHUNK_SIZE: usize = 64;
+
+/// CCSDS の送受信に用いる直結 UART のチャネル
+///
+/// 送信するフレームには ASM を前置し、受信したバイト列からは `sync_mode` でフレームを切り出す。
+pub struct UartLink {
+    pub tx: &'static direct::Tx,
+    pub rx: &'static direct::Rx,
+    pub sync_mode: SyncMode,
+}
+
+struct Inner {
+    builder: AosFrameBuilder,
+    tx_frame: [u8; ASM.len() + AOS_MAX_FRAME_LEN],
+    // 最後に送信したフレームの ASM を含む長さ
+    last_tx_len: usize,
+}
+
+/// UART から受信したバイト列からフレームを切り出す
+struct Receiver {
+    deframer: Deframer,
+    rx_chunk: [u8; RX_CHUNK_SIZE],
+    rx_pos: usize,
+    rx_len: usize,
+}
+
+impl Receiver {
+    fn reset(&mut self) {
+        self.deframer.reset();
+        self.rx_pos = 0;
+        self.rx_len = 0;
+    }
+
+    /// `rx` から読み出したバイト列から切り出したフレームを `buffer` に書き込み、その長さを返す
+    ///
+    /// 読み出せるバイト列がなくなった場合は 0 を返す。
+    fn receive(&mut self, rx: &direct::Rx, buffer: &mut [u8]) -> usize {
+        loop {
+            if self.rx_pos == self.rx_len {
+                self.rx_pos = 0;
+                self.rx_len = 0;
+                // 割り込みを禁止するのは、受信バッファから RX_CHUNK_SIZE バイトまでを読み出す間だけにする
+                match rx.read(&mut self.rx_chunk) {
+                    Ok(0) => return 0,
+                    Ok(read_len) => self.rx_len = read_len,
+                    Err(direct::RxError::FifoOver) => {
+                        // 読みかけのフレームの続きが失われている
+                        self.deframer.stats_mut().count_skipped();
+                        self.deframer.reset();
+                        continue;
+                    }
+                }
+            }
+            let byte = self.rx_chunk[self.rx_pos];
+            self.rx_pos += 1;
+            let Some(frame) = self.deframer.push(byte) else {
+                continue;
+            };
+            let len = frame.len();
+            if len <= buffer.len() {
+                buffer[..len].copy_from_slice(frame);
+                return len;
+            }
+            self.deframer.stats_mut().count_skipped();
+        }
+    }
+}
+
+pub struct Ccsds {
+    inner: Mutex<RefCell<Inner>>,
+    // BCH の復号に時間がかかるため、受信中は取り出して割り込みを禁止せずに操作する
+    receiver: Mutex<RefCell<Option<Receiver>>>,
+    link: Option<UartLink>,
+}
 
 impl Ccsds {
-    pub fn new() -> Self {
-        Self
+    /// `link` が `None` の場合、送信するフレームは破棄され、何も受信しない
+    pub fn new(link: Option<UartLink>) -> Self {
+        let sync_mode = link.as_ref().map_or(SyncMode::Asm, |link| link.sync_mode);
+        Self {
+            inner: Mutex::new(RefCell::new(Inner {
+                builder: AosFrameBuilder::new(0, false),
+                tx_frame: [0; ASM.len() + AOS_MAX_FRAME_LEN],
+                last_tx_len: ASM.len() + AOS_MAX_FRAME_LEN,
+            })),
+            receiver: Mutex::new(RefCell::new(Some(Receiver {
+                deframer: Deframer::new(sync_mode),
+                rx_chunk: [0; RX_CHUNK_SIZE],
+                rx_pos: 0,
+                rx_len: 0,
+            }))),
+            link,
+        }
+    }
+
+    fn reset(&self) {
+        cortex_m::interrupt::free(|cs| {
+            if let Some(receiver) = self.receiver.borrow(cs).borrow_mut().as_mut() {
+                receiver.reset();
+            }
+        });
+        if let Some(link) = &self.link {
+            direct::reset(link.tx, link.rx);
+        }
+    }
+}
+
+fn from_aos_error(err: AosError) -> Error {
+    match err {
+        AosError::TooShort | AosError::TooLong(_) => Error::TxSizeError,
+        AosError::InvalidVersion(_) => Error::TxInvalid,
+    }
+}
+
+fn from_frame_rx_stats(stats: FrameRxStats) -> RxStats {
+    RxStats {
+        corrupted_frames: stats.corrupted_frames,
+        overflowed_frames: stats.overflowed_frames,
+        found_starts: stats.found_starts,
+        skipped_frames: stats.skipped_frames,
+        last_frame_corrected_errors: stats.last_frame_corrected_errors,
     }
 }
 
 impl CcsdsBind for Ccsds {
     fn initialize(&self) -> Result<(), Error> {
+        self.reset();
         Ok(())
     }
 
     fn reopen(&self) -> Result<(), Error> {
+        self.reset();
         Ok(())
     }
 
-    fn send(&self, _data: &[u8]) -> Result<(), Error> {
-        Ok(())
+    fn send(&self, data: &[u8]) -> Result<(), Error> {
+        cortex_m::interrupt::free(|cs| {
+            let mut inner = self.inner.borrow(cs).borrow_mut();
+            let Inner {
+                builder,
+                tx_frame,
+                last_tx_len,
+                ..
+            } = &mut *inner;
+            let (asm, frame) = tx_frame.split_at_mut(ASM.len());
+            let len = ASM.len() + builder.rebuild(data, frame).map_err(from_aos_error)?;
+            let Some(link) = &self.link else {
+                // 送信機が接続されていないため、組み立てたフレームは破棄する
+                defmt::trace!("CCSDS_tx: drop {} bytes frame", len);
+                return Ok(());
+            };
+            asm.copy_from_slice(&ASM);
+            *last_tx_len = len;
+            if link.tx.write(&tx_frame[..len]) {
+                Ok(())
+            } else {
+                Err(Error::TxNoBuffer)
+            }
+        })
     }
 
-    fn receive(&self, _buffer: &mut [u8]) -> Result<usize, Error> {
-        Ok(0)
+    fn receive(&self, buffer: &mut [u8]) -> Result<usize, Error> {
+        let Some(link) = &self.link else {
+            return Ok(0);
+        };
+        // C2A のタスクからのみ呼ばれるため、受信中に他から取り出されることはない
+        let Some(mut receiver) =
+            cortex_m::interrupt::free(|cs| self.receiver.borrow(cs).borrow_mut().take())
+        else {
+            return Ok(0);
+        };
+        let len = receiver.receive(link.rx, buffer);
+        cortex_m::interrupt::free(|cs| {
+            self.receiver.borrow(cs).replace(Some(receiver));
+        });
+        Ok(len)
     }
 
     fn tx_buffer_free_frames(&self) -> usize {
-        1
+        let Some(link) = &self.link else {
+            return 1;
+        };
+        let last_tx_len =
+            cortex_m::interrupt::free(|cs| self.inner.borrow(cs).borrow().last_tx_len);
+        (link.tx.available() / last_tx_len).min(FIFO_SIZE)
     }
 
     fn rx_stats(&self) -> RxStats {
-        RxStats {
-            corrupted_frames: 0,
-            overflowed_frames: 0,
-            found_starts: 0,
-            skipped_frames: 0,
-            last_frame_corrected_errors: 0,
-        }
+        let stats = cortex_m::interrupt::free(|cs| {
+            self.receiver
+                .borrow(cs)
+                .borrow()
+                .as_ref()
+                .map_or_else(FrameRxStats::default, |receiver| receiver.deframer.stats())
+        });
+        from_frame_rx_stats(stats)
     }
 
-    fn set_aos_scid(&self, _aos_scid: u8) {
-        // noop
+    fn set_aos_scid(&self, aos_scid: u8) {
+        cortex_m::interrupt::free(|cs| {
+            self.inner
+                .borrow(cs)
+                .borrow_mut()
+                .builder
+                .set_scid(aos_scid);
+        });
     }
 }
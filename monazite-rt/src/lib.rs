@@ -49,6 +49,7 @@ use hal::rcc::rec::AdcClkSel;
 use stm32h7xx_hal as hal;
 
 use bootmeta::{BootMeta, FlashOptionBytes};
+use ccsds_frame::SyncMode;
 use uart::DirectUartArray;
 
 #[rtic::app(device = stm32h7xx_hal::stm32, peripherals = true)]
@@ -91,7 +92,7 @@ mod app {
 
         let mut res = resources::Resources::new(dp);
 
-        init_wdt(res.wdt);
+        init_wdt(res.wdt, &res.shared);
 
         let ram_scrubber = init_ramecc(res.ramecc);
 
@@ -101,10 +102,10 @@ mod app {
 
         init_gpio(res.gpio);
 
-        init_ccsds(&mut res.shared);
-
         let direct_uarts = init_uart(res.direct_uart, &mut res.shared);
 
+        init_ccsds(direct_uarts);
+
         init_dbgmcu(res.dbgmcu);
 
         init_adc(res.adc, &mut res.shared);
@@ -378,11 +379,18 @@ fn init_power_reset_and_clocks(
         rcc.apb4enr.modify(|_, w| w.vrefen().set_bit());
     }
 
+    fn enable_wwdg1(rcc: &hal::pac::RCC) {
+        rcc.apb3enr.modify(|_, w| w.wwdg1en().set_bit());
+        // WWDG1 によるリセットで CPU だけでなく MCU 全体をリセットする
+        rcc.gcr.modify(|_, w| w.ww1rsc().set_bit());
+    }
+
     let pwr = pwr.constrain();
     let pwrcfg = pwr.freeze();
 
     enable_sram123(&rcc);
     enable_vrefbuf(&rcc);
+    enable_wwdg1(&rcc);
 
     let rcc = rcc.constrain();
     let mut ccdr = rcc.sys_ck(400.MHz()).hclk(100.MHz()).freeze(pwrcfg, syscfg);
@@ -390,8 +398,8 @@ fn init_power_reset_and_clocks(
     ccdr
 }
 
-fn init_wdt(res: resources::Wdt) {
-    let wdt = wdt::Wdt::new(res.iwdg);
+fn init_wdt(res: resources::Wdt, shared: &resources::Shared) {
+    let wdt = wdt::Wdt::new(res.iwdg, res.wwdg, shared.clocks.pclk3());
     let wdt = singleton!(: wdt::Wdt = wdt).unwrap();
     let wdt = singleton!(: &dyn WdtBind = wdt).unwrap();
     C2A_MONAZITE_WDT.set(wdt);
@@ -412,13 +420,20 @@ fn init_ramecc(res: resources::Ramecc) -> ramecc::RamScrubber {
         ecc_stats,
         (res.ramecc1, res.ramecc2, res.ramecc3),
         res.mdma_s0,
+        (ramecc::MEMORY_SCRUB_TARGETS, ramecc::MEMORY_SCRUB_WORDS),
     )
 }
 
 fn init_btmgr(res: resources::Btmgr) {
     let bootmeta = BootMeta::new(res.rtc);
     let flash_option_bytes = unsafe { FlashOptionBytes::new() };
-    let btmgr = btmgr::Btmgr::new(bootmeta, flash_option_bytes);
+    // ブート履歴を読み出すため、Backup SRAM のクロックを有効化する（ブートローダでも有効化されている）
+    unsafe { &(*pac::RCC::ptr()) }
+        .ahb4enr
+        .modify(|_, w| w.bkpramen().set_bit());
+    // ブート履歴に書き込むのはブートローダだけなので、アプリケーションからの参照はここでの 1 つのみ
+    let history = unsafe { bootmeta::bsram_boot_history() };
+    let btmgr = btmgr::Btmgr::new(bootmeta, flash_option_bytes, history);
     let btmgr = singleton!(: btmgr::Btmgr = btmgr).unwrap();
     let btmgr = singleton!(: &dyn BtmgrBind = btmgr).unwrap();
     C2A_MONAZITE_BTMGR.set(btmgr);
@@ -542,15 +557,25 @@ fn init_uart(
         singleton!(: DirectUartArray = direct_uarts).unwrap()
     };
 
-    let uart = uart::Uart::new(direct_uarts);
+    let uart = uart::Uart::new(direct_uarts, CCSDS_UART.map(|(ch, _)| ch));
     let uart = singleton!(: uart::Uart = uart).unwrap();
     let uart = singleton!(: &dyn UartBind = uart).unwrap();
     C2A_MONAZITE_UART.set(uart);
     direct_uarts
 }
 
-fn init_ccsds(_shared: &mut resources::Shared) {
-    let ccsds = ccsds::Ccsds::new();
+/// CCSDS の送受信に用いる直結 UART のチャネルと、受信するフレームの区切り方
+#[cfg(feature = "ccsds-uart")]
+const CCSDS_UART: Option<(usize, SyncMode)> = Some((5, SyncMode::Cltu));
+#[cfg(not(feature = "ccsds-uart"))]
+const CCSDS_UART: Option<(usize, SyncMode)> = None;
+
+fn init_ccsds(direct_uarts: &'static DirectUartArray) {
+    let link = CCSDS_UART.map(|(ch, sync_mode)| {
+        let (tx, rx) = &direct_uarts[ch];
+        ccsds::UartLink { tx, rx, sync_mode }
+    });
+    let ccsds = ccsds::Ccsds::new(link);
     let ccsds = singleton!(: ccsds::Ccsds = ccsds).unwrap();
     let ccsds = singleton!(: &dyn CcsdsBind = ccsds).unwrap();
     C2A_MONAZITE_CCSDS.set(ccsds);
@@ -580,6 +605,6 @@ fn init_c2a() {
     }
     rprintln!("C2A_init: TMGR_init done.");
 
-    // FIXME: BTMGR_set_next_boot_bank(0) の呼び出しタイミングは user 側で定義させたい
-    c2a_monazite_btmgr_bind::BTMGR_set_next_boot_bank(0);
+    // FIXME: BTMGR_confirm_boot() の呼び出しタイミングは user 側で定義させたい
+    c2a_monazite_btmgr_bind::BTMGR_confirm_boot();
 }
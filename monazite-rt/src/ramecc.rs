@@ -1,47 +1,53 @@
-use c2a_monazite_ramecc_bind::Ramecc as RameccBind;
-use core::ops::Range;
+use c2a_monazite_ramecc_bind::{
+    ErrorCount, Fault, Ramecc as RameccBind, Region, ScrubbingProgress,
+};
+use core::cell::Cell;
 use core::sync::atomic::{AtomicU32, Ordering};
+use cortex_m::interrupt::Mutex;
+use embedded_dma::WriteBuffer;
 use hal::dma::{
     mdma::{MdmaConfig, MdmaIncrement, MdmaTrigger, Stream0},
     traits::Direction,
     MasterTransfer, MemoryToMemory, Transfer,
 };
+use ram_scrub::{
+    Targets, Walker, AXI_SRAM, BSRAM, DTCM, ITCM, SRAM1_0, SRAM1_1, SRAM2_0, SRAM2_1, SRAM3, SRAM4,
+};
 use stm32h7xx_hal as hal;
 use stm32h7xx_hal::stm32::{MDMA, RAMECC1, RAMECC2, RAMECC3};
 
-const AXI_SRAM: Range<usize> = 0x2400_0000..0x2408_0000;
-const DTCM: Range<usize> = 0x2000_0000..0x2002_0000;
-const SRAM1_0: Range<usize> = 0x3000_0000..0x3001_0000;
-const SRAM1_1: Range<usize> = 0x3001_0000..0x3002_0000;
-const SRAM2_0: Range<usize> = 0x3002_0000..0x3003_0000;
-const SRAM2_1: Range<usize> = 0x3003_0000..0x3004_0000;
-const SRAM3: Range<usize> = 0x3004_0000..0x3004_8000;
-const SRAM4: Range<usize> = 0x3800_0000..0x3801_0000;
-
-const ECC_TARGETS: [Range<usize>; 7] = [AXI_SRAM, SRAM1_0, SRAM1_1, SRAM2_0, SRAM2_1, SRAM3, SRAM4];
-
-// scrubbing entire memory in 2200 sec
+// scrubbing entire memory in 2700 sec
 // RAM size / src_buf size = memory scrub time
-// -> (512 + 128 + 128 + 32 + 64) * 1024 [byte] / 400 [byte / sec] = 2211.84 sec
+// -> (512 + 128 + 128 + 32 + 64 + 128 + 64 + 4) * 1024 [byte] / 400 [byte / sec] = 2713.6 sec
 const MEMORY_SCRUB_INTERVAL_TICKS: u32 = 1000;
 pub const MEMORY_SCRUB_WORDS: usize = 100; // src_buf size
+pub const MEMORY_SCRUB_TARGETS: Targets = Targets::ALL;
+
+/// MDMA で読み書きするメモリ
+///
+/// ITCM は 0 番地から始まりスライスとして扱えないため、アドレスとワード数で保持する。
+struct ScrubBuffer {
+    address: usize,
+    words: usize,
+}
+
+unsafe impl WriteBuffer for ScrubBuffer {
+    type Word = u32;
+
+    unsafe fn write_buffer(&mut self) -> (*mut u32, usize) {
+        (self.address as *mut u32, self.words)
+    }
+}
 
 type OptionTransfer<S> = Option<
-    Transfer<
-        S,
-        hal::dma::MemoryToMemory<u32>,
-        MemoryToMemory<u32>,
-        &'static mut [u32],
-        MasterTransfer,
-    >,
+    Transfer<S, hal::dma::MemoryToMemory<u32>, MemoryToMemory<u32>, ScrubBuffer, MasterTransfer>,
 >;
 
 pub struct RamScrubber<S = Stream0<MDMA>>
 where
     S: hal::dma::traits::MasterStream,
 {
-    target_ram_index: usize,
-    dma_ptr: usize,
+    walker: Walker,
     dma_config: MdmaConfig,
     regs: Regs,
     // if we don't store the transfer, it will be dropped and the DMA will stop
@@ -54,15 +60,20 @@ impl<S> RamScrubber<S>
 where
     S: hal::dma::traits::MasterStream + hal::dma::traits::Stream<Config = MdmaConfig>,
 {
+    /// `targets` を `words` ワードずつスクラブする
     pub fn new(
         dst_buf: &'static mut [u32; 1],
         stats: &'static EccStats,
         (ramecc1, ramecc2, ramecc3): (RAMECC1, RAMECC2, RAMECC3),
         dma_stream: S,
+        (targets, words): (Targets, usize),
     ) -> Self {
-        let target_ram_index = 0;
-        let dma_ptr = ECC_TARGETS[target_ram_index].start as *mut u32;
-        let src_buf = unsafe { core::slice::from_raw_parts_mut(dma_ptr, MEMORY_SCRUB_WORDS) };
+        let mut walker = Walker::new(targets, words);
+        let src_buf = Self::next_chunk(&mut walker, stats);
+        let dst_buf = ScrubBuffer {
+            address: dst_buf.as_mut_ptr() as usize,
+            words: dst_buf.len(),
+        };
 
         let dma_config = MdmaConfig::default()
             .trigger_mode(MdmaTrigger::Buffer)
@@ -73,12 +84,15 @@ where
         let mut transfer: Transfer<_, _, _, _, _> = Transfer::init_master(
             dma_stream,
             MemoryToMemory::new(),
-            &mut dst_buf[..],
+            dst_buf,
             Some(src_buf),
             dma_config,
         );
 
         stats.set_scrubbing_interval(MEMORY_SCRUB_INTERVAL_TICKS);
+        stats.set_scrubbing_targets(walker.targets().bits());
+        #[allow(clippy::cast_possible_truncation)]
+        stats.set_scrubbing_words(walker.chunk_words() as u32);
 
         let mut ramecc = Regs::new(ramecc1, ramecc2, ramecc3);
         ramecc.enable_ecc();
@@ -86,8 +100,7 @@ where
         transfer.start(|_| {});
 
         Self {
-            target_ram_index,
-            dma_ptr: dma_ptr as usize,
+            walker,
             dma_config,
             regs: ramecc,
             transfer: Some(transfer),
@@ -110,18 +123,13 @@ where
             .as_ref()
             .is_some_and(stm32h7xx_hal::dma::Transfer::get_transfer_complete_flag)
         {
-            let src_buf =
-                unsafe { core::slice::from_raw_parts_mut(self.dma_ptr_next(), MEMORY_SCRUB_WORDS) };
+            self.apply_config();
+            let src_buf = Self::next_chunk(&mut self.walker, self.stats);
 
             let old_transfer = self.transfer.take();
             let (dma_stream, mem2mem, dst_buf, _) = old_transfer.unwrap().free();
-            let mut transfer: Transfer<_, _, _, _, _> = Transfer::init_master(
-                dma_stream,
-                mem2mem,
-                &mut dst_buf[..],
-                Some(src_buf),
-                self.dma_config,
-            );
+            let mut transfer: Transfer<_, _, _, _, _> =
+                Transfer::init_master(dma_stream, mem2mem, dst_buf, Some(src_buf), self.dma_config);
 
             transfer.start(|_| {});
 
@@ -129,19 +137,39 @@ where
         }
     }
 
-    fn dma_ptr_next(&mut self) -> *mut u32 {
-        self.dma_ptr = self.dma_ptr.wrapping_add(MEMORY_SCRUB_WORDS * 4); // add MEMORY_SCRUB_WORDS * 4 bytes (1 WORD)
-        if !ECC_TARGETS[self.target_ram_index].contains(&self.dma_ptr) {
-            if self.target_ram_index + 1 < ECC_TARGETS.len() {
-                self.target_ram_index += 1;
-            } else {
-                self.incr_scrubbing_loop_count();
-                self.target_ram_index = 0;
+    /// C2A から変更された対象とワード数を反映する
+    fn apply_config(&mut self) {
+        let targets = self.stats.scrubbing_targets.load(Ordering::Relaxed);
+        if let Some(targets) = Targets::from_bits(targets) {
+            if targets != self.walker.targets() {
+                self.walker.set_targets(targets);
             }
-            self.dma_ptr = ECC_TARGETS[self.target_ram_index].start;
         }
+        let words = self.stats.scrubbing_words.load(Ordering::Relaxed) as usize;
+        self.walker.set_chunk_words(words);
+    }
 
-        self.dma_ptr as *mut u32
+    /// 次にスクラブする範囲を返し、進み具合を `stats` に反映する
+    fn next_chunk(walker: &mut Walker, stats: &EccStats) -> ScrubBuffer {
+        let chunk = walker.next().unwrap();
+        let progress = walker.progress();
+        stats
+            .scrubbing_loops
+            .store(progress.loops, Ordering::Relaxed);
+        // RAM の大きさは 32 bit に収まる
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            stats
+                .scrubbed_bytes
+                .store(progress.scrubbed_bytes as u32, Ordering::Relaxed);
+            stats
+                .scrubbing_total_bytes
+                .store(progress.total_bytes as u32, Ordering::Relaxed);
+        }
+        ScrubBuffer {
+            address: chunk.address,
+            words: chunk.words,
+        }
     }
 
     #[allow(clippy::too_many_lines)]
@@ -151,6 +179,7 @@ where
         let ramecc3 = &self.regs.ramecc3;
 
         let r1m1sr = ramecc1.m1sr.read(); // D1 M1 = AXI SRAM
+        let r1m2sr = ramecc1.m2sr.read(); // D1 M2 = ITCM
         let r1m3sr = ramecc1.m3sr.read(); // D1 M3 = D0TCM
         let r1m4sr = ramecc1.m4sr.read(); // D1 M4 = D1TCM
         let r2m1sr = ramecc2.m1sr.read(); // D2 M1 = SRAM1_0
@@ -159,10 +188,13 @@ where
         let r2m4sr = ramecc2.m4sr.read(); // D2 M4 = SRAM2_1
         let r2m5sr = ramecc2.m5sr.read(); // D2 M5 = SRAM3
         let r3m1sr = ramecc3.m1sr.read(); // D3 M1 = SRAM4
+        let r3m2sr = ramecc3.m2sr.read(); // D3 M2 = Backup SRAM
 
         if r1m1sr.bits() != 0 {
             // AXI SRAM
             let fadd = ramecc1.m1far.read().fadd().bits();
+            let fdatal = ramecc1.m1fdrl.read().fdatal().bits();
+            let fdatah = ramecc1.m1fdrh.read().fdatah().bits();
             ramecc1.m1sr.reset();
             let addr = crate::ramecc::AXI_SRAM.start + fadd as usize * 8; // word size of AXI SRAM is 8;
             ramecc1.m1cr.write(|w| {
@@ -175,14 +207,15 @@ where
                 w.eccelen().set_bit();
                 w
             });
+            self.record_fault(Region::AxiSram, addr, fdatal, fdatah);
             if r1m1sr.sedcf().bit_is_set() {
-                self.incr_single_error_count();
+                self.incr_single_error_count(Region::AxiSram);
             }
             if r1m1sr.dedf().bit_is_set() {
-                self.incr_double_error_count();
+                self.incr_double_error_count(Region::AxiSram);
             }
             if r1m1sr.debwdf().bit_is_set() {
-                self.incr_double_error_on_byte_write_count();
+                self.incr_double_error_on_byte_write_count(Region::AxiSram);
             }
         } else if r1m3sr.bits() != 0 {
             // D0TCM
@@ -201,18 +234,20 @@ where
                 w
             });
             unsafe { (addr as *mut u32).write_volatile(fdatal) };
+            self.record_fault(Region::D0Tcm, addr, fdatal, 0);
             if r1m3sr.sedcf().bit_is_set() {
-                self.incr_dtcm_single_error_count();
+                self.incr_dtcm_single_error_count(Region::D0Tcm);
             }
             if r1m3sr.dedf().bit_is_set() {
-                self.incr_dtcm_double_error_count();
+                self.incr_dtcm_double_error_count(Region::D0Tcm);
             }
             if r1m3sr.debwdf().bit_is_set() {
-                self.incr_dtcm_double_error_on_byte_write_count();
+                self.incr_dtcm_double_error_on_byte_write_count(Region::D0Tcm);
             }
         } else if r1m4sr.bits() != 0 {
             // D1TCM
             let fadd = ramecc1.m4far.read().fadd().bits();
+            let fdatal = ramecc1.m4fdrl.read().fdatal().bits();
             ramecc1.m4sr.reset();
             let addr = crate::ramecc::DTCM.start + 4 + fadd as usize * 8;
             ramecc1.m4cr.write(|w| {
@@ -225,18 +260,46 @@ where
                 w.eccelen().set_bit();
                 w
             });
+            self.record_fault(Region::D1Tcm, addr, fdatal, 0);
             if r1m4sr.sedcf().bit_is_set() {
-                self.incr_dtcm_single_error_count();
+                self.incr_dtcm_single_error_count(Region::D1Tcm);
             }
             if r1m4sr.dedf().bit_is_set() {
-                self.incr_dtcm_double_error_count();
+                self.incr_dtcm_double_error_count(Region::D1Tcm);
             }
             if r1m4sr.debwdf().bit_is_set() {
-                self.incr_dtcm_double_error_on_byte_write_count();
+                self.incr_dtcm_double_error_on_byte_write_count(Region::D1Tcm);
+            }
+        } else if r1m2sr.bits() != 0 {
+            // ITCM
+            let fadd = ramecc1.m2far.read().fadd().bits();
+            let fdatal = ramecc1.m2fdrl.read().fdatal().bits();
+            let fdatah = ramecc1.m2fdrh.read().fdatah().bits();
+            ramecc1.m2sr.reset();
+            let addr = crate::ramecc::ITCM.start + fadd as usize * 8; // word size of ITCM is 8
+            ramecc1.m2cr.write(|w| {
+                w.eccelen().clear_bit();
+                w
+            });
+            unsafe { rewrite_u64(addr) };
+            ramecc1.m2cr.write(|w| {
+                w.eccelen().set_bit();
+                w
+            });
+            self.record_fault(Region::Itcm, addr, fdatal, fdatah);
+            if r1m2sr.sedcf().bit_is_set() {
+                self.incr_single_error_count(Region::Itcm);
+            }
+            if r1m2sr.dedf().bit_is_set() {
+                self.incr_double_error_count(Region::Itcm);
+            }
+            if r1m2sr.debwdf().bit_is_set() {
+                self.incr_double_error_on_byte_write_count(Region::Itcm);
             }
         } else if r2m1sr.bits() != 0 {
             // SRAM1_0
             let fadd = ramecc2.m1far.read().fadd().bits();
+            let fdatal = ramecc2.m1fdrl.read().fdatal().bits();
             ramecc2.m1sr.reset();
             let addr = crate::ramecc::SRAM1_0.start + fadd as usize * 4;
             ramecc2.m1cr.write(|w| {
@@ -249,18 +312,20 @@ where
                 w.eccelen().set_bit();
                 w
             });
+            self.record_fault(Region::Sram1_0, addr, fdatal, 0);
             if r2m1sr.sedcf().bit_is_set() {
-                self.incr_single_error_count();
+                self.incr_single_error_count(Region::Sram1_0);
             }
             if r2m1sr.dedf().bit_is_set() {
-                self.incr_double_error_count();
+                self.incr_double_error_count(Region::Sram1_0);
             }
             if r2m1sr.debwdf().bit_is_set() {
-                self.incr_double_error_on_byte_write_count();
+                self.incr_double_error_on_byte_write_count(Region::Sram1_0);
             }
         } else if r2m2sr.bits() != 0 {
             // SRAM1_1
             let fadd = ramecc2.m2far.read().fadd().bits();
+            let fdatal = ramecc2.m2fdrl.read().fdatal().bits();
             ramecc2.m2sr.reset();
             let addr = crate::ramecc::SRAM1_1.start + fadd as usize * 4;
             ramecc2.m2cr.write(|w| {
@@ -273,18 +338,20 @@ where
                 w.eccelen().set_bit();
                 w
             });
+            self.record_fault(Region::Sram1_1, addr, fdatal, 0);
             if r2m2sr.sedcf().bit_is_set() {
-                self.incr_single_error_count();
+                self.incr_single_error_count(Region::Sram1_1);
             }
             if r2m2sr.dedf().bit_is_set() {
-                self.incr_double_error_count();
+                self.incr_double_error_count(Region::Sram1_1);
             }
             if r2m2sr.debwdf().bit_is_set() {
-                self.incr_double_error_on_byte_write_count();
+                self.incr_double_error_on_byte_write_count(Region::Sram1_1);
             }
         } else if r2m3sr.bits() != 0 {
             // SRAM2_0
             let fadd = ramecc2.m3far.read().fadd().bits();
+            let fdatal = ramecc2.m3fdrl.read().fdatal().bits();
             ramecc2.m3sr.reset();
             let addr = crate::ramecc::SRAM2_0.start + fadd as usize * 4;
             ramecc2.m3cr.write(|w| {
@@ -297,18 +364,20 @@ where
                 w.eccelen().set_bit();
                 w
             });
+            self.record_fault(Region::Sram2_0, addr, fdatal, 0);
             if r2m3sr.sedcf().bit_is_set() {
-                self.incr_single_error_count();
+                self.incr_single_error_count(Region::Sram2_0);
             }
             if r2m3sr.dedf().bit_is_set() {
-                self.incr_double_error_count();
+                self.incr_double_error_count(Region::Sram2_0);
             }
             if r2m3sr.debwdf().bit_is_set() {
-                self.incr_double_error_on_byte_write_count();
+                self.incr_double_error_on_byte_write_count(Region::Sram2_0);
             }
         } else if r2m4sr.bits() != 0 {
             // SRAM2_1
             let fadd = ramecc2.m4far.read().fadd().bits();
+            let fdatal = ramecc2.m4fdrl.read().fdatal().bits();
             ramecc2.m4sr.reset();
             let addr = crate::ramecc::SRAM2_1.start + fadd as usize * 4;
             ramecc2.m4cr.write(|w| {
@@ -321,18 +390,20 @@ where
                 w.eccelen().set_bit();
                 w
             });
+            self.record_fault(Region::Sram2_1, addr, fdatal, 0);
             if r2m4sr.sedcf().bit_is_set() {
-                self.incr_single_error_count();
+                self.incr_single_error_count(Region::Sram2_1);
             }
             if r2m4sr.dedf().bit_is_set() {
-                self.incr_double_error_count();
+                self.incr_double_error_count(Region::Sram2_1);
             }
             if r2m4sr.debwdf().bit_is_set() {
-                self.incr_double_error_on_byte_write_count();
+                self.incr_double_error_on_byte_write_count(Region::Sram2_1);
             }
         } else if r2m5sr.bits() != 0 {
             // SRAM3
             let fadd = ramecc2.m5far.read().fadd().bits();
+            let fdatal = ramecc2.m5fdrl.read().fdatal().bits();
             ramecc2.m5sr.reset();
             let addr = crate::ramecc::SRAM3.start + fadd as usize * 4;
             ramecc2.m5cr.write(|w| {
@@ -345,18 +416,20 @@ where
                 w.eccelen().set_bit();
                 w
             });
+            self.record_fault(Region::Sram3, addr, fdatal, 0);
             if r2m5sr.sedcf().bit_is_set() {
-                self.incr_single_error_count();
+                self.incr_single_error_count(Region::Sram3);
             }
             if r2m5sr.dedf().bit_is_set() {
-                self.incr_double_error_count();
+                self.incr_double_error_count(Region::Sram3);
             }
             if r2m5sr.debwdf().bit_is_set() {
-                self.incr_double_error_on_byte_write_count();
+                self.incr_double_error_on_byte_write_count(Region::Sram3);
             }
         } else if r3m1sr.bits() != 0 {
             // SRAM4
             let fadd = ramecc3.m1far.read().fadd().bits();
+            let fdatal = ramecc3.m1fdrl.read().fdatal().bits();
             ramecc3.m1sr.reset();
             let addr = crate::ramecc::SRAM4.start + fadd as usize * 4;
             ramecc3.m1cr.write(|w| {
@@ -369,55 +442,132 @@ where
                 w.eccelen().set_bit();
                 w
             });
+            self.record_fault(Region::Sram4, addr, fdatal, 0);
             if r3m1sr.sedcf().bit_is_set() {
-                self.incr_single_error_count();
+                self.incr_single_error_count(Region::Sram4);
             }
             if r3m1sr.dedf().bit_is_set() {
-                self.incr_double_error_count();
+                self.incr_double_error_count(Region::Sram4);
             }
             if r3m1sr.debwdf().bit_is_set() {
-                self.incr_double_error_on_byte_write_count();
+                self.incr_double_error_on_byte_write_count(Region::Sram4);
+            }
+        } else if r3m2sr.bits() != 0 {
+            // Backup SRAM
+            let fadd = ramecc3.m2far.read().fadd().bits();
+            let fdatal = ramecc3.m2fdrl.read().fdatal().bits();
+            ramecc3.m2sr.reset();
+            let addr = crate::ramecc::BSRAM.start + fadd as usize * 4;
+            ramecc3.m2cr.write(|w| {
+                w.eccelen().clear_bit();
+                w
+            });
+            let data = unsafe { (addr as *mut u32).read_volatile() };
+            unsafe { (addr as *mut u32).write_volatile(data) };
+            ramecc3.m2cr.write(|w| {
+                w.eccelen().set_bit();
+                w
+            });
+            self.record_fault(Region::Bsram, addr, fdatal, 0);
+            if r3m2sr.sedcf().bit_is_set() {
+                self.incr_single_error_count(Region::Bsram);
+            }
+            if r3m2sr.dedf().bit_is_set() {
+                self.incr_double_error_count(Region::Bsram);
+            }
+            if r3m2sr.debwdf().bit_is_set() {
+                self.incr_double_error_on_byte_write_count(Region::Bsram);
             }
         }
     }
 
-    pub fn incr_scrubbing_loop_count(&mut self) {
-        self.stats.scrubbing_loops.fetch_add(1, Ordering::Relaxed);
-    }
-
-    pub fn incr_single_error_count(&mut self) {
+    pub fn incr_single_error_count(&mut self, region: Region) {
         self.stats.single_errors.fetch_add(1, Ordering::Relaxed);
+        self.stats
+            .region(region)
+            .single_errors
+            .fetch_add(1, Ordering::Relaxed);
     }
 
-    pub fn incr_double_error_count(&mut self) {
+    pub fn incr_double_error_count(&mut self, region: Region) {
         self.stats.double_errors.fetch_add(1, Ordering::Relaxed);
+        self.stats
+            .region(region)
+            .double_errors
+            .fetch_add(1, Ordering::Relaxed);
     }
 
-    pub fn incr_double_error_on_byte_write_count(&mut self) {
+    pub fn incr_double_error_on_byte_write_count(&mut self, region: Region) {
+        self.stats
+            .double_errors_on_byte_write
+            .fetch_add(1, Ordering::Relaxed);
         self.stats
+            .region(region)
             .double_errors_on_byte_write
             .fetch_add(1, Ordering::Relaxed);
     }
 
-    pub fn incr_dtcm_single_error_count(&mut self) {
+    pub fn incr_dtcm_single_error_count(&mut self, region: Region) {
         self.stats
             .dtcm_single_errors
             .fetch_add(1, Ordering::Relaxed);
+        self.stats
+            .region(region)
+            .single_errors
+            .fetch_add(1, Ordering::Relaxed);
     }
 
-    pub fn incr_dtcm_double_error_count(&mut self) {
+    pub fn incr_dtcm_double_error_count(&mut self, region: Region) {
         self.stats
             .dtcm_double_errors
             .fetch_add(1, Ordering::Relaxed);
+        self.stats
+            .region(region)
+            .double_errors
+            .fetch_add(1, Ordering::Relaxed);
     }
 
-    pub fn incr_dtcm_double_error_on_byte_write_count(&mut self) {
+    pub fn incr_dtcm_double_error_on_byte_write_count(&mut self, region: Region) {
         self.stats
             .dtcm_double_errors_on_byte_write
             .fetch_add(1, Ordering::Relaxed);
+        self.stats
+            .region(region)
+            .double_errors_on_byte_write
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_fault(&mut self, region: Region, addr: usize, data_low: u32, data_high: u32) {
+        // RAM のアドレスは 32 bit に収まる
+        #[allow(clippy::cast_possible_truncation)]
+        let address = addr as u32;
+        let fault = Fault {
+            region: region as u32,
+            address,
+            data_low,
+            data_high,
+        };
+        cortex_m::interrupt::free(|cs| self.stats.last_fault.borrow(cs).set(Some(fault)));
     }
 }
 
+/// `addr` の 64 bit を読み出して、そのまま書き戻す
+///
+/// ITCM は 0 番地から始まるため、ポインタを経由せずにアセンブリで読み書きする。
+///
+/// # Safety
+/// `addr` は読み書きできる 8 byte 境界のアドレスである必要がある。
+unsafe fn rewrite_u64(addr: usize) {
+    core::arch::asm!(
+        "ldrd {lo}, {hi}, [{addr}]",
+        "strd {lo}, {hi}, [{addr}]",
+        addr = in(reg) addr,
+        lo = out(reg) _,
+        hi = out(reg) _,
+        options(nostack, preserves_flags),
+    );
+}
+
 struct Regs {
     ramecc1: RAMECC1,
     ramecc2: RAMECC2,
@@ -442,6 +592,13 @@ impl Regs {
             w.eccdebwie().set_bit();
             w
         });
+        self.ramecc1.m2cr.write(|w| {
+            w.eccelen().set_bit();
+            w.eccseie().set_bit();
+            w.eccdeie().set_bit();
+            w.eccdebwie().set_bit();
+            w
+        });
         self.ramecc1.m3cr.write(|w| {
             w.eccelen().set_bit();
             w.eccseie().set_bit();
@@ -506,6 +663,14 @@ impl Regs {
             w.eccdebwie().set_bit();
             w
         });
+        self.ramecc3.m2sr.reset();
+        self.ramecc3.m2cr.write(|w| {
+            w.eccelen().set_bit();
+            w.eccseie().set_bit();
+            w.eccdeie().set_bit();
+            w.eccdebwie().set_bit();
+            w
+        });
 
         self.ramecc2.ier.write(|w| {
             w.geccseie().set_bit();
@@ -523,6 +688,12 @@ impl Regs {
 }
 
 #[derive(Default)]
+pub struct RegionErrors {
+    pub single_errors: AtomicU32,
+    pub double_errors: AtomicU32,
+    pub double_errors_on_byte_write: AtomicU32,
+}
+
 pub struct EccStats {
     pub scrubbing_loops: AtomicU32,
     pub single_errors: AtomicU32,
@@ -532,6 +703,39 @@ pub struct EccStats {
     pub dtcm_double_errors: AtomicU32,
     pub dtcm_double_errors_on_byte_write: AtomicU32,
     pub scrubbing_interval_tick: AtomicU32,
+    pub scrubbing_targets: AtomicU32,
+    pub scrubbing_words: AtomicU32,
+    pub scrubbed_bytes: AtomicU32,
+    pub scrubbing_total_bytes: AtomicU32,
+    pub region_errors: [RegionErrors; Region::ALL.len()],
+    pub last_fault: Mutex<Cell<Option<Fault>>>,
+}
+
+impl Default for EccStats {
+    fn default() -> Self {
+        Self {
+            scrubbing_loops: AtomicU32::default(),
+            single_errors: AtomicU32::default(),
+            double_errors: AtomicU32::default(),
+            double_errors_on_byte_write: AtomicU32::default(),
+            dtcm_single_errors: AtomicU32::default(),
+            dtcm_double_errors: AtomicU32::default(),
+            dtcm_double_errors_on_byte_write: AtomicU32::default(),
+            scrubbing_interval_tick: AtomicU32::default(),
+            scrubbing_targets: AtomicU32::default(),
+            scrubbing_words: AtomicU32::default(),
+            scrubbed_bytes: AtomicU32::default(),
+            scrubbing_total_bytes: AtomicU32::default(),
+            region_errors: Default::default(),
+            last_fault: Mutex::new(Cell::new(None)),
+        }
+    }
+}
+
+impl EccStats {
+    fn region(&self, region: Region) -> &RegionErrors {
+        &self.region_errors[region as usize]
+    }
 }
 
 impl RameccBind for EccStats {
@@ -573,4 +777,42 @@ impl RameccBind for EccStats {
         self.scrubbing_interval_tick
             .store(scrubbing_interval_tick, Ordering::Relaxed);
     }
+
+    fn region_errors(&self, region: Region) -> ErrorCount {
+        let errors = self.region(region);
+        ErrorCount {
+            single_error: errors.single_errors.load(Ordering::Relaxed),
+            double_error: errors.double_errors.load(Ordering::Relaxed),
+            double_error_on_byte_write: errors.double_errors_on_byte_write.load(Ordering::Relaxed),
+        }
+    }
+
+    fn last_fault(&self) -> Option<Fault> {
+        cortex_m::interrupt::free(|cs| self.last_fault.borrow(cs).get())
+    }
+
+    fn scrubbing_targets(&self) -> u32 {
+        self.scrubbing_targets.load(Ordering::Relaxed)
+    }
+
+    // 次のスクラブの前に RamScrubber が反映する
+    fn set_scrubbing_targets(&self, targets: u32) {
+        self.scrubbing_targets.store(targets, Ordering::Relaxed);
+    }
+
+    fn scrubbing_words(&self) -> u32 {
+        self.scrubbing_words.load(Ordering::Relaxed)
+    }
+
+    // 次のスクラブの前に RamScrubber が反映する
+    fn set_scrubbing_words(&self, words: u32) {
+        self.scrubbing_words.store(words, Ordering::Relaxed);
+    }
+
+    fn scrubbing_progress(&self) -> ScrubbingProgress {
+        ScrubbingProgress {
+            scrubbed_bytes: self.scrubbed_bytes.load(Ordering::Relaxed),
+            total_bytes: self.scrubbing_total_bytes.load(Ordering::Relaxed),
+        }
+    }
 }
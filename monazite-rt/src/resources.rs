@@ -82,6 +82,7 @@ pub struct Thermometer {
 
 pub struct Wdt {
     pub iwdg: pac::IWDG,
+    pub wwdg: pac::WWDG,
 }
 
 pub struct Dbgmcu {
@@ -177,7 +178,10 @@ impl Resources {
                 ccdrp_adc3: ccdrp.ADC3,
                 dma2s7: dma2s.7,
             },
-            wdt: Wdt { iwdg: dp.IWDG },
+            wdt: Wdt {
+                iwdg: dp.IWDG,
+                wwdg: dp.WWDG,
+            },
             dbgmcu: Dbgmcu { dbgmcu: dp.DBGMCU },
         }
     }
@@ -7,18 +7,28 @@ pub type DirectUartArray = [(direct::Tx, direct::Rx); DIRECT_UART_NUM];
 
 pub struct Uart {
     direct: &'static DirectUartArray,
+    // CCSDS が占有しているチャネル
+    reserved: Option<usize>,
 }
 
 impl Uart {
-    pub fn new(direct: &'static DirectUartArray) -> Self {
-        Self { direct }
+    /// `reserved` のチャネルは C2A の UART として使用できない
+    pub fn new(direct: &'static DirectUartArray, reserved: Option<usize>) -> Self {
+        Self { direct, reserved }
+    }
+
+    fn channel(&self, ch: usize) -> Option<&(direct::Tx, direct::Rx)> {
+        if self.reserved == Some(ch) {
+            return None;
+        }
+        self.direct.get(ch)
     }
 }
 
 impl UartBind for Uart {
     fn initialize(&self, ch: ChannelId, baudrate: u32) -> Result<(), Error> {
         let ch = u8::from(ch) as usize;
-        if let Some((tx, rx)) = self.direct.get(ch) {
+        if let Some((tx, rx)) = self.channel(ch) {
             direct::set_baud_rate(baudrate, tx, rx);
             direct::reset(tx, rx);
             Ok(())
@@ -33,7 +43,7 @@ impl UartBind for Uart {
 
     fn send(&self, ch: ChannelId, data: &[u8]) -> Result<(), Error> {
         let ch = u8::from(ch) as usize;
-        if let Some((tx, _rx)) = self.direct.get(ch) {
+        if let Some((tx, _rx)) = self.channel(ch) {
             defmt::trace!("UART_tx(direct): ch: {} write {} bytes", ch, data.len());
             if tx.write(data) {
                 Ok(())
@@ -47,7 +57,7 @@ impl UartBind for Uart {
 
     fn receive(&self, ch: ChannelId, buffer: &mut [u8]) -> Result<usize, Error> {
         let ch = u8::from(ch) as usize;
-        if let Some((_tx, rx)) = self.direct.get(ch) {
+        if let Some((_tx, rx)) = self.channel(ch) {
             match rx.read(buffer) {
                 Ok(read_len) => {
                     defmt::trace!("UART_rx(direct): ch: {} read {} bytes", ch, read_len);
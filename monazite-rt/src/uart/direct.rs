@@ -145,6 +145,12 @@ impl TxInner {
         true
     }
 
+    #[inline]
+    fn available(&mut self) -> usize {
+        self.restart();
+        self.ring.available()
+    }
+
     #[inline]
     fn complete_read(&mut self) {
         self.restart();
@@ -210,6 +216,14 @@ impl Tx {
         })
     }
 
+    /// 送信バッファの空きバイト数
+    pub fn available(&self) -> usize {
+        cortex_m::interrupt::free(|cs| {
+            let mut inner = self.inner.borrow(cs).borrow_mut();
+            inner.available()
+        })
+    }
+
     pub fn readable<T>(&self, f: impl FnOnce(&[u8], &[u8]) -> T) -> T {
         cortex_m::interrupt::free(|cs| {
             let inner = self.inner.borrow(cs).borrow();
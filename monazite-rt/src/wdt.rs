@@ -1,19 +1,51 @@
 use core::cell::RefCell;
 
-use c2a_monazite_wdt_bind::Wdt as WdtBind;
+use c2a_monazite_wdt_bind::{UnsupportedWindow, Wdt as WdtBind};
 use cortex_m::interrupt::Mutex;
-use hal::{independent_watchdog::IndependentWatchdog, pac, prelude::*};
+use hal::{independent_watchdog::IndependentWatchdog, pac, prelude::*, time::Hertz};
 use stm32h7xx_hal as hal;
+use wwdg_window::Config as WwdgConfig;
 
+struct Inner {
+    iwdg: IndependentWatchdog,
+    wwdg: pac::WWDG,
+    pclk3: Hertz,
+    // WWDG を有効化した後の設定
+    wwdg_config: Option<WwdgConfig>,
+}
+
+impl Inner {
+    fn start_wwdg(&mut self, config: WwdgConfig) {
+        self.wwdg
+            .cfr
+            .modify(|_, w| w.wdgtb().bits(config.wdgtb).w().bits(config.window));
+        self.wwdg.cr.modify(|_, w| w.t().bits(config.counter));
+        // T と同時に WDGA を書き込むとすぐにリセットされることがあるため、分けて書き込む
+        self.wwdg.cr.modify(|_, w| w.wdga().set_bit());
+        self.wwdg_config = Some(config);
+    }
+}
+
+/// IWDG と、ウィンドウ付きで有効化した場合は WWDG を使うウォッチドッグ
+///
+/// WWDG は一度有効化すると止められないため、その後に [`WdtBind::enable`] した場合は
+/// ウィンドウを開き、WWDG も `time` で数え直す。
+/// `time` が WWDG で数えられる最も長い時間（PCLK3 が 100 MHz の場合は約 335 ミリ秒）を超える場合は、その時間でリセットする。
 pub struct Wdt {
-    inner: Mutex<RefCell<IndependentWatchdog>>,
+    inner: Mutex<RefCell<Inner>>,
 }
 
 impl Wdt {
-    pub fn new(iwdg: pac::IWDG) -> Self {
+    /// WWDG1 のクロックは有効化されていなければならない
+    pub fn new(iwdg: pac::IWDG, wwdg: pac::WWDG, pclk3: Hertz) -> Self {
         let iwdg = IndependentWatchdog::new(iwdg);
         Self {
-            inner: Mutex::new(RefCell::new(iwdg)),
+            inner: Mutex::new(RefCell::new(Inner {
+                iwdg,
+                wwdg,
+                pclk3,
+                wwdg_config: None,
+            })),
         }
     }
 }
@@ -26,14 +58,35 @@ impl WdtBind for Wdt {
     fn clear(&self) {
         cortex_m::interrupt::free(|cs| {
             let mut inner = self.inner.borrow(cs).borrow_mut();
-            inner.feed();
+            inner.iwdg.feed();
+            if let Some(counter) = inner.wwdg_config.as_ref().map(|config| config.counter) {
+                inner.wwdg.cr.modify(|_, w| w.t().bits(counter));
+            }
         });
     }
 
     fn enable(&self, time: u32) {
         cortex_m::interrupt::free(|cs| {
             let mut inner = self.inner.borrow(cs).borrow_mut();
-            inner.start(time.millis());
+            inner.iwdg.start(time.millis());
+            if inner.wwdg_config.is_some() {
+                // `time` が WWDG で数えられない場合は、できるだけ長くする
+                let config =
+                    WwdgConfig::new(inner.pclk3.raw(), 0, time).unwrap_or(WwdgConfig::LONGEST);
+                inner.start_wwdg(config);
+            }
         });
     }
+
+    fn enable_windowed(&self, min_time: u32, time: u32) -> Result<(), UnsupportedWindow> {
+        cortex_m::interrupt::free(|cs| {
+            let mut inner = self.inner.borrow(cs).borrow_mut();
+            let config =
+                WwdgConfig::new(inner.pclk3.raw(), min_time, time).ok_or(UnsupportedWindow)?;
+            // 早すぎるクリアと遅すぎるクリアは WWDG で検出し、IWDG は WWDG が動かない場合に備えて残す
+            inner.iwdg.start(time.millis());
+            inner.start_wwdg(config);
+            Ok(())
+        })
+    }
 }
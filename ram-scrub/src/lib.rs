@@ -0,0 +1,10 @@
+#![cfg_attr(not(test), no_std)]
+#![allow(clippy::must_use_candidate)]
+
+mod target;
+mod walker;
+
+pub use target::{
+    Target, Targets, AXI_SRAM, BSRAM, DTCM, ITCM, SRAM1_0, SRAM1_1, SRAM2_0, SRAM2_1, SRAM3, SRAM4,
+};
+pub use walker::{Chunk, Progress, Walker, MAX_CHUNK_WORDS};
@@ -0,0 +1,163 @@
+use core::ops::{BitOr, Range};
+
+pub const AXI_SRAM: Range<usize> = 0x2400_0000..0x2408_0000;
+pub const DTCM: Range<usize> = 0x2000_0000..0x2002_0000;
+pub const ITCM: Range<usize> = 0x0000_0000..0x0001_0000;
+pub const SRAM1_0: Range<usize> = 0x3000_0000..0x3001_0000;
+pub const SRAM1_1: Range<usize> = 0x3001_0000..0x3002_0000;
+pub const SRAM2_0: Range<usize> = 0x3002_0000..0x3003_0000;
+pub const SRAM2_1: Range<usize> = 0x3003_0000..0x3004_0000;
+pub const SRAM3: Range<usize> = 0x3004_0000..0x3004_8000;
+pub const SRAM4: Range<usize> = 0x3800_0000..0x3801_0000;
+pub const BSRAM: Range<usize> = 0x3880_0000..0x3880_1000;
+
+/// スクラブできるメモリ領域
+///
+/// 値は C2A の `RAMECC_SCRUB_TARGET` のビット位置と同じ。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Target {
+    AxiSram,
+    Sram1_0,
+    Sram1_1,
+    Sram2_0,
+    Sram2_1,
+    Sram3,
+    Sram4,
+    Dtcm,
+    Itcm,
+    /// Backup SRAM
+    Bsram,
+}
+
+impl Target {
+    /// スクラブする順に並べたもの
+    pub const ALL: [Self; 10] = [
+        Self::AxiSram,
+        Self::Sram1_0,
+        Self::Sram1_1,
+        Self::Sram2_0,
+        Self::Sram2_1,
+        Self::Sram3,
+        Self::Sram4,
+        Self::Dtcm,
+        Self::Itcm,
+        Self::Bsram,
+    ];
+
+    pub const fn range(self) -> Range<usize> {
+        match self {
+            Self::AxiSram => AXI_SRAM,
+            Self::Sram1_0 => SRAM1_0,
+            Self::Sram1_1 => SRAM1_1,
+            Self::Sram2_0 => SRAM2_0,
+            Self::Sram2_1 => SRAM2_1,
+            Self::Sram3 => SRAM3,
+            Self::Sram4 => SRAM4,
+            Self::Dtcm => DTCM,
+            Self::Itcm => ITCM,
+            Self::Bsram => BSRAM,
+        }
+    }
+
+    pub const fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+/// スクラブするメモリ領域の集合
+///
+/// 空の集合は作れない。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Targets(u32);
+
+impl Targets {
+    pub const ALL: Self = Self((1 << Target::ALL.len()) - 1);
+
+    /// [`Target::bit`] の論理和から構築する。空の場合や、未知のビットを含む場合は `None` を返す
+    pub const fn from_bits(bits: u32) -> Option<Self> {
+        if bits == 0 || bits & !Self::ALL.0 != 0 {
+            None
+        } else {
+            Some(Self(bits))
+        }
+    }
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub const fn contains(self, target: Target) -> bool {
+        self.0 & target.bit() != 0
+    }
+
+    /// スクラブする順に返す
+    pub fn iter(self) -> impl Iterator<Item = Target> {
+        Target::ALL
+            .into_iter()
+            .filter(move |target| self.contains(*target))
+    }
+
+    /// 1 周で読み出すバイト数
+    pub fn len_bytes(self) -> usize {
+        self.iter().map(|target| target.range().len()).sum()
+    }
+
+    pub(crate) fn first(self) -> Target {
+        Target::ALL[self.0.trailing_zeros() as usize]
+    }
+
+    /// `target` の次にスクラブする領域。`target` が最後の場合は `None` を返す
+    pub(crate) fn after(self, target: Target) -> Option<Target> {
+        self.iter().find(|next| *next as u32 > target as u32)
+    }
+}
+
+impl From<Target> for Targets {
+    fn from(target: Target) -> Self {
+        Self(target.bit())
+    }
+}
+
+impl BitOr<Target> for Targets {
+    type Output = Self;
+
+    fn bitor(self, target: Target) -> Self {
+        Self(self.0 | target.bit())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_follow_all_order() {
+        assert!(Target::ALL
+            .iter()
+            .enumerate()
+            .all(|(i, target)| *target as usize == i));
+        assert_eq!(
+            Targets::ALL.len_bytes(),
+            (512 + 128 + 128 + 32 + 64 + 128 + 64 + 4) * 1024
+        );
+    }
+
+    #[test]
+    fn from_bits_rejects_empty_and_unknown_targets() {
+        assert_eq!(Targets::from_bits(0), None);
+        assert_eq!(Targets::from_bits(1 << 10), None);
+        assert_eq!(Targets::from_bits(0x3FF), Some(Targets::ALL));
+        assert_eq!(
+            Targets::from_bits(0x081),
+            Some(Targets::from(Target::AxiSram) | Target::Dtcm)
+        );
+    }
+
+    #[test]
+    fn after_skips_excluded_targets() {
+        let targets = Targets::from(Target::Sram1_0) | Target::Itcm;
+        assert_eq!(targets.first(), Target::Sram1_0);
+        assert_eq!(targets.after(Target::Sram1_0), Some(Target::Itcm));
+        assert_eq!(targets.after(Target::Itcm), None);
+    }
+}
@@ -0,0 +1,247 @@
+use crate::{Target, Targets};
+
+/// 1 回のスクラブで読み出せる最大のワード数
+///
+/// MDMA の 1 ブロックで転送できる 64 KiB に合わせている。
+pub const MAX_CHUNK_WORDS: usize = 0x4000;
+
+/// 1 回のスクラブで読み出す範囲
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Chunk {
+    pub address: usize,
+    pub words: usize,
+}
+
+/// スクラブの進み具合
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Progress {
+    /// 完了した周回の数
+    pub loops: u32,
+    /// 現在の周回で読み出したバイト数
+    pub scrubbed_bytes: usize,
+    /// 1 周で読み出すバイト数
+    pub total_bytes: usize,
+}
+
+/// スクラブする領域を一定のワード数ずつ巡回する
+///
+/// 最後の領域を読み出し終えると最初の領域に戻り、周回の数を数える。
+/// 領域の末尾では、範囲を超えないように読み出すワード数を減らす。
+pub struct Walker {
+    targets: Targets,
+    chunk_words: usize,
+    target: Target,
+    // `target` の先頭から次に読み出すまでのバイト数
+    offset: usize,
+    loops: u32,
+}
+
+impl Walker {
+    /// `chunk_words` は [`Walker::set_chunk_words`] と同様に制限する
+    pub fn new(targets: Targets, chunk_words: usize) -> Self {
+        Self {
+            targets,
+            chunk_words: chunk_words.clamp(1, MAX_CHUNK_WORDS),
+            target: targets.first(),
+            offset: 0,
+            loops: 0,
+        }
+    }
+
+    pub fn targets(&self) -> Targets {
+        self.targets
+    }
+
+    /// 現在の周回を中断し、`targets` の最初の領域から読み出し直す
+    pub fn set_targets(&mut self, targets: Targets) {
+        self.targets = targets;
+        self.target = targets.first();
+        self.offset = 0;
+    }
+
+    pub fn chunk_words(&self) -> usize {
+        self.chunk_words
+    }
+
+    /// 1 以上 [`MAX_CHUNK_WORDS`] 以下に制限する。次に読み出す位置は変わらない
+    pub fn set_chunk_words(&mut self, chunk_words: usize) {
+        self.chunk_words = chunk_words.clamp(1, MAX_CHUNK_WORDS);
+    }
+
+    pub fn progress(&self) -> Progress {
+        let done: usize = self
+            .targets
+            .iter()
+            .take_while(|target| *target != self.target)
+            .map(|target| target.range().len())
+            .sum();
+        Progress {
+            loops: self.loops,
+            scrubbed_bytes: done + self.offset,
+            total_bytes: self.targets.len_bytes(),
+        }
+    }
+
+    /// [`Iterator::next`] を `chunks` 回呼び出したものとして進める
+    ///
+    /// 周回を丸ごと飛ばす分は数えずに計算するため、`chunks` が大きくても時間はかからない。
+    pub fn skip_chunks(&mut self, mut chunks: u64) {
+        while chunks > 0 {
+            let remaining = self.remaining_chunks(self.target, self.offset);
+            if chunks < remaining {
+                #[allow(clippy::cast_possible_truncation)]
+                let bytes = chunks as usize * self.chunk_bytes();
+                self.offset += bytes;
+                return;
+            }
+            chunks -= remaining;
+            if self.next_target() {
+                let per_loop = self.chunks_per_loop();
+                #[allow(clippy::cast_possible_truncation)]
+                let loops = (chunks / per_loop) as u32;
+                self.loops = self.loops.wrapping_add(loops);
+                chunks %= per_loop;
+            }
+        }
+    }
+
+    fn chunk_bytes(&self) -> usize {
+        self.chunk_words * 4
+    }
+
+    /// `target` の先頭から `offset` バイトの位置から末尾まで読み出す回数
+    fn remaining_chunks(&self, target: Target, offset: usize) -> u64 {
+        (target.range().len() - offset).div_ceil(self.chunk_bytes()) as u64
+    }
+
+    fn chunks_per_loop(&self) -> u64 {
+        self.targets
+            .iter()
+            .map(|target| self.remaining_chunks(target, 0))
+            .sum()
+    }
+
+    /// 次の領域の先頭に移る。最初の領域に戻った場合は `true` を返す
+    fn next_target(&mut self) -> bool {
+        self.offset = 0;
+        if let Some(next) = self.targets.after(self.target) {
+            self.target = next;
+            false
+        } else {
+            self.target = self.targets.first();
+            self.loops = self.loops.wrapping_add(1);
+            true
+        }
+    }
+}
+
+/// 終わりのない [`Iterator`]
+impl Iterator for Walker {
+    type Item = Chunk;
+
+    fn next(&mut self) -> Option<Chunk> {
+        let range = self.target.range();
+        let address = range.start + self.offset;
+        let words = self.chunk_words.min((range.end - address) / 4);
+        self.offset += words * 4;
+        if self.offset >= range.len() {
+            self.next_target();
+        }
+        Some(Chunk { address, words })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BSRAM, SRAM3, SRAM4};
+
+    fn chunk(address: usize, words: usize) -> Chunk {
+        Chunk { address, words }
+    }
+
+    #[test]
+    fn walks_targets_in_order_and_counts_loops() {
+        let mut walker = Walker::new(Targets::from(Target::Sram4) | Target::Bsram, 0x1000);
+        for i in 0..4 {
+            assert_eq!(walker.next(), Some(chunk(SRAM4.start + i * 0x4000, 0x1000)));
+        }
+        assert_eq!(walker.next(), Some(chunk(BSRAM.start, 0x400)));
+        assert_eq!(walker.progress().loops, 1);
+        assert_eq!(walker.next(), Some(chunk(SRAM4.start, 0x1000)));
+    }
+
+    #[test]
+    fn last_chunk_does_not_overrun_target() {
+        // 32 KiB の SRAM3 は 400 バイトで割り切れない
+        let mut walker = Walker::new(Target::Sram3.into(), 100);
+        let last = walker.by_ref().take(82).last().unwrap();
+        assert_eq!(last.address, SRAM3.start + 81 * 400);
+        assert_eq!(last.address + last.words * 4, SRAM3.end);
+        assert_eq!(walker.progress().loops, 1);
+        assert_eq!(walker.next(), Some(chunk(SRAM3.start, 100)));
+    }
+
+    #[test]
+    fn progress_counts_scrubbed_bytes_in_current_loop() {
+        let mut walker = Walker::new(Targets::from(Target::Sram3) | Target::Bsram, 0x800);
+        assert_eq!(
+            walker.progress(),
+            Progress {
+                loops: 0,
+                scrubbed_bytes: 0,
+                total_bytes: 0x9000,
+            }
+        );
+        walker.by_ref().take(3).for_each(drop);
+        assert_eq!(walker.progress().scrubbed_bytes, 0x6000);
+        walker.next();
+        assert_eq!(walker.progress().scrubbed_bytes, 0x8000);
+        walker.next();
+        assert_eq!(walker.progress().loops, 1);
+        assert_eq!(walker.progress().scrubbed_bytes, 0);
+    }
+
+    #[test]
+    fn chunk_size_change_keeps_position() {
+        let mut walker = Walker::new(Target::Sram4.into(), 100);
+        walker.next();
+        walker.set_chunk_words(0);
+        assert_eq!(walker.chunk_words(), 1);
+        assert_eq!(walker.next(), Some(chunk(SRAM4.start + 400, 1)));
+        walker.set_chunk_words(usize::MAX);
+        assert_eq!(walker.chunk_words(), MAX_CHUNK_WORDS);
+        // 64 KiB の SRAM4 の残りだけを読み出す
+        assert_eq!(
+            walker.next(),
+            Some(chunk(SRAM4.start + 404, (SRAM4.len() - 404) / 4))
+        );
+    }
+
+    #[test]
+    fn target_change_restarts_loop() {
+        let mut walker = Walker::new(Targets::ALL, 100);
+        walker.next();
+        walker.set_targets(Target::Bsram.into());
+        assert_eq!(walker.progress().scrubbed_bytes, 0);
+        assert_eq!(walker.progress().loops, 0);
+        assert_eq!(walker.next(), Some(chunk(BSRAM.start, 100)));
+    }
+
+    #[test]
+    fn skip_chunks_matches_next() {
+        let targets = Targets::from(Target::Sram3) | Target::Sram4 | Target::Bsram;
+        for chunks in [0, 1, 81, 82, 83, 247, 248, 1000, 123_456] {
+            let mut skipped = Walker::new(targets, 100);
+            skipped.next();
+            skipped.skip_chunks(chunks);
+            let mut stepped = Walker::new(targets, 100);
+            stepped.next();
+            for _ in 0..chunks {
+                stepped.next();
+            }
+            assert_eq!(skipped.progress(), stepped.progress(), "chunks = {chunks}");
+            assert_eq!(skipped.next(), stepped.next(), "chunks = {chunks}");
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! SILS のセンサの値を与える、時刻と値の組からなる時系列
+//!
+//! CSV ファイルの各行は「経過時間（秒）,値」の形式とする。
+#![allow(clippy::must_use_candidate)]
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+    time::Duration,
+};
+
+/// 時刻と値の組からなる時系列
+///
+/// 最初の時刻より前は最初の値を、最後の時刻より後は最後の値を保ち、その間は線形補間する。
+#[derive(Clone)]
+pub struct Trace {
+    points: Vec<(Duration, f64)>,
+}
+
+impl Trace {
+    /// # Panics
+    /// `points` が空の場合は panic する。
+    #[must_use]
+    pub fn new(points: impl IntoIterator<Item = (Duration, f64)>) -> Self {
+        let mut points: Vec<_> = points.into_iter().collect();
+        assert!(!points.is_empty(), "trace must have at least one point");
+        points.sort_by_key(|(at, _)| *at);
+        Self { points }
+    }
+
+    /// 各行が「経過時間（秒）,値」である CSV を読み込む
+    ///
+    /// 数値として解釈できない先頭行はヘッダとして読み飛ばす。
+    ///
+    /// # Errors
+    /// 読み込みに失敗した場合や、不正な行がある場合は [`io::Error`] を返す。
+    pub fn from_csv(reader: impl BufRead) -> io::Result<Self> {
+        let mut points = Vec::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(point) = parse_point(&line) {
+                points.push(point);
+            } else if index > 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid trace at line {}: {line}", index + 1),
+                ));
+            }
+        }
+        if points.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "empty trace"));
+        }
+        Ok(Self::new(points))
+    }
+
+    /// CSV ファイル `path` を [`Trace::from_csv`] で読み込む
+    ///
+    /// # Errors
+    /// 読み込みに失敗した場合や、不正な行がある場合は [`io::Error`] を返す。
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_csv(BufReader::new(File::open(path)?))
+    }
+
+    /// 最初の時刻から `elapsed` 経過した時点の値
+    pub fn value_at(&self, elapsed: Duration) -> f64 {
+        let next = self.points.partition_point(|(at, _)| *at <= elapsed);
+        match (
+            next.checked_sub(1).map(|i| self.points[i]),
+            self.points.get(next),
+        ) {
+            (Some((t0, v0)), Some(&(t1, v1))) => {
+                let ratio = (elapsed - t0).as_secs_f64() / (t1 - t0).as_secs_f64();
+                v0 + (v1 - v0) * ratio
+            }
+            (Some((_, value)), None) | (None, Some(&(_, value))) => value,
+            (None, None) => unreachable!("trace is not empty"),
+        }
+    }
+}
+
+fn parse_point(line: &str) -> Option<(Duration, f64)> {
+    let (time, value) = line.split_once(',')?;
+    let time = Duration::try_from_secs_f64(time.trim().parse().ok()?).ok()?;
+    Some((time, value.trim().parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    #[test]
+    fn interpolates_between_points() {
+        let trace = Trace::new([(secs(3.0), 300.0), (secs(1.0), 100.0)]);
+        assert!((trace.value_at(secs(0.0)) - 100.0).abs() < 1e-9);
+        assert!((trace.value_at(secs(1.0)) - 100.0).abs() < 1e-9);
+        assert!((trace.value_at(secs(1.5)) - 150.0).abs() < 1e-9);
+        assert!((trace.value_at(secs(3.0)) - 300.0).abs() < 1e-9);
+        assert!((trace.value_at(secs(10.0)) - 300.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn single_point_is_constant() {
+        let trace = Trace::new([(secs(1.0), -5.0)]);
+        assert!((trace.value_at(secs(0.0)) + 5.0).abs() < 1e-9);
+        assert!((trace.value_at(secs(2.0)) + 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn from_csv_skips_header() {
+        let csv = "time,value\n0,10\n\n0.5, 20\n";
+        let trace = Trace::from_csv(csv.as_bytes()).unwrap();
+        assert!((trace.value_at(secs(0.25)) - 15.0).abs() < 1e-9);
+        assert!(Trace::from_csv("0,10\nx,20\n".as_bytes()).is_err());
+        assert!(Trace::from_csv("0,10\n-1,20\n".as_bytes()).is_err());
+        assert!(Trace::from_csv("time,value\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    format::{Direction, Record, Source, Writer},
+    ReplayClock,
+};
+
+struct Inner {
+    writer: Writer<BufWriter<File>>,
+    clock: ReplayClock,
+}
+
+/// 送受信したバイト列をキャプチャファイルに記録する
+///
+/// 複製したものは同じファイルに記録する。タイムスタンプは記録した時点の [`ReplayClock`] の時刻とする。
+#[derive(Clone)]
+pub struct Capture {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Capture {
+    /// `path` にキャプチャファイルを作成する
+    ///
+    /// 再生する際と同じ `clock` で記録することで、同じ時刻に再生できる。
+    ///
+    /// # Errors
+    /// ファイルの作成に失敗した場合は [`io::Error`] を返す。
+    pub fn create(path: impl AsRef<Path>, clock: ReplayClock) -> io::Result<Self> {
+        let writer = Writer::new(BufWriter::new(File::create(path)?))?;
+        Ok(Self {
+            inner: Arc::new(Mutex::new(Inner { writer, clock })),
+        })
+    }
+
+    /// `data` を記録する
+    ///
+    /// テストが失敗した場合にも記録が残るよう、毎回ファイルに書き出す。
+    /// 書き込みに失敗しても通信は続けられるよう、エラーは標準エラー出力に表示するのみとする。
+    ///
+    /// # Panics
+    /// 他のスレッドが記録中に panic していた場合は panic する。
+    pub fn record(&self, source: Source, channel: u8, direction: Direction, data: &[u8]) {
+        let mut inner = self.inner.lock().unwrap();
+        let record = Record {
+            timestamp: (inner.clock)(),
+            source,
+            channel,
+            direction,
+            data: data.to_vec(),
+        };
+        let result = inner
+            .writer
+            .write(&record)
+            .and_then(|()| Ok(inner.writer.flush()?));
+        if let Err(e) = result {
+            eprintln!("failed to write capture: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use super::*;
+    use crate::{Reader, Replay};
+
+    #[test]
+    fn records_replay_clock_time() {
+        let path = std::env::temp_dir().join("traffic-capture-clock-test.bin");
+        let clock: ReplayClock = Arc::new(|| Duration::from_millis(300));
+        let capture = Capture::create(&path, clock.clone()).unwrap();
+        capture.record(Source::Uart, 1, Direction::Rx, &[0xAB]);
+        drop(capture);
+
+        let reader = Reader::new(fs::File::open(&path).unwrap()).unwrap();
+        let records = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(records[0].timestamp, Duration::from_millis(300));
+        // 同じ時刻で再生すればすぐに受信できる
+        let replay = Replay::open(&path, clock).unwrap();
+        assert_eq!(replay.pop(Source::Uart, 1), Some(vec![0xAB]));
+        fs::remove_file(path).unwrap();
+    }
+}
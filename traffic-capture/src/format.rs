@@ -0,0 +1,273 @@
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    time::Duration,
+};
+
+/// キャプチャファイルの先頭に置くバイト列
+pub const MAGIC: [u8; 8] = *b"MNZCAP\x00\x01";
+
+// レコードのヘッダのバイト数
+const RECORD_HEADER_SIZE: usize = 16;
+
+/// 記録した通信の種類
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Source {
+    Uart,
+    Ccsds,
+}
+
+/// C2A から見た通信の向き
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+    Tx,
+    Rx,
+}
+
+/// 送受信したバイト列 1 つ分の記録
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Record {
+    /// 記録した時点の [`ReplayClock`](crate::ReplayClock) の時刻
+    ///
+    /// SILS では C2A のマスタークロックの時刻であり、キャプチャを開始してからの実時間ではない。
+    pub timestamp: Duration,
+    pub source: Source,
+    pub channel: u8,
+    pub direction: Direction,
+    pub data: Vec<u8>,
+}
+
+/// キャプチャファイルの読み書きに失敗した理由
+#[derive(Debug)]
+pub enum FormatError {
+    Io(io::Error),
+    /// 先頭が [`MAGIC`] でない
+    InvalidMagic,
+    /// [`Source`] または [`Direction`] の値が不正か、データがレコードの長さより短い
+    InvalidRecord,
+    /// データが長すぎて記録できない
+    TooLong(usize),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::InvalidMagic => write!(f, "not a capture file"),
+            Self::InvalidRecord => write!(f, "invalid record"),
+            Self::TooLong(len) => write!(f, "{len} bytes record is too long"),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+impl From<io::Error> for FormatError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// キャプチャファイルを書き込む
+pub struct Writer<W> {
+    inner: W,
+}
+
+impl<W: Write> Writer<W> {
+    /// `inner` に [`MAGIC`] を書き込む
+    ///
+    /// # Errors
+    /// 書き込みに失敗した場合は [`io::Error`] を返す。
+    pub fn new(mut inner: W) -> io::Result<Self> {
+        inner.write_all(&MAGIC)?;
+        Ok(Self { inner })
+    }
+
+    /// # Errors
+    /// 書き込みに失敗した場合は [`FormatError`] を返す。
+    pub fn write(&mut self, record: &Record) -> Result<(), FormatError> {
+        let len = u32::try_from(record.data.len())
+            .map_err(|_| FormatError::TooLong(record.data.len()))?;
+        // 584 年を超えるキャプチャは想定しない
+        #[allow(clippy::cast_possible_truncation)]
+        let timestamp = record.timestamp.as_nanos() as u64;
+        let mut header = [0; RECORD_HEADER_SIZE];
+        header[..8].copy_from_slice(&timestamp.to_le_bytes());
+        header[8] = match record.source {
+            Source::Uart => 0,
+            Source::Ccsds => 1,
+        };
+        header[9] = record.channel;
+        header[10] = match record.direction {
+            Direction::Tx => 0,
+            Direction::Rx => 1,
+        };
+        header[12..].copy_from_slice(&len.to_le_bytes());
+        self.inner.write_all(&header)?;
+        self.inner.write_all(&record.data)?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// 書き込みに失敗した場合は [`io::Error`] を返す。
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// キャプチャファイルを先頭から読み出す
+///
+/// レコードを順に返すイテレータとして使う。
+pub struct Reader<R> {
+    inner: R,
+}
+
+impl<R: Read> Reader<R> {
+    /// `inner` の先頭の [`MAGIC`] を検証する
+    ///
+    /// # Errors
+    /// 読み出しに失敗した場合や、キャプチャファイルでない場合は [`FormatError`] を返す。
+    pub fn new(mut inner: R) -> Result<Self, FormatError> {
+        let mut magic = [0; MAGIC.len()];
+        inner.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(FormatError::InvalidMagic);
+        }
+        Ok(Self { inner })
+    }
+
+    /// 次のレコードを読み出す。ファイルの終端では `None` を返す
+    ///
+    /// # Errors
+    /// 読み出しに失敗した場合や、レコードが不正な場合は [`FormatError`] を返す。
+    pub fn read(&mut self) -> Result<Option<Record>, FormatError> {
+        let mut header = [0; RECORD_HEADER_SIZE];
+        // レコードの境界で終わっていれば正常な終端とする
+        let mut filled = 0;
+        while filled < header.len() {
+            match self.inner.read(&mut header[filled..])? {
+                0 if filled == 0 => return Ok(None),
+                0 => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                n => filled += n,
+            }
+        }
+        let [t0, t1, t2, t3, t4, t5, t6, t7, source, channel, direction, _, l0, l1, l2, l3] =
+            header;
+        let timestamp = Duration::from_nanos(u64::from_le_bytes([t0, t1, t2, t3, t4, t5, t6, t7]));
+        let source = match source {
+            0 => Source::Uart,
+            1 => Source::Ccsds,
+            _ => return Err(FormatError::InvalidRecord),
+        };
+        let direction = match direction {
+            0 => Direction::Tx,
+            1 => Direction::Rx,
+            _ => return Err(FormatError::InvalidRecord),
+        };
+        let len = u32::from_le_bytes([l0, l1, l2, l3]);
+        // 壊れたファイルの長さで確保しないよう、実際に読み出せた分だけ確保する
+        let mut data = Vec::new();
+        self.inner
+            .by_ref()
+            .take(u64::from(len))
+            .read_to_end(&mut data)?;
+        if data.len() != len as usize {
+            return Err(FormatError::InvalidRecord);
+        }
+        Ok(Some(Record {
+            timestamp,
+            source,
+            channel,
+            direction,
+            data,
+        }))
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Record, FormatError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records() -> Vec<Record> {
+        vec![
+            Record {
+                timestamp: Duration::from_micros(1500),
+                source: Source::Uart,
+                channel: 2,
+                direction: Direction::Tx,
+                data: vec![1, 2, 3],
+            },
+            Record {
+                timestamp: Duration::from_secs(3),
+                source: Source::Ccsds,
+                channel: 0,
+                direction: Direction::Rx,
+                data: vec![],
+            },
+        ]
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut writer = Writer::new(Vec::new()).unwrap();
+        for record in records() {
+            writer.write(&record).unwrap();
+        }
+        let bytes = writer.into_inner();
+        assert_eq!(bytes.len(), MAGIC.len() + 2 * RECORD_HEADER_SIZE + 3);
+        let reader = Reader::new(bytes.as_slice()).unwrap();
+        let read: Vec<_> = reader.collect::<Result<_, _>>().unwrap();
+        assert_eq!(read, records());
+    }
+
+    #[test]
+    fn rejects_non_capture_file() {
+        assert!(matches!(
+            Reader::new(&b"not a capture"[..]),
+            Err(FormatError::InvalidMagic)
+        ));
+    }
+
+    #[test]
+    fn truncated_record_is_an_error() {
+        let mut writer = Writer::new(Vec::new()).unwrap();
+        writer.write(&records()[0]).unwrap();
+        let bytes = writer.into_inner();
+        for len in MAGIC.len() + 1..bytes.len() {
+            let mut reader = Reader::new(&bytes[..len]).unwrap();
+            assert!(reader.read().is_err(), "len {len}");
+        }
+    }
+
+    #[test]
+    fn corrupt_length_does_not_allocate() {
+        let mut writer = Writer::new(Vec::new()).unwrap();
+        writer.write(&records()[0]).unwrap();
+        let mut bytes = writer.into_inner();
+        bytes[MAGIC.len() + 12..MAGIC.len() + 16].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut reader = Reader::new(bytes.as_slice()).unwrap();
+        assert!(matches!(reader.read(), Err(FormatError::InvalidRecord)));
+    }
+
+    #[test]
+    fn rejects_unknown_source() {
+        let mut writer = Writer::new(Vec::new()).unwrap();
+        writer.write(&records()[0]).unwrap();
+        let mut bytes = writer.into_inner();
+        bytes[MAGIC.len() + 8] = 7;
+        let mut reader = Reader::new(bytes.as_slice()).unwrap();
+        assert!(matches!(reader.read(), Err(FormatError::InvalidRecord)));
+    }
+}
@@ -0,0 +1,23 @@
+//! SILS の UART・CCSDS の通信を記録するキャプチャファイルと、その再生
+//!
+//! ファイルは 8 バイトの [`MAGIC`] に続けてレコードを並べたもので、各レコードは次の形式を持つ。
+//! 整数はすべてリトルエンディアンとする。
+//!
+//! | オフセット | サイズ | 内容                                         |
+//! |-----------:|-------:|----------------------------------------------|
+//! |          0 |      8 | [`ReplayClock`] の時刻（ns）                 |
+//! |          8 |      1 | [`Source`]（0: UART, 1: CCSDS）              |
+//! |          9 |      1 | チャネル番号（CCSDS は 0）                   |
+//! |         10 |      1 | [`Direction`]（0: C2A の送信, 1: C2A の受信）|
+//! |         11 |      1 | 予約（0）                                    |
+//! |         12 |      4 | データのバイト数 `n`                         |
+//! |         16 |    `n` | データ                                       |
+#![allow(clippy::must_use_candidate)]
+
+mod capture;
+mod format;
+mod replay;
+
+pub use capture::Capture;
+pub use format::{Direction, FormatError, Reader, Record, Source, Writer, MAGIC};
+pub use replay::{Replay, ReplayClock};
@@ -0,0 +1,140 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::BufReader,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::format::{Direction, FormatError, Reader, Record, Source};
+
+/// 記録と再生の基準とする時刻
+///
+/// SILS では C2A のマスタークロックから求めた時刻を返すことで、実時間によらず同じ周期に同じ入力を与えられる。
+pub type ReplayClock = Arc<dyn Fn() -> Duration + Send + Sync>;
+
+/// 記録した C2A の受信を、[`ReplayClock`] の時刻に合わせて再生する
+///
+/// 各レコードは、タイムスタンプの時刻を過ぎてから最初に C2A が受信しようとしたときに受信させる。
+/// 複製したものは同じ記録を共有するため、1 つのキャプチャファイルを複数の dev HAL で再生できる。
+#[derive(Clone)]
+pub struct Replay {
+    records: Arc<Mutex<VecDeque<Record>>>,
+    clock: ReplayClock,
+}
+
+impl Replay {
+    /// `records` のうち C2A が受信したものを再生する
+    pub fn new(records: impl IntoIterator<Item = Record>, clock: ReplayClock) -> Self {
+        let mut records: Vec<_> = records
+            .into_iter()
+            .filter(|record| record.direction == Direction::Rx)
+            .collect();
+        // 同時刻のレコードは記録した順に再生する
+        records.sort_by_key(|record| record.timestamp);
+        Self {
+            records: Arc::new(Mutex::new(records.into())),
+            clock,
+        }
+    }
+
+    /// キャプチャファイル `path` を読み込む
+    ///
+    /// # Errors
+    /// 読み込みに失敗した場合や、キャプチャファイルが不正な場合は [`FormatError`] を返す。
+    pub fn open(path: impl AsRef<Path>, clock: ReplayClock) -> Result<Self, FormatError> {
+        let reader = Reader::new(BufReader::new(File::open(path)?))?;
+        let records = reader.collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::new(records, clock))
+    }
+
+    /// `source` の `channel` で、現在の時刻までに受信すべきバイト列を 1 つ取り出す
+    ///
+    /// # Panics
+    /// 他のスレッドが取り出し中に panic していた場合は panic する。
+    pub fn pop(&self, source: Source, channel: u8) -> Option<Vec<u8>> {
+        let now = (self.clock)();
+        let mut records = self.records.lock().unwrap();
+        let index = records
+            .iter()
+            .take_while(|record| record.timestamp <= now)
+            .position(|record| record.source == source && record.channel == channel)?;
+        records.remove(index).map(|record| record.data)
+    }
+
+    /// 全てのレコードを再生し終えたかどうか
+    ///
+    /// # Panics
+    /// 他のスレッドが取り出し中に panic していた場合は panic する。
+    pub fn is_finished(&self) -> bool {
+        self.records.lock().unwrap().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+
+    fn record(millis: u64, source: Source, channel: u8, direction: Direction, data: u8) -> Record {
+        Record {
+            timestamp: Duration::from_millis(millis),
+            source,
+            channel,
+            direction,
+            data: vec![data],
+        }
+    }
+
+    fn manual_clock() -> (Arc<AtomicU64>, ReplayClock) {
+        let millis = Arc::new(AtomicU64::new(0));
+        let clock = {
+            let millis = millis.clone();
+            Arc::new(move || Duration::from_millis(millis.load(Ordering::SeqCst)))
+        };
+        (millis, clock)
+    }
+
+    #[test]
+    fn follows_clock() {
+        let (millis, clock) = manual_clock();
+        let replay = Replay::new(
+            [
+                record(200, Source::Ccsds, 0, Direction::Rx, 2),
+                record(100, Source::Ccsds, 0, Direction::Rx, 1),
+                record(100, Source::Ccsds, 0, Direction::Tx, 9),
+            ],
+            clock,
+        );
+        assert_eq!(replay.pop(Source::Ccsds, 0), None);
+        millis.store(150, Ordering::SeqCst);
+        assert_eq!(replay.pop(Source::Ccsds, 0), Some(vec![1]));
+        assert_eq!(replay.pop(Source::Ccsds, 0), None);
+        millis.store(1000, Ordering::SeqCst);
+        assert_eq!(replay.pop(Source::Ccsds, 0), Some(vec![2]));
+        // C2A の送信は再生しない
+        assert_eq!(replay.pop(Source::Ccsds, 0), None);
+        assert!(replay.is_finished());
+    }
+
+    #[test]
+    fn channels_are_independent() {
+        let (millis, clock) = manual_clock();
+        let replay = Replay::new(
+            [
+                record(100, Source::Uart, 1, Direction::Rx, 1),
+                record(200, Source::Uart, 2, Direction::Rx, 2),
+                record(300, Source::Uart, 1, Direction::Rx, 3),
+            ],
+            clock,
+        );
+        millis.store(250, Ordering::SeqCst);
+        assert_eq!(replay.pop(Source::Uart, 2), Some(vec![2]));
+        assert_eq!(replay.pop(Source::Ccsds, 0), None);
+        assert_eq!(replay.pop(Source::Uart, 1), Some(vec![1]));
+        assert_eq!(replay.pop(Source::Uart, 1), None);
+        assert!(!replay.is_finished());
+    }
+}
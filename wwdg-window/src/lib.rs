@@ -0,0 +1,111 @@
+#![cfg_attr(not(test), no_std)]
+#![allow(clippy::must_use_candidate)]
+
+use core::time::Duration;
+
+// WWDG のカウンタは 0x40 を下回るとリセットする
+const COUNTER_MIN: u8 = 0x40;
+const COUNTER_MAX: u8 = 0x7F;
+// WWDG のカウンタは PCLK3 を 4096 << WDGTB 分周したクロックで減る
+const PRESCALER: u64 = 4096;
+const WDGTB_MAX: u8 = 7;
+
+/// WWDG のカウンタを減らすクロックの周期で数えたウィンドウ
+///
+/// 実機の WWDG に書き込む値で、dev HAL も同じ値で設定できるウィンドウを判定する。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Config {
+    pub wdgtb: u8,
+    /// クリアした時に書き込むカウンタの値
+    pub counter: u8,
+    /// カウンタがこの値以下になるまでクリアしてはならない
+    pub window: u8,
+}
+
+impl Config {
+    /// 最も長い時間で数え、いつでもクリアできる設定
+    pub const LONGEST: Self = Self {
+        wdgtb: WDGTB_MAX,
+        counter: COUNTER_MAX,
+        window: COUNTER_MAX,
+    };
+
+    /// 前回のクリアから `min_time` ミリ秒以上 `time` ミリ秒未満の間にクリアしなければリセットする設定
+    ///
+    /// `pclk3` は PCLK3 の周波数（Hz）。最も細かく数えられる分周比を選ぶ。
+    /// `time` が長すぎる場合や、`min_time` と `time` の間にカウンタが減らない場合は `None` を返す。
+    pub fn new(pclk3: u32, min_time: u32, time: u32) -> Option<Self> {
+        let pclk3 = u64::from(pclk3);
+        (0..=WDGTB_MAX).find_map(|wdgtb| {
+            let tick = PRESCALER << wdgtb;
+            // リセットするまでにカウンタが減る回数。`time` より後にリセットしないよう切り捨てる
+            let ticks = u64::from(time) * pclk3 / 1000 / tick;
+            if ticks == 0 || ticks > u64::from(COUNTER_MAX - COUNTER_MIN + 1) {
+                return None;
+            }
+            // クリアできるようになるまでにカウンタが減る回数。`min_time` より前にクリアできないよう切り上げる
+            let min_ticks = (u64::from(min_time) * pclk3 / 1000).div_ceil(tick);
+            if min_ticks >= ticks {
+                return None;
+            }
+            #[allow(clippy::cast_possible_truncation)]
+            let counter = COUNTER_MIN + (ticks - 1) as u8;
+            #[allow(clippy::cast_possible_truncation)]
+            let window = counter - min_ticks as u8;
+            Some(Self {
+                wdgtb,
+                counter,
+                window,
+            })
+        })
+    }
+
+    /// クリアしてからリセットするまでの時間
+    pub fn timeout(self, pclk3: u32) -> Duration {
+        self.ticks(pclk3, self.counter - COUNTER_MIN + 1)
+    }
+
+    /// クリアしてからクリアできるようになるまでの時間
+    pub fn min_time(self, pclk3: u32) -> Duration {
+        self.ticks(pclk3, self.counter - self.window)
+    }
+
+    fn ticks(self, pclk3: u32, ticks: u8) -> Duration {
+        let cycles = (PRESCALER << self.wdgtb) * u64::from(ticks);
+        Duration::from_nanos(cycles * 1_000_000_000 / u64::from(pclk3))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PCLK3: u32 = 100_000_000;
+
+    #[test]
+    fn picks_finest_prescaler() {
+        // 4096 分周では 1 カウント 40.96 µs なので、64 カウントで約 2.6 ミリ秒まで数えられる
+        assert_eq!(
+            Config::new(PCLK3, 1, 2),
+            Some(Config {
+                wdgtb: 0,
+                counter: 0x40 + 47,
+                window: 0x40 + 47 - 25,
+            })
+        );
+        let config = Config::new(PCLK3, 100, 300).unwrap();
+        assert_eq!(config.wdgtb, 7);
+        assert!(config.timeout(PCLK3) <= Duration::from_millis(300));
+        assert!(config.min_time(PCLK3) >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn rejects_unsupported_windows() {
+        // PCLK3 が 100 MHz の場合、最も長くても約 335 ミリ秒
+        assert!(Config::LONGEST.timeout(PCLK3) < Duration::from_millis(336));
+        assert_eq!(Config::new(PCLK3, 1, 400), None);
+        // 300 ミリ秒を数えられる分周比では 1 カウントが約 5 ミリ秒なので、幅 1 ミリ秒のウィンドウは設定できない
+        assert_eq!(Config::new(PCLK3, 299, 300), None);
+        assert_eq!(Config::new(PCLK3, 0, 0), None);
+    }
+}